@@ -0,0 +1,6 @@
+-- Trigram matching for location autocomplete
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX idx_properties_city_trgm ON properties USING GIN (city gin_trgm_ops);
+CREATE INDEX idx_properties_country_trgm ON properties USING GIN (country gin_trgm_ops);
+CREATE INDEX idx_properties_location_trgm ON properties USING GIN (location gin_trgm_ops);
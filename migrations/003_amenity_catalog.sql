@@ -0,0 +1,87 @@
+-- Admin-managed amenity catalog
+CREATE TABLE amenities (
+    key VARCHAR(50) PRIMARY KEY,
+    label VARCHAR(100) NOT NULL,
+    category VARCHAR(50) NOT NULL,
+    icon VARCHAR(100),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_amenities_category ON amenities(category);
+
+CREATE TRIGGER update_amenities_updated_at BEFORE UPDATE ON amenities
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+INSERT INTO amenities (key, label, category, icon) VALUES
+    ('wifi', 'Wi-Fi', 'essentials', 'wifi'),
+    ('kitchen', 'Kitchen', 'essentials', 'utensils'),
+    ('washer', 'Washing machine', 'essentials', 'washing-machine'),
+    ('dryer', 'Dryer', 'essentials', 'wind'),
+    ('heating', 'Heating', 'essentials', 'thermometer'),
+    ('air_conditioning', 'Air conditioning', 'essentials', 'snowflake'),
+    ('tv', 'TV', 'entertainment', 'tv'),
+    ('workspace', 'Dedicated workspace', 'features', 'briefcase'),
+    ('parking', 'Free parking', 'features', 'car'),
+    ('pool', 'Pool', 'features', 'waves'),
+    ('hot_tub', 'Hot tub', 'features', 'bath'),
+    ('gym', 'Gym', 'features', 'dumbbell'),
+    ('elevator', 'Elevator', 'features', 'arrow-up-down'),
+    ('breakfast', 'Breakfast', 'services', 'coffee'),
+    ('smoke_alarm', 'Smoke alarm', 'safety', 'bell'),
+    ('carbon_monoxide_alarm', 'Carbon monoxide alarm', 'safety', 'alert-triangle'),
+    ('first_aid_kit', 'First aid kit', 'safety', 'first-aid'),
+    ('fire_extinguisher', 'Fire extinguisher', 'safety', 'fire-extinguisher');
+
+-- Map existing free-form values onto catalog keys.
+-- Values are compared on lowercase alphanumerics only, so "WiFi", "wifi" and "Wi-Fi" all match.
+CREATE TEMP TABLE amenity_aliases (
+    alias TEXT PRIMARY KEY,
+    amenity_key VARCHAR(50) NOT NULL
+);
+
+INSERT INTO amenity_aliases (alias, amenity_key)
+SELECT regexp_replace(lower(key), '[^a-z0-9]', '', 'g'), key FROM amenities
+ON CONFLICT DO NOTHING;
+
+INSERT INTO amenity_aliases (alias, amenity_key)
+SELECT regexp_replace(lower(label), '[^a-z0-9]', '', 'g'), key FROM amenities
+ON CONFLICT DO NOTHING;
+
+INSERT INTO amenity_aliases (alias, amenity_key) VALUES
+    ('internet', 'wifi'),
+    ('wirelessinternet', 'wifi'),
+    ('washingmachine', 'washer'),
+    ('ac', 'air_conditioning'),
+    ('aircon', 'air_conditioning'),
+    ('television', 'tv'),
+    ('swimmingpool', 'pool'),
+    ('jacuzzi', 'hot_tub'),
+    ('freeparking', 'parking')
+ON CONFLICT DO NOTHING;
+
+-- Keep values that could not be mapped so they can be reviewed by an admin
+CREATE TABLE unmapped_amenities (
+    property_id UUID NOT NULL REFERENCES properties(id) ON DELETE CASCADE,
+    value TEXT NOT NULL,
+    PRIMARY KEY (property_id, value)
+);
+
+INSERT INTO unmapped_amenities (property_id, value)
+SELECT DISTINCT p.id, a.value
+FROM properties p
+CROSS JOIN LATERAL unnest(p.amenities) AS a(value)
+LEFT JOIN amenity_aliases al ON al.alias = regexp_replace(lower(a.value), '[^a-z0-9]', '', 'g')
+WHERE al.amenity_key IS NULL;
+
+UPDATE properties p
+SET amenities = COALESCE((
+    SELECT array_agg(DISTINCT al.amenity_key ORDER BY al.amenity_key)
+    FROM unnest(p.amenities) AS a(value)
+    JOIN amenity_aliases al ON al.alias = regexp_replace(lower(a.value), '[^a-z0-9]', '', 'g')
+), '{}')
+WHERE p.amenities <> '{}';
+
+DROP TABLE amenity_aliases;
+
+CREATE INDEX idx_properties_amenities ON properties USING GIN (amenities);
@@ -0,0 +1,9 @@
+-- Rating aggregate used for sort=rating
+ALTER TABLE properties
+    ADD COLUMN average_rating DOUBLE PRECISION,
+    ADD COLUMN review_count INTEGER NOT NULL DEFAULT 0;
+
+-- Keyset pagination indexes, one per sort order (id breaks ties)
+CREATE INDEX idx_properties_newest ON properties (created_at DESC, id DESC) WHERE is_active = true;
+CREATE INDEX idx_properties_price_id ON properties (price_per_night, id) WHERE is_active = true;
+CREATE INDEX idx_properties_rating ON properties ((COALESCE(average_rating, 0)) DESC, id DESC) WHERE is_active = true;
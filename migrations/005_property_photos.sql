@@ -0,0 +1,29 @@
+-- Structured photo records replace the free-form properties.images array
+CREATE TABLE property_photos (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    property_id UUID NOT NULL REFERENCES properties(id) ON DELETE CASCADE,
+    storage_key VARCHAR(255), -- key prefix in the blob store
+    external_url TEXT,        -- legacy image URLs carried over from properties.images
+    content_type VARCHAR(50),
+    width INTEGER,
+    height INTEGER,
+    size_bytes BIGINT NOT NULL DEFAULT 0,
+    caption VARCHAR(500),
+    position INTEGER NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CHECK (storage_key IS NOT NULL OR external_url IS NOT NULL)
+);
+
+CREATE INDEX idx_property_photos_property_position ON property_photos(property_id, position);
+
+CREATE TRIGGER update_property_photos_updated_at BEFORE UPDATE ON property_photos
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+INSERT INTO property_photos (property_id, external_url, position)
+SELECT p.id, img.url, img.ord - 1
+FROM properties p
+CROSS JOIN LATERAL unnest(p.images) WITH ORDINALITY AS img(url, ord)
+WHERE img.url <> '';
+
+ALTER TABLE properties DROP COLUMN images;
@@ -0,0 +1,15 @@
+-- Listing lifecycle: draft -> pending_review -> published / rejected
+CREATE TYPE listing_status AS ENUM ('draft', 'pending_review', 'published', 'rejected');
+
+ALTER TABLE properties
+    ADD COLUMN status listing_status NOT NULL DEFAULT 'draft',
+    ADD COLUMN rejection_reason TEXT,
+    ADD COLUMN submitted_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN reviewed_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN reviewed_by UUID REFERENCES users(id) ON DELETE SET NULL;
+
+-- Listings that were already live stay live
+UPDATE properties SET status = 'published', reviewed_at = NOW();
+
+CREATE INDEX idx_properties_status ON properties(status);
+CREATE INDEX idx_properties_review_queue ON properties(submitted_at) WHERE status = 'pending_review';
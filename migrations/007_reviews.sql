@@ -0,0 +1,24 @@
+-- Guest reviews of completed stays, one per booking
+CREATE TABLE reviews (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    booking_id UUID NOT NULL UNIQUE REFERENCES bookings(id) ON DELETE CASCADE,
+    property_id UUID NOT NULL REFERENCES properties(id) ON DELETE CASCADE,
+    guest_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    overall_rating SMALLINT NOT NULL CHECK (overall_rating BETWEEN 1 AND 5),
+    cleanliness_rating SMALLINT NOT NULL CHECK (cleanliness_rating BETWEEN 1 AND 5),
+    accuracy_rating SMALLINT NOT NULL CHECK (accuracy_rating BETWEEN 1 AND 5),
+    location_rating SMALLINT NOT NULL CHECK (location_rating BETWEEN 1 AND 5),
+    value_rating SMALLINT NOT NULL CHECK (value_rating BETWEEN 1 AND 5),
+    comment TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_reviews_property_created ON reviews(property_id, created_at DESC, id DESC);
+CREATE INDEX idx_reviews_guest_id ON reviews(guest_id);
+
+CREATE TRIGGER update_reviews_updated_at BEFORE UPDATE ON reviews
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- Stays whose check-out has passed are complete and can be reviewed
+CREATE INDEX idx_bookings_approved_check_out ON bookings(check_out_date) WHERE status = 'approved';
@@ -0,0 +1,35 @@
+-- Hosts review guests; both sides stay hidden until both are in or the review window ends
+CREATE TABLE guest_reviews (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    booking_id UUID NOT NULL UNIQUE REFERENCES bookings(id) ON DELETE CASCADE,
+    guest_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    host_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    overall_rating SMALLINT NOT NULL CHECK (overall_rating BETWEEN 1 AND 5),
+    cleanliness_rating SMALLINT NOT NULL CHECK (cleanliness_rating BETWEEN 1 AND 5),
+    communication_rating SMALLINT NOT NULL CHECK (communication_rating BETWEEN 1 AND 5),
+    house_rules_rating SMALLINT NOT NULL CHECK (house_rules_rating BETWEEN 1 AND 5),
+    comment TEXT,
+    published_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_guest_reviews_guest_published ON guest_reviews(guest_id) WHERE published_at IS NOT NULL;
+CREATE INDEX idx_guest_reviews_unpublished ON guest_reviews(booking_id) WHERE published_at IS NULL;
+
+CREATE TRIGGER update_guest_reviews_updated_at BEFORE UPDATE ON guest_reviews
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- Guest reviews become public on publication; hosts may reply once
+ALTER TABLE reviews
+    ADD COLUMN published_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN host_reply TEXT,
+    ADD COLUMN host_replied_at TIMESTAMP WITH TIME ZONE;
+
+-- Reviews written before blind reviewing were already public
+UPDATE reviews SET published_at = created_at;
+
+DROP INDEX idx_reviews_property_created;
+CREATE INDEX idx_reviews_property_published ON reviews(property_id, created_at DESC, id DESC)
+    WHERE published_at IS NOT NULL;
+CREATE INDEX idx_reviews_unpublished ON reviews(booking_id) WHERE published_at IS NULL;
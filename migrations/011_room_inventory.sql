@@ -0,0 +1,83 @@
+-- Bookable unit types for hotels and hostels: private rooms and dorm beds
+CREATE TYPE unit_kind AS ENUM ('room', 'dorm_bed');
+
+CREATE TABLE room_types (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    property_id UUID NOT NULL REFERENCES properties(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    description TEXT,
+    kind unit_kind NOT NULL DEFAULT 'room',
+    max_guests INTEGER NOT NULL CHECK (max_guests > 0), -- per unit
+    price_per_night INTEGER NOT NULL CHECK (price_per_night > 0), -- per unit, in cents
+    total_units INTEGER NOT NULL CHECK (total_units > 0),
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_room_types_property_id ON room_types(property_id) WHERE is_active = true;
+
+CREATE TRIGGER update_room_types_updated_at BEFORE UPDATE ON room_types
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- Per-night overrides of sellable units and price; nights without a row use the room type defaults
+CREATE TABLE room_type_inventory (
+    room_type_id UUID NOT NULL REFERENCES room_types(id) ON DELETE CASCADE,
+    date DATE NOT NULL,
+    units_available INTEGER CHECK (units_available >= 0),
+    price_override INTEGER CHECK (price_override > 0), -- in cents
+    PRIMARY KEY (room_type_id, date)
+);
+
+-- Bookings of a room type reserve a number of its units; other bookings take the whole property
+ALTER TABLE bookings
+    ADD COLUMN room_type_id UUID REFERENCES room_types(id),
+    ADD COLUMN units INTEGER NOT NULL DEFAULT 1 CHECK (units > 0);
+
+CREATE INDEX idx_bookings_room_type_dates ON bookings(room_type_id, check_in_date, check_out_date)
+    WHERE room_type_id IS NOT NULL AND status IN ('pending', 'approved');
+
+-- Units of a room type free on every night of [check_in, check_out)
+CREATE FUNCTION room_type_available_units(p_room_type_id UUID, p_check_in DATE, p_check_out DATE)
+RETURNS INTEGER AS $$
+    SELECT COALESCE(MIN(
+        COALESCE(inv.units_available, rt.total_units) - COALESCE((
+            SELECT SUM(b.units)
+            FROM bookings b
+            WHERE b.room_type_id = rt.id
+            AND b.status IN ('pending', 'approved')
+            AND b.check_in_date <= night.date
+            AND b.check_out_date > night.date
+        ), 0)
+    ), 0)::INTEGER
+    FROM room_types rt
+    CROSS JOIN generate_series(p_check_in, p_check_out - 1, INTERVAL '1 day') AS night(date)
+    LEFT JOIN room_type_inventory inv ON inv.room_type_id = rt.id AND inv.date = night.date::DATE
+    WHERE rt.id = p_room_type_id AND rt.is_active = true;
+$$ LANGUAGE SQL STABLE;
+
+-- Properties with room types are available while any room type has a free unit;
+-- others while no whole-property booking overlaps the stay
+CREATE FUNCTION property_is_available(p_property_id UUID, p_check_in DATE, p_check_out DATE)
+RETURNS BOOLEAN AS $$
+    SELECT CASE
+        WHEN EXISTS (SELECT 1 FROM room_types WHERE property_id = p_property_id AND is_active = true) THEN
+            EXISTS (
+                SELECT 1 FROM room_types
+                WHERE property_id = p_property_id AND is_active = true
+                AND room_type_available_units(id, p_check_in, p_check_out) > 0
+            )
+        ELSE
+            NOT EXISTS (
+                SELECT 1 FROM bookings
+                WHERE property_id = p_property_id
+                AND room_type_id IS NULL
+                AND status IN ('pending', 'approved')
+                AND (
+                    (check_in_date <= p_check_in AND check_out_date >= p_check_in) OR
+                    (check_in_date <= p_check_out AND check_out_date >= p_check_out) OR
+                    (check_in_date >= p_check_in AND check_out_date <= p_check_out)
+                )
+            )
+    END;
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,11 @@
+-- More kinds of stays
+ALTER TYPE property_type ADD VALUE IF NOT EXISTS 'house';
+ALTER TYPE property_type ADD VALUE IF NOT EXISTS 'villa';
+ALTER TYPE property_type ADD VALUE IF NOT EXISTS 'cabin';
+ALTER TYPE property_type ADD VALUE IF NOT EXISTS 'guesthouse';
+ALTER TYPE property_type ADD VALUE IF NOT EXISTS 'room_in_home';
+
+-- Type-specific details (e.g. floor for apartments, star rating for hotels), validated by the API per type
+ALTER TABLE properties ADD COLUMN attributes JSONB NOT NULL DEFAULT '{}';
+
+CREATE INDEX idx_properties_attributes ON properties USING GIN (attributes jsonb_path_ops);
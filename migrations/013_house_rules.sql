@@ -0,0 +1,19 @@
+-- Structured house rules and check-in/check-out times
+ALTER TABLE properties
+    ADD COLUMN check_in_from TIME NOT NULL DEFAULT '15:00',
+    ADD COLUMN check_in_until TIME, -- NULL means no latest check-in time
+    ADD COLUMN check_out_until TIME NOT NULL DEFAULT '11:00',
+    ADD COLUMN pets_allowed BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN smoking_allowed BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN events_allowed BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN children_allowed BOOLEAN NOT NULL DEFAULT true,
+    ADD COLUMN quiet_hours_start TIME,
+    ADD COLUMN quiet_hours_end TIME,
+    ADD COLUMN additional_rules TEXT,
+    ADD CONSTRAINT properties_quiet_hours_check
+        CHECK ((quiet_hours_start IS NULL) = (quiet_hours_end IS NULL));
+
+-- The rules the guest accepted when booking, kept as they were at that moment
+ALTER TABLE bookings
+    ADD COLUMN accepted_house_rules JSONB,
+    ADD COLUMN house_rules_accepted_at TIMESTAMP WITH TIME ZONE;
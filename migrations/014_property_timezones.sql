@@ -0,0 +1,3 @@
+-- IANA timezone of the property; booking rules, cancellations and completion use its local time.
+-- Existing listings are filled in from their coordinates when the server starts.
+ALTER TABLE properties ADD COLUMN timezone VARCHAR(64);
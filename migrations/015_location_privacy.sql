@@ -0,0 +1,36 @@
+-- Public location of a listing: the real coordinates moved 200-800 m in a random
+-- direction. Picked once per location so repeated requests cannot be averaged out.
+ALTER TABLE properties
+    ADD COLUMN approximate_latitude DOUBLE PRECISION,
+    ADD COLUMN approximate_longitude DOUBLE PRECISION;
+
+CREATE OR REPLACE FUNCTION fuzz_property_location()
+RETURNS TRIGGER AS $$
+DECLARE
+    distance_m DOUBLE PRECISION;
+    bearing DOUBLE PRECISION;
+BEGIN
+    IF NEW.latitude IS NULL OR NEW.longitude IS NULL THEN
+        NEW.approximate_latitude := NULL;
+        NEW.approximate_longitude := NULL;
+    ELSIF TG_OP = 'INSERT'
+        OR NEW.approximate_latitude IS NULL
+        OR NEW.latitude IS DISTINCT FROM OLD.latitude
+        OR NEW.longitude IS DISTINCT FROM OLD.longitude THEN
+        distance_m := 200 + random() * 600;
+        bearing := random() * 2 * pi();
+        NEW.approximate_latitude := NEW.latitude + distance_m * cos(bearing) / 111320.0;
+        NEW.approximate_longitude := NEW.longitude
+            + distance_m * sin(bearing) / (111320.0 * GREATEST(cos(radians(NEW.latitude)), 0.01));
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER fuzz_properties_location BEFORE INSERT OR UPDATE ON properties
+    FOR EACH ROW EXECUTE FUNCTION fuzz_property_location();
+
+-- Fill in existing listings without touching updated_at
+ALTER TABLE properties DISABLE TRIGGER update_properties_updated_at;
+UPDATE properties SET approximate_latitude = NULL WHERE latitude IS NOT NULL AND longitude IS NOT NULL;
+ALTER TABLE properties ENABLE TRIGGER update_properties_updated_at;
@@ -0,0 +1,35 @@
+-- A session is one login on one device: the refresh token family plus what the
+-- user needs to recognise it. Revoking a session ends its refresh tokens and the
+-- access tokens issued for it.
+CREATE TABLE sessions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_agent VARCHAR(512),
+    ip_address VARCHAR(45),
+    -- Expiry of the session's latest refresh token
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    last_seen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_sessions_user_id ON sessions(user_id);
+
+-- Existing refresh token families become sessions
+INSERT INTO sessions (id, user_id, expires_at, last_seen_at, revoked_at, created_at)
+SELECT
+    family_id,
+    user_id,
+    MAX(expires_at),
+    MAX(created_at),
+    CASE WHEN BOOL_OR(revoked_at IS NOT NULL) THEN MAX(revoked_at) END,
+    MIN(created_at)
+FROM refresh_tokens
+GROUP BY family_id, user_id;
+
+ALTER TABLE refresh_tokens RENAME COLUMN family_id TO session_id;
+ALTER TABLE refresh_tokens
+    DROP COLUMN revoked_at,
+    ADD CONSTRAINT refresh_tokens_session_id_fkey
+        FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE;
+ALTER INDEX idx_refresh_tokens_family_id RENAME TO idx_refresh_tokens_session_id;
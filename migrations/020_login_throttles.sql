@@ -0,0 +1,10 @@
+-- Failed login attempts per account (keyed by lowercased email, so unknown
+-- addresses are throttled the same way as real ones) and per client IP
+CREATE TABLE login_throttles (
+    scope VARCHAR(16) NOT NULL CHECK (scope IN ('account', 'ip')),
+    key VARCHAR(255) NOT NULL,
+    failures INTEGER NOT NULL DEFAULT 0,
+    last_failed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (scope, key)
+);
@@ -0,0 +1,22 @@
+-- Nightly price a guest pays: the cheapest active room type for properties
+-- sold per unit, otherwise the listing price. Per-night inventory prices are
+-- left out so one discounted night does not read as a price drop.
+CREATE FUNCTION effective_nightly_price(p_property_id UUID)
+RETURNS INTEGER AS $$
+    SELECT COALESCE(
+        (SELECT MIN(price_per_night) FROM room_types WHERE property_id = p_property_id AND is_active = true),
+        (SELECT price_per_night FROM properties WHERE id = p_property_id)
+    );
+$$ LANGUAGE SQL STABLE;
+
+-- Effective price each user last saw for the properties on their wishlists,
+-- so the matcher can tell them when it falls
+CREATE TABLE wishlist_price_watches (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    property_id UUID NOT NULL REFERENCES properties(id) ON DELETE CASCADE,
+    price_per_night INTEGER NOT NULL,
+    checked_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, property_id)
+);
+
+CREATE INDEX idx_wishlist_price_watches_property_id ON wishlist_price_watches(property_id);
@@ -0,0 +1,10 @@
+-- Password reset requests per account (keyed by lowercased email, so unknown
+-- addresses are limited the same way as real ones) and per client IP
+CREATE TABLE password_reset_throttles (
+    scope VARCHAR(16) NOT NULL CHECK (scope IN ('account', 'ip')),
+    key VARCHAR(255) NOT NULL,
+    requests INTEGER NOT NULL DEFAULT 1,
+    window_started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_requested_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (scope, key)
+);
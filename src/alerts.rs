@@ -0,0 +1,158 @@
+use crate::{db, error::AppError, models::{NewNotification, NotificationKind, SavedSearch}};
+use serde_json::json;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+pub const MAX_SAVED_SEARCHES_PER_USER: i64 = 20;
+
+// Saved searches checked per matcher run; the rest wait for the next run
+const SEARCHES_PER_RUN: i64 = 500;
+// Listings considered per saved search, newest first
+const MATCHES_PER_SEARCH: i64 = 500;
+// Wishlisted properties checked per matcher run, least recently checked first
+const WATCHED_PRICES_PER_RUN: i64 = 5000;
+
+// Record what a new saved search already matches, so only later listings trigger alerts
+pub async fn seed_saved_search(pool: &PgPool, search: &SavedSearch) -> Result<(), AppError> {
+    let checked_at = OffsetDateTime::now_utc();
+    let matches = db::get_listing_prices_matching(pool, &search.filters, MATCHES_PER_SEARCH).await?;
+    let property_ids: Vec<_> = matches.iter().map(|(id, _, _, _)| *id).collect();
+    let prices: Vec<_> = matches.iter().map(|(_, _, price, _)| *price).collect();
+
+    db::record_saved_search_matches(pool, search.id, checked_at, &property_ids, &prices, &[]).await
+}
+
+// Check every saved search with alerts enabled for new listings and price drops.
+// Returns the number of notifications sent.
+pub async fn run_saved_search_alerts(pool: &PgPool) -> Result<u64, AppError> {
+    let searches = db::get_saved_searches_for_alerts(pool, SEARCHES_PER_RUN).await?;
+
+    let mut sent = 0;
+    for search in searches {
+        // One broken search should not hold up the others
+        match check_saved_search(pool, &search).await {
+            Ok(count) => sent += count,
+            Err(e) => tracing::warn!("Saved search {} could not be checked: {}", search.id, e),
+        }
+    }
+
+    Ok(sent)
+}
+
+async fn check_saved_search(pool: &PgPool, search: &SavedSearch) -> Result<u64, AppError> {
+    // Taken before reading, so listings published meanwhile count as new next time
+    let checked_at = OffsetDateTime::now_utc();
+    let matches = db::get_listing_prices_matching(pool, &search.filters, MATCHES_PER_SEARCH).await?;
+    let known = db::get_saved_search_matches(pool, search.id).await?;
+
+    let mut new_listings = Vec::new();
+    let mut price_drops = Vec::new();
+    for (property_id, title, price, published_at) in &matches {
+        match known.get(property_id) {
+            // Listings published earlier that only now match (after an edit, say) are not new
+            None => {
+                let published_since_check = match (published_at, search.last_checked_at) {
+                    (Some(published_at), Some(last_checked_at)) => *published_at > last_checked_at,
+                    _ => search.last_checked_at.is_none(),
+                };
+                if published_since_check {
+                    new_listings.push((*property_id, title));
+                }
+            }
+            Some(&previous_price) if *price < previous_price => {
+                price_drops.push((*property_id, title, previous_price, *price))
+            }
+            Some(_) => {}
+        }
+    }
+
+    let mut notifications = Vec::new();
+
+    if !new_listings.is_empty() {
+        let title = match new_listings.len() {
+            1 => format!("New listing for \"{}\"", search.name),
+            n => format!("{} new listings for \"{}\"", n, search.name),
+        };
+        let body = new_listings
+            .iter()
+            .map(|(_, title)| title.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let property_ids: Vec<_> = new_listings.iter().map(|(id, _)| *id).collect();
+
+        notifications.push(NewNotification {
+            user_id: search.user_id,
+            kind: NotificationKind::NewListing,
+            title,
+            body,
+            data: json!({ "saved_search_id": search.id, "property_ids": property_ids }),
+        });
+    }
+
+    for (property_id, title, previous_price, price) in price_drops {
+        notifications.push(NewNotification {
+            user_id: search.user_id,
+            kind: NotificationKind::PriceDrop,
+            title: format!("Price drop for \"{}\"", search.name),
+            body: price_drop_body(title, previous_price, price),
+            data: json!({
+                "saved_search_id": search.id,
+                "property_id": property_id,
+                "previous_price": previous_price,
+                "price": price,
+            }),
+        });
+    }
+
+    let property_ids: Vec<_> = matches.iter().map(|(id, _, _, _)| *id).collect();
+    let prices: Vec<_> = matches.iter().map(|(_, _, price, _)| *price).collect();
+    db::record_saved_search_matches(pool, search.id, checked_at, &property_ids, &prices, &notifications).await?;
+
+    Ok(notifications.len() as u64)
+}
+
+// Tell users when a property on one of their wishlists gets cheaper.
+// Returns the number of notifications sent.
+pub async fn run_wishlist_price_alerts(pool: &PgPool) -> Result<u64, AppError> {
+    let watched = db::get_watched_prices(pool, WATCHED_PRICES_PER_RUN).await?;
+
+    // Properties seen for the first time only have their price recorded
+    let notifications: Vec<_> = watched
+        .iter()
+        .filter_map(|item| {
+            let previous_price = item.last_seen_price.filter(|&previous| item.price < previous)?;
+            Some(NewNotification {
+                user_id: item.user_id,
+                kind: NotificationKind::PriceDrop,
+                title: "Price drop on your wishlist".to_string(),
+                body: price_drop_body(&item.title, previous_price, item.price),
+                data: json!({
+                    "property_id": item.property_id,
+                    "previous_price": previous_price,
+                    "price": item.price,
+                }),
+            })
+        })
+        .collect();
+
+    let user_ids: Vec<_> = watched.iter().map(|item| item.user_id).collect();
+    let property_ids: Vec<_> = watched.iter().map(|item| item.property_id).collect();
+    let prices: Vec<_> = watched.iter().map(|item| item.price).collect();
+    db::record_watched_prices(pool, &user_ids, &property_ids, &prices, &notifications).await?;
+
+    Ok(notifications.len() as u64)
+}
+
+fn price_drop_body(title: &str, previous_price: i32, price: i32) -> String {
+    format!(
+        "{} is now {} per night (was {})",
+        title,
+        format_price(price),
+        format_price(previous_price)
+    )
+}
+
+// Prices are stored in cents
+fn format_price(cents: i32) -> String {
+    format!("{}.{:02}", cents / 100, cents % 100)
+}
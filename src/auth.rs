@@ -1,19 +1,55 @@
-use crate::{error::AppError, models::UserRole};
-use actix_web::{
-    dev::ServiceRequest,
-    error::Error,
-    http::header,
-    web, HttpMessage, HttpRequest,
-};
+use crate::{config::Config, error::AppError, models::UserRole};
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpMessage, HttpRequest};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
 use bcrypt::{hash, verify, DEFAULT_COST};
 use time::{Duration, OffsetDateTime};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use ring::{
+    digest::{digest, SHA256},
+    rand::{SecureRandom, SystemRandom},
+    signature::{Ed25519KeyPair, KeyPair, RsaKeyPair, RsaPublicKeyComponents},
+};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::LazyLock;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use totp_rs::{Secret, TOTP};
 use uuid::Uuid;
 
-const JWT_SECRET: &[u8] = b"your-secret-key-change-in-production";
-const JWT_EXPIRATION_HOURS: i64 = 24;
+// Authenticator codes change every TOTP_STEP seconds; one step either side is
+// accepted for clock drift
+const TOTP_STEP: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+// API keys read `abk_<prefix>_<secret>`
+const API_KEY_PREFIX: &str = "abk_";
+
+// Endpoints API keys may call (method, route pattern) and the scope each needs.
+// Anything else needs an interactive login.
+const API_KEY_ROUTES: &[(&str, &str, &str)] = &[
+    ("GET", "/api/v1/properties/my", "properties:read"),
+    ("GET", "/api/v1/properties/{id}/bookings", "bookings:read"),
+    ("POST", "/api/v1/bookings/{id}/approve", "bookings:write"),
+    ("POST", "/api/v1/bookings/{id}/deny", "bookings:write"),
+    ("GET", "/api/v1/properties/{id}/room-types/{room_type_id}/inventory", "calendar:read"),
+    ("PUT", "/api/v1/properties/{id}/room-types/{room_type_id}/inventory", "calendar:write"),
+];
+
+// The only endpoints admins can use until they have enabled two-factor authentication
+const ADMIN_ENROLMENT_ROUTES: &[(&str, &str)] = &[
+    ("POST", "/api/v1/auth/2fa/setup"),
+    ("POST", "/api/v1/auth/2fa/confirm"),
+    ("POST", "/api/v1/auth/logout-all"),
+];
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -21,6 +57,9 @@ pub struct Claims {
     pub exp: i64,    // expiration time
     pub iat: i64,    // issued at
     pub role: UserRole,
+    // Session the token was issued for; revoking the session ends the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -28,6 +67,11 @@ pub struct AuthUser {
     pub id: Uuid,
     pub email: String,
     pub role: UserRole,
+    pub session_id: Option<Uuid>,
+    pub email_verified: bool,
+    pub two_factor_enabled: bool,
+    // Set when the request authenticated with an API key instead of a login
+    pub api_key_id: Option<Uuid>,
 }
 
 pub async fn hash_password(password: &str) -> Result<String, AppError> {
@@ -40,39 +84,259 @@ pub async fn verify_password(password: &str, hash: &str) -> Result<bool, AppErro
     Ok(is_valid)
 }
 
-pub fn create_jwt(user_id: Uuid, role: UserRole) -> Result<String, AppError> {
-    let now = OffsetDateTime::now_utc();
-    let expires_at = now + Duration::hours(JWT_EXPIRATION_HOURS);
+// Hash with the same cost as real ones, so checking a password for an unknown
+// email takes as long as for an existing account
+static DUMMY_PASSWORD_HASH: LazyLock<String> =
+    LazyLock::new(|| hash("dummy-password", DEFAULT_COST).expect("bcrypt hashing failed"));
 
-    let claims = Claims {
-        sub: user_id.to_string(),
-        exp: expires_at.unix_timestamp(),
-        iat: now.unix_timestamp(),
-        role,
-    };
+pub async fn dummy_verify_password(password: &str) -> Result<(), AppError> {
+    verify(password, &DUMMY_PASSWORD_HASH)?;
+    Ok(())
+}
+
+// Keys for signing and verifying access tokens. Every token names its key in the
+// `kid` header, so a new key can take over signing while tokens signed with the
+// previous one stay valid until they expire.
+pub struct JwtKeys {
+    signing_kid: String,
+    // Tokens issued before keys had ids
+    legacy_kid: String,
+    expiration: Duration,
+    keys: HashMap<String, JwtKey>,
+}
+
+struct JwtKey {
+    algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    // Published in the JWKS; None for shared secrets
+    public_jwk: Option<Jwk>,
+}
+
+impl JwtKey {
+    fn from_secret(secret: &str) -> Self {
+        JwtKey {
+            algorithm: Algorithm::HS256,
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            public_jwk: None,
+        }
+    }
+
+    // An Ed25519 (EdDSA) or RSA (RS256) private key
+    fn from_private_pem(kid: &str, pem: &[u8]) -> Result<Self, AppError> {
+        let der = pem::parse(pem)
+            .map_err(|e| AppError::Internal(format!("JWT key {} is not valid PEM: {}", kid, e)))?
+            .into_contents();
+
+        if let Ok(key_pair) = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der) {
+            let x = URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref());
+            return Ok(JwtKey {
+                algorithm: Algorithm::EdDSA,
+                encoding: EncodingKey::from_ed_pem(pem)?,
+                decoding: DecodingKey::from_ed_components(&x)?,
+                public_jwk: Some(public_jwk(
+                    kid,
+                    KeyAlgorithm::EdDSA,
+                    AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                        key_type: OctetKeyPairType::OctetKeyPair,
+                        curve: EllipticCurve::Ed25519,
+                        x,
+                    }),
+                )),
+            });
+        }
+
+        let key_pair = RsaKeyPair::from_pkcs8(&der)
+            .or_else(|_| RsaKeyPair::from_der(&der))
+            .map_err(|e| {
+                AppError::Internal(format!("JWT key {} is not an Ed25519 or RSA private key: {}", kid, e))
+            })?;
+        let components = RsaPublicKeyComponents::<Vec<u8>>::from(key_pair.public());
+        let (n, e) = (URL_SAFE_NO_PAD.encode(&components.n), URL_SAFE_NO_PAD.encode(&components.e));
+        Ok(JwtKey {
+            algorithm: Algorithm::RS256,
+            encoding: EncodingKey::from_rsa_pem(pem)?,
+            decoding: DecodingKey::from_rsa_components(&n, &e)?,
+            public_jwk: Some(public_jwk(
+                kid,
+                KeyAlgorithm::RS256,
+                AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n,
+                    e,
+                }),
+            )),
+        })
+    }
+}
+
+fn public_jwk(kid: &str, algorithm: KeyAlgorithm, parameters: AlgorithmParameters) -> Jwk {
+    Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(algorithm),
+            key_id: Some(kid.to_string()),
+            ..Default::default()
+        },
+        algorithm: parameters,
+    }
+}
+
+impl JwtKeys {
+    pub fn from_config(config: &Config) -> Result<Self, AppError> {
+        let mut keys = HashMap::new();
+        let mut add = |kid: &str, key: JwtKey| match keys.insert(kid.to_string(), key) {
+            Some(_) => Err(AppError::Internal(format!("Duplicate JWT key id {}", kid))),
+            None => Ok(()),
+        };
+
+        add(&config.jwt_kid, JwtKey::from_secret(&config.jwt_secret))?;
+        for (kid, secret) in &config.jwt_previous_secrets {
+            add(kid, JwtKey::from_secret(secret))?;
+        }
+        for (kid, path) in &config.jwt_private_keys {
+            let pem = std::fs::read(path)
+                .map_err(|e| AppError::Internal(format!("Failed to read JWT key {}: {}", path, e)))?;
+            add(kid, JwtKey::from_private_pem(kid, &pem)?)?;
+        }
+
+        let signing_kid = config.jwt_signing_kid.clone().unwrap_or_else(|| config.jwt_kid.clone());
+        if !keys.contains_key(&signing_kid) {
+            return Err(AppError::Internal(format!("No JWT key with id {}", signing_kid)));
+        }
+
+        Ok(JwtKeys {
+            signing_kid,
+            legacy_kid: config.jwt_kid.clone(),
+            expiration: Duration::minutes(config.jwt_expiration_minutes as i64),
+            keys,
+        })
+    }
+
+    // Access token lifetime in seconds
+    pub fn expires_in(&self) -> i64 {
+        self.expiration.whole_seconds()
+    }
+
+    pub fn create_jwt(&self, user_id: Uuid, role: UserRole, session_id: Option<Uuid>) -> Result<String, AppError> {
+        let now = OffsetDateTime::now_utc();
+        let expires_at = now + self.expiration;
+
+        let claims = Claims {
+            sub: user_id.to_string(),
+            exp: expires_at.unix_timestamp(),
+            iat: now.unix_timestamp(),
+            role,
+            sid: session_id,
+        };
+
+        let key = &self.keys[&self.signing_kid];
+        let mut header = Header::new(key.algorithm);
+        header.kid = Some(self.signing_kid.clone());
+
+        let token = encode(&header, &claims, &key.encoding)?;
+
+        Ok(token)
+    }
+
+    pub fn verify_jwt(&self, token: &str) -> Result<Claims, AppError> {
+        let kid = decode_header(token)?.kid.unwrap_or_else(|| self.legacy_kid.clone());
+        let key = self
+            .keys
+            .get(&kid)
+            .ok_or_else(|| AppError::Authentication("Token signed with an unknown key".to_string()))?;
+
+        // Only the key's own algorithm is accepted, whatever the header claims
+        let token_data = decode::<Claims>(token, &key.decoding, &Validation::new(key.algorithm))?;
+
+        Ok(token_data.claims)
+    }
+
+    // Public keys for verifying tokens elsewhere; shared secrets are never listed
+    pub fn jwks(&self) -> JwkSet {
+        let mut keys: Vec<Jwk> = self.keys.values().filter_map(|key| key.public_jwk.clone()).collect();
+        keys.sort_by(|a, b| a.common.key_id.cmp(&b.common.key_id));
+        JwkSet { keys }
+    }
+}
+
+// Random opaque token for refresh tokens and links sent to users
+pub fn generate_token() -> Result<String, AppError> {
+    Ok(URL_SAFE_NO_PAD.encode(random_bytes::<32>()?))
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], AppError> {
+    let mut bytes = [0u8; N];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| AppError::Internal("Failed to generate random bytes".to_string()))?;
+    Ok(bytes)
+}
+
+// Opaque tokens are stored as their SHA-256 so a database leak does not expose them
+pub fn hash_token(token: &str) -> String {
+    digest(&SHA256, token.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET),
-    )?;
+// New base32 TOTP secret (160 bits, as RFC 4226 recommends)
+pub fn generate_totp_secret() -> Result<String, AppError> {
+    match Secret::Raw(random_bytes::<20>()?.to_vec()).to_encoded() {
+        Secret::Encoded(secret) => Ok(secret),
+        Secret::Raw(_) => unreachable!("to_encoded returns an encoded secret"),
+    }
+}
 
-    Ok(token)
+pub fn totp(secret: &str, issuer: &str, account: &str) -> Result<TOTP, AppError> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| AppError::Internal(format!("Invalid TOTP secret: {}", e)))?;
+    TOTP::new(totp_rs::Algorithm::SHA1, 6, 0, TOTP_STEP, secret, Some(issuer.to_string()), account.to_string())
+        .map_err(|e| AppError::Internal(format!("Invalid TOTP parameters: {}", e)))
 }
 
-pub fn verify_jwt(token: &str) -> Result<Claims, AppError> {
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET),
-        &Validation::default(),
-    )?;
+// The time step `code` belongs to, if it is valid now
+pub fn totp_step(totp: &TOTP, code: &str) -> Option<i64> {
+    let now = OffsetDateTime::now_utc().unix_timestamp() as u64 / TOTP_STEP;
+    (now - 1..=now + 1)
+        .find(|step| totp.check(code.trim(), step * TOTP_STEP))
+        .map(|step| step as i64)
+}
+
+// Recovery codes look like `k3vq8-2mzpa`: 50 random bits from an alphabet
+// without easily confused characters
+pub fn generate_recovery_codes() -> Result<Vec<String>, AppError> {
+    const ALPHABET: &[u8; 32] = b"023456789abcdefghjkmnpqrstuvwxyz";
+
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code: String = random_bytes::<10>()?
+                .iter()
+                .map(|byte| ALPHABET[(byte % 32) as usize] as char)
+                .collect();
+            Ok(format!("{}-{}", &code[..5], &code[5..]))
+        })
+        .collect()
+}
 
-    Ok(token_data.claims)
+// Ignores case, spaces and dashes so codes can be typed loosely
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hash_token(&normalized)
 }
 
 pub async fn get_current_user(
     req: &HttpRequest,
     pool: &PgPool,
+    keys: &JwtKeys,
 ) -> Result<AuthUser, AppError> {
     let auth_header = req
         .headers()
@@ -90,80 +354,251 @@ pub async fn get_current_user(
     }
 
     let token = &auth_str[7..];
-    let claims = verify_jwt(token)?;
+    if token.starts_with(API_KEY_PREFIX) {
+        return authenticate_api_key(req, pool, token).await;
+    }
+    let claims = keys.verify_jwt(token)?;
 
     let user_id = Uuid::parse_str(&claims.sub)?;
 
-    // Verify user still exists and is active
-    let row = sqlx::query("SELECT * FROM users WHERE id = $1 AND is_active = true")
-        .bind(user_id)
-        .fetch_optional(pool)
-        .await?;
+    let client = ClientInfo::from_request_head(req);
+
+    // Verify user still exists and is active, and that the session the token was
+    // issued for has not been revoked. Last-seen is recorded at most once a minute.
+    let row = sqlx::query(
+        r#"
+        WITH seen AS (
+            UPDATE sessions SET last_seen_at = NOW(), ip_address = COALESCE($3, ip_address)
+            WHERE id = $2 AND revoked_at IS NULL AND last_seen_at < NOW() - INTERVAL '1 minute'
+        )
+        SELECT * FROM users
+        WHERE id = $1 AND is_active = true
+            AND ($2::UUID IS NULL OR EXISTS (
+                SELECT 1 FROM sessions WHERE id = $2 AND user_id = $1 AND revoked_at IS NULL
+            ))
+        "#
+    )
+    .bind(user_id)
+    .bind(claims.sid)
+    .bind(client.ip_address)
+    .fetch_optional(pool)
+    .await?;
 
     let user = match row {
         Some(row) => crate::db::row_to_user(row)?,
-        None => return Err(AppError::Authentication("User not found or inactive".to_string())),
+        None => return Err(AppError::Authentication("User not found, inactive or logged out".to_string())),
     };
 
     Ok(AuthUser {
         id: user.id,
         email: user.email,
         role: user.role,
+        session_id: claims.sid,
+        email_verified: user.email_verified_at.is_some(),
+        two_factor_enabled: user.totp_enabled_at.is_some(),
+        api_key_id: None,
+    })
+}
+
+// New API key, returned with its prefix
+pub fn generate_api_key() -> Result<(String, String), AppError> {
+    let prefix: String = random_bytes::<4>()?.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let key = format!("{}{}_{}", API_KEY_PREFIX, prefix, generate_token()?);
+    Ok((prefix, key))
+}
+
+async fn authenticate_api_key(req: &HttpRequest, pool: &PgPool, key: &str) -> Result<AuthUser, AppError> {
+    let invalid = || AppError::Authentication("Invalid, expired or revoked API key".to_string());
+
+    let prefix = key[API_KEY_PREFIX.len()..].split('_').next().ok_or_else(invalid)?;
+    let api_key = crate::db::get_usable_api_key(pool, prefix, &hash_token(key))
+        .await?
+        .ok_or_else(invalid)?;
+    let user = crate::db::get_user_by_id(pool, api_key.user_id)
+        .await?
+        .filter(|user| user.is_active)
+        .ok_or_else(invalid)?;
+
+    // Keys stop working if their owner no longer hosts, and never act for an admin
+    if user.role != UserRole::PropertyOwner {
+        return Err(AppError::Authorization("API keys are only available to property owners".to_string()));
+    }
+
+    let method = req.method().as_str();
+    let pattern = req.match_pattern().unwrap_or_default();
+    let scope = API_KEY_ROUTES
+        .iter()
+        .find(|(route_method, route, _)| *route_method == method && *route == pattern)
+        .map(|(_, _, scope)| *scope)
+        .ok_or_else(|| AppError::Authorization("This endpoint cannot be used with an API key".to_string()))?;
+    if !api_key.scopes.iter().any(|granted| granted == scope) {
+        return Err(AppError::Authorization(format!("API key is missing the {} scope", scope)));
+    }
+
+    Ok(AuthUser {
+        id: user.id,
+        email: user.email,
+        role: user.role,
+        session_id: None,
+        email_verified: user.email_verified_at.is_some(),
+        two_factor_enabled: user.totp_enabled_at.is_some(),
+        api_key_id: Some(api_key.id),
     })
 }
 
-pub async fn require_auth(
-    req: ServiceRequest,
-    pool: web::Data<PgPool>,
-) -> Result<ServiceRequest, Error> {
-    let auth_user = get_current_user(req.request(), &pool).await;
+// Device details recorded on sessions
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl ClientInfo {
+    fn from_request_head(req: &HttpRequest) -> Self {
+        let user_agent = req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(512).collect());
 
-    match auth_user {
-        Ok(user) => {
-            req.extensions_mut().insert(user);
-            Ok(req)
+        // Forwarding headers are easy to spoof, so they only count when the
+        // connection comes from one of our own proxies
+        let trusted_proxies = req
+            .app_data::<web::Data<Config>>()
+            .map(|config| config.trusted_proxies.as_slice())
+            .unwrap_or_default();
+        let forwarded_for = req
+            .headers()
+            .get_all("x-forwarded-for")
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+        let ip_address = req
+            .peer_addr()
+            .map(|peer| client_ip(peer.ip(), &forwarded_for, trusted_proxies).to_string());
+
+        ClientInfo { user_agent, ip_address }
+    }
+}
+
+// Each proxy appends the address it received the request from, so the client
+// is the last address in X-Forwarded-For that is not one of the trusted proxies
+fn client_ip(peer: IpAddr, forwarded_for: &str, trusted_proxies: &[IpAddr]) -> IpAddr {
+    if !trusted_proxies.contains(&peer) {
+        return peer;
+    }
+
+    forwarded_for
+        .rsplit(',')
+        .map_while(|hop| hop.trim().parse::<IpAddr>().ok())
+        .find(|hop| !trusted_proxies.contains(hop))
+        .unwrap_or(peer)
+}
+
+impl FromRequest for ClientInfo {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Ok(ClientInfo::from_request_head(req)))
+    }
+}
+
+impl AuthUser {
+    fn require_role(self, allowed: &[UserRole], message: &str) -> Result<Self, AppError> {
+        if allowed.contains(&self.role) {
+            Ok(self)
+        } else {
+            Err(AppError::Authorization(message.to_string()))
         }
-        Err(_) => Err(actix_web::error::ErrorUnauthorized("Unauthorized")),
     }
 }
 
-pub async fn require_role(
-    req: ServiceRequest,
-    pool: web::Data<PgPool>,
-    required_role: UserRole,
-) -> Result<ServiceRequest, Error> {
-    let auth_user = get_current_user(req.request(), &pool).await;
+// Authenticates the bearer token of the request. Use `Option<AuthUser>` for
+// endpoints where signing in is optional.
+impl FromRequest for AuthUser {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
 
-    match auth_user {
-        Ok(user) => {
-            if user.role == required_role || user.role == UserRole::Admin {
-                req.extensions_mut().insert(user);
-                Ok(req)
-            } else {
-                Err(actix_web::error::ErrorForbidden("Insufficient permissions"))
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            // Extracted once per request, however many guards ask for it
+            if let Some(user) = req.extensions().get::<AuthUser>().cloned() {
+                return Ok(user);
             }
-        }
-        Err(_) => Err(actix_web::error::ErrorUnauthorized("Unauthorized")),
+
+            let pool = req
+                .app_data::<web::Data<PgPool>>()
+                .ok_or_else(|| AppError::Internal("Database pool is not configured".to_string()))?;
+            let keys = req
+                .app_data::<web::Data<JwtKeys>>()
+                .ok_or_else(|| AppError::Internal("JWT keys are not configured".to_string()))?;
+            let user = get_current_user(&req, pool, keys).await?;
+
+            // Two-factor is mandatory for admins, whatever role an endpoint asks for
+            if user.role == UserRole::Admin && !user.two_factor_enabled {
+                let method = req.method().as_str();
+                let pattern = req.match_pattern().unwrap_or_default();
+                if !ADMIN_ENROLMENT_ROUTES.iter().any(|(route_method, route)| *route_method == method && *route == pattern) {
+                    return Err(AppError::Authorization(
+                        "Admins must enable two-factor authentication".to_string(),
+                    ));
+                }
+            }
+
+            req.extensions_mut().insert(user.clone());
+            Ok(user)
+        })
     }
 }
 
-pub async fn require_admin(
-    req: ServiceRequest,
-    pool: web::Data<PgPool>,
-) -> Result<ServiceRequest, Error> {
-    require_role(req, pool, UserRole::Admin).await
+// Property owners and admins
+pub struct RequireOwner(pub AuthUser);
+
+impl FromRequest for RequireOwner {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let user = AuthUser::from_request(req, payload);
+        Box::pin(async move {
+            user.await?
+                .require_role(&[UserRole::PropertyOwner, UserRole::Admin], "Property owner access required")
+                .map(RequireOwner)
+        })
+    }
 }
 
-pub async fn require_property_owner(
-    req: ServiceRequest,
-    pool: web::Data<PgPool>,
-) -> Result<ServiceRequest, Error> {
-    require_role(req, pool, UserRole::PropertyOwner).await
+pub struct RequireAdmin(pub AuthUser);
+
+impl FromRequest for RequireAdmin {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let user = AuthUser::from_request(req, payload);
+        Box::pin(async move {
+            user.await?
+                .require_role(&[UserRole::Admin], "Admin access required")
+                .map(RequireAdmin)
+        })
+    }
 }
 
-pub fn extract_auth_user(req: &HttpRequest) -> Result<AuthUser, AppError> {
-    req.extensions()
-        .get::<AuthUser>()
-        .cloned()
-        .ok_or_else(|| AppError::Authentication("User not found in request".to_string()))
+// Regular users, i.e. guests; owners and admins cannot book stays
+pub struct RequireGuest(pub AuthUser);
+
+impl FromRequest for RequireGuest {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let user = AuthUser::from_request(req, payload);
+        Box::pin(async move {
+            user.await?
+                .require_role(&[UserRole::User], "Only users can book stays")
+                .map(RequireGuest)
+        })
+    }
 }
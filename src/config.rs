@@ -1,4 +1,5 @@
 use std::env;
+use std::net::IpAddr;
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -6,11 +7,74 @@ pub struct Config {
     pub host: String,
     pub port: u16,
     pub jwt_secret: String,
-    pub jwt_expiration_hours: u64,
+    // Access tokens are short-lived; refresh tokens keep clients signed in
+    pub jwt_expiration_minutes: u64,
+    pub refresh_token_days: u64,
+    // Key id of `jwt_secret`
+    pub jwt_kid: String,
+    // Key signing new tokens; defaults to `jwt_kid`
+    pub jwt_signing_kid: Option<String>,
+    // Retired `kid=secret` HS256 keys still accepted until their tokens expire
+    pub jwt_previous_secrets: Vec<(String, String)>,
+    // `kid=path` RSA or Ed25519 private keys in PEM files (RS256 / EdDSA)
+    pub jwt_private_keys: Vec<(String, String)>,
     pub cors_allow_origin: String,
     pub cors_allow_methods: String,
     pub cors_allow_headers: String,
     pub cors_max_age: u64,
+    pub upload_dir: String,
+    pub max_photo_bytes: usize,
+    pub review_window_days: i64,
+    pub saved_search_alert_interval_secs: u64,
+    // Outbound email; without an SMTP host emails are only logged
+    pub smtp_host: Option<String>,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub email_from: String,
+    // Frontend that links in emails point to
+    pub app_base_url: String,
+    pub password_reset_token_minutes: i64,
+    // Minimum wait between reset emails for one address, and how many an
+    // address or client IP can request a day
+    pub password_reset_resend_secs: i64,
+    pub password_reset_daily_limit: i64,
+    pub password_reset_ip_daily_limit: i64,
+    pub email_verification_token_hours: i64,
+    // Minimum wait between verification emails, and how many can be sent a day
+    pub email_verification_resend_secs: i64,
+    pub email_verification_daily_limit: i64,
+    // Guests must verify their email address before booking
+    pub require_verified_email_for_booking: bool,
+    // Failed logins allowed per account and per IP before further attempts are
+    // locked out, first for the base time and then twice as long per failure
+    pub login_max_failures: i32,
+    pub login_ip_max_failures: i32,
+    pub login_lockout_base_secs: i64,
+    pub login_lockout_max_secs: i64,
+    // Failure counts start over after this long without a failed attempt
+    pub login_failure_window_minutes: i64,
+    // Reverse proxies trusted to report the client address in X-Forwarded-For
+    pub trusted_proxies: Vec<IpAddr>,
+    // Issuer shown in authenticator apps
+    pub totp_issuer: String,
+    // How long the second login step may take
+    pub login_challenge_minutes: i64,
+    // External OpenID Connect providers users can sign in with
+    pub oidc_providers: Vec<OidcProviderConfig>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OidcProviderConfig {
+    // Used in URLs, e.g. `google` in /auth/oidc/google/authorize
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub scopes: String,
+    // Frontend page the provider sends users back to; defaults to
+    // `{app_base_url}/auth/callback/{name}`
+    pub redirect_url: Option<String>,
 }
 
 impl Config {
@@ -24,10 +88,23 @@ impl Config {
                 .unwrap_or(8080),
             jwt_secret: env::var("JWT_SECRET")
                 .unwrap_or_else(|_| "your-secret-key-change-in-production".to_string()),
-            jwt_expiration_hours: env::var("JWT_EXPIRATION_HOURS")
-                .unwrap_or_else(|_| "24".to_string())
+            // Deployments configured before the switch to minutes still set JWT_EXPIRATION_HOURS
+            jwt_expiration_minutes: env::var("JWT_EXPIRATION_MINUTES")
+                .ok()
+                .and_then(|minutes| minutes.parse().ok())
+                .or_else(|| {
+                    let hours: u64 = env::var("JWT_EXPIRATION_HOURS").ok()?.parse().ok()?;
+                    Some(hours * 60)
+                })
+                .unwrap_or(15),
+            refresh_token_days: env::var("REFRESH_TOKEN_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
                 .parse()
-                .unwrap_or(24),
+                .unwrap_or(30),
+            jwt_kid: env::var("JWT_KID").unwrap_or_else(|_| "default".to_string()),
+            jwt_signing_kid: env::var("JWT_SIGNING_KID").ok(),
+            jwt_previous_secrets: key_list("JWT_PREVIOUS_SECRETS"),
+            jwt_private_keys: key_list("JWT_PRIVATE_KEYS"),
             cors_allow_origin: env::var("CORS_ALLOW_ORIGIN")
                 .unwrap_or_else(|_| "*".to_string()),
             cors_allow_methods: env::var("CORS_ALLOW_METHODS")
@@ -38,6 +115,92 @@ impl Config {
                 .unwrap_or_else(|_| "3600".to_string())
                 .parse()
                 .unwrap_or(3600),
+            upload_dir: env::var("UPLOAD_DIR").unwrap_or_else(|_| "./uploads".to_string()),
+            max_photo_bytes: env::var("MAX_PHOTO_BYTES")
+                .unwrap_or_else(|_| "10485760".to_string())
+                .parse()
+                .unwrap_or(10 * 1024 * 1024),
+            review_window_days: env::var("REVIEW_WINDOW_DAYS")
+                .unwrap_or_else(|_| "14".to_string())
+                .parse()
+                .unwrap_or(14),
+            saved_search_alert_interval_secs: env::var("SAVED_SEARCH_ALERT_INTERVAL_SECS")
+                .unwrap_or_else(|_| "900".to_string())
+                .parse()
+                .unwrap_or(900),
+            smtp_host: env::var("SMTP_HOST").ok(),
+            smtp_port: env::var("SMTP_PORT")
+                .unwrap_or_else(|_| "587".to_string())
+                .parse()
+                .unwrap_or(587),
+            smtp_username: env::var("SMTP_USERNAME").ok(),
+            smtp_password: env::var("SMTP_PASSWORD").ok(),
+            email_from: env::var("EMAIL_FROM")
+                .unwrap_or_else(|_| "Airbnb <no-reply@localhost>".to_string()),
+            app_base_url: env::var("APP_BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:3000".to_string()),
+            password_reset_token_minutes: env::var("PASSWORD_RESET_TOKEN_MINUTES")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
+            password_reset_resend_secs: env::var("PASSWORD_RESET_RESEND_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
+            password_reset_daily_limit: env::var("PASSWORD_RESET_DAILY_LIMIT")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .unwrap_or(5),
+            password_reset_ip_daily_limit: env::var("PASSWORD_RESET_IP_DAILY_LIMIT")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .unwrap_or(20),
+            email_verification_token_hours: env::var("EMAIL_VERIFICATION_TOKEN_HOURS")
+                .unwrap_or_else(|_| "48".to_string())
+                .parse()
+                .unwrap_or(48),
+            email_verification_resend_secs: env::var("EMAIL_VERIFICATION_RESEND_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
+            email_verification_daily_limit: env::var("EMAIL_VERIFICATION_DAILY_LIMIT")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .unwrap_or(5),
+            require_verified_email_for_booking: env::var("REQUIRE_VERIFIED_EMAIL_FOR_BOOKING")
+                .map(|value| value != "false" && value != "0")
+                .unwrap_or(true),
+            login_max_failures: env::var("LOGIN_MAX_FAILURES")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .unwrap_or(5),
+            login_ip_max_failures: env::var("LOGIN_IP_MAX_FAILURES")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .unwrap_or(20),
+            login_lockout_base_secs: env::var("LOGIN_LOCKOUT_BASE_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            login_lockout_max_secs: env::var("LOGIN_LOCKOUT_MAX_SECS")
+                .unwrap_or_else(|_| "900".to_string())
+                .parse()
+                .unwrap_or(900),
+            login_failure_window_minutes: env::var("LOGIN_FAILURE_WINDOW_MINUTES")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
+            trusted_proxies: env::var("TRUSTED_PROXIES")
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|addr| addr.trim().parse().ok())
+                .collect(),
+            totp_issuer: env::var("TOTP_ISSUER").unwrap_or_else(|_| "Airbnb".to_string()),
+            login_challenge_minutes: env::var("LOGIN_CHALLENGE_MINUTES")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .unwrap_or(5),
+            oidc_providers: oidc_providers()?,
         })
     }
 
@@ -46,6 +209,38 @@ impl Config {
     }
 }
 
+// Comma-separated `kid=value` pairs
+fn key_list(name: &str) -> Vec<(String, String)> {
+    env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(kid, value)| (kid.trim().to_string(), value.trim().to_string()))
+        .filter(|(kid, value)| !kid.is_empty() && !value.is_empty())
+        .collect()
+}
+
+// Providers named in OIDC_PROVIDERS, each configured by OIDC_<NAME>_* variables
+fn oidc_providers() -> Result<Vec<OidcProviderConfig>, env::VarError> {
+    env::var("OIDC_PROVIDERS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            let var = |suffix: &str| env::var(format!("OIDC_{}_{}", name.to_uppercase().replace('-', "_"), suffix));
+            Ok(OidcProviderConfig {
+                name: name.to_lowercase(),
+                issuer: var("ISSUER")?,
+                client_id: var("CLIENT_ID")?,
+                client_secret: var("CLIENT_SECRET").ok(),
+                scopes: var("SCOPES").unwrap_or_else(|_| "openid email profile".to_string()),
+                redirect_url: var("REDIRECT_URL").ok(),
+            })
+        })
+        .collect()
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
@@ -53,11 +248,43 @@ impl Default for Config {
             host: "127.0.0.1".to_string(),
             port: 8080,
             jwt_secret: "your-secret-key-change-in-production".to_string(),
-            jwt_expiration_hours: 24,
+            jwt_expiration_minutes: 15,
+            refresh_token_days: 30,
+            jwt_kid: "default".to_string(),
+            jwt_signing_kid: None,
+            jwt_previous_secrets: Vec::new(),
+            jwt_private_keys: Vec::new(),
             cors_allow_origin: "*".to_string(),
             cors_allow_methods: "GET,POST,PUT,DELETE,OPTIONS".to_string(),
             cors_allow_headers: "*".to_string(),
             cors_max_age: 3600,
+            upload_dir: "./uploads".to_string(),
+            max_photo_bytes: 10 * 1024 * 1024,
+            review_window_days: 14,
+            saved_search_alert_interval_secs: 900,
+            smtp_host: None,
+            smtp_port: 587,
+            smtp_username: None,
+            smtp_password: None,
+            email_from: "Airbnb <no-reply@localhost>".to_string(),
+            app_base_url: "http://localhost:3000".to_string(),
+            password_reset_token_minutes: 60,
+            password_reset_resend_secs: 60,
+            password_reset_daily_limit: 5,
+            password_reset_ip_daily_limit: 20,
+            email_verification_token_hours: 48,
+            email_verification_resend_secs: 60,
+            email_verification_daily_limit: 5,
+            require_verified_email_for_booking: true,
+            login_max_failures: 5,
+            login_ip_max_failures: 20,
+            login_lockout_base_secs: 30,
+            login_lockout_max_secs: 900,
+            login_failure_window_minutes: 60,
+            trusted_proxies: Vec::new(),
+            totp_issuer: "Airbnb".to_string(),
+            login_challenge_minutes: 5,
+            oidc_providers: Vec::new(),
         }
     }
 }
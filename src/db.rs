@@ -1,6 +1,7 @@
-use crate::{error::AppError, models::*};
-use sqlx::{PgPool, Row};
-use time::Date;
+use crate::{auth::ClientInfo, error::AppError, images::MAX_PHOTOS_PER_PROPERTY, models::*, timezone};
+use std::collections::{HashMap, HashSet};
+use sqlx::{PgPool, Postgres, QueryBuilder, Row};
+use time::{Date, OffsetDateTime, Time};
 use uuid::Uuid;
 
 pub async fn create_pool(database_url: &str) -> Result<PgPool, AppError> {
@@ -16,13 +17,16 @@ pub fn row_to_user(row: sqlx::postgres::PgRow) -> Result<User, AppError> {
         password_hash: row.try_get("password_hash")?,
         first_name: row.try_get("first_name")?,
         last_name: row.try_get("last_name")?,
-        role: match row.try_get::<&str, _>("role")? {
+        // Postgres enums are decoded through their text label
+        role: match row.try_get_unchecked::<&str, _>("role")? {
             "user" => UserRole::User,
             "property_owner" => UserRole::PropertyOwner,
             "admin" => UserRole::Admin,
             _ => return Err(AppError::Internal("Invalid user role".to_string())),
         },
         is_active: row.try_get("is_active")?,
+        email_verified_at: row.try_get("email_verified_at")?,
+        totp_enabled_at: row.try_get("totp_enabled_at")?,
         created_at: row.try_get("created_at")?,
         updated_at: row.try_get("updated_at")?,
     })
@@ -35,12 +39,8 @@ pub fn row_to_property(row: sqlx::postgres::PgRow) -> Result<Property, AppError>
         owner_id: row.try_get("owner_id")?,
         title: row.try_get("title")?,
         description: row.try_get("description")?,
-        property_type: match row.try_get::<&str, _>("property_type")? {
-            "hotel" => PropertyType::Hotel,
-            "hostel" => PropertyType::Hostel,
-            "apartment" => PropertyType::Apartment,
-            _ => return Err(AppError::Internal("Invalid property type".to_string())),
-        },
+        property_type: PropertyType::parse(row.try_get_unchecked::<&str, _>("property_type")?)
+            .ok_or_else(|| AppError::Internal("Invalid property type".to_string()))?,
         location: row.try_get("location")?,
         address: row.try_get("address")?,
         city: row.try_get("city")?,
@@ -48,18 +48,53 @@ pub fn row_to_property(row: sqlx::postgres::PgRow) -> Result<Property, AppError>
         postal_code: row.try_get("postal_code")?,
         latitude: row.try_get("latitude")?,
         longitude: row.try_get("longitude")?,
+        approximate_latitude: row.try_get("approximate_latitude")?,
+        approximate_longitude: row.try_get("approximate_longitude")?,
         price_per_night: row.try_get("price_per_night")?,
         max_guests: row.try_get("max_guests")?,
         bedrooms: row.try_get("bedrooms")?,
         bathrooms: row.try_get("bathrooms")?,
         amenities: row.try_get("amenities")?,
-        images: row.try_get("images")?,
+        attributes: row.try_get::<sqlx::types::Json<PropertyAttributes>, _>("attributes")?.0,
+        house_rules: row_to_house_rules(&row)?,
+        timezone: row
+            .try_get::<Option<String>, _>("timezone")?
+            .unwrap_or_else(|| timezone::DEFAULT_TIMEZONE.to_string()),
+        average_rating: row.try_get("average_rating")?,
+        review_count: row.try_get("review_count")?,
         is_active: row.try_get("is_active")?,
+        status: match row.try_get_unchecked::<&str, _>("status")? {
+            "draft" => ListingStatus::Draft,
+            "pending_review" => ListingStatus::PendingReview,
+            "published" => ListingStatus::Published,
+            "rejected" => ListingStatus::Rejected,
+            _ => return Err(AppError::Internal("Invalid listing status".to_string())),
+        },
+        rejection_reason: row.try_get("rejection_reason")?,
+        submitted_at: row.try_get("submitted_at")?,
+        reviewed_at: row.try_get("reviewed_at")?,
+        reviewed_by: row.try_get("reviewed_by")?,
         created_at: row.try_get("created_at")?,
         updated_at: row.try_get("updated_at")?,
     })
 }
 
+// House rule columns of a properties row
+fn row_to_house_rules(row: &sqlx::postgres::PgRow) -> Result<HouseRules, AppError> {
+    Ok(HouseRules {
+        check_in_from: row.try_get("check_in_from")?,
+        check_in_until: row.try_get("check_in_until")?,
+        check_out_until: row.try_get("check_out_until")?,
+        pets_allowed: row.try_get("pets_allowed")?,
+        smoking_allowed: row.try_get("smoking_allowed")?,
+        events_allowed: row.try_get("events_allowed")?,
+        children_allowed: row.try_get("children_allowed")?,
+        quiet_hours_start: row.try_get("quiet_hours_start")?,
+        quiet_hours_end: row.try_get("quiet_hours_end")?,
+        additional_rules: row.try_get("additional_rules")?,
+    })
+}
+
 // Helper function to convert database row to Booking
 pub fn row_to_booking(row: sqlx::postgres::PgRow) -> Result<Booking, AppError> {
     Ok(Booking {
@@ -69,7 +104,7 @@ pub fn row_to_booking(row: sqlx::postgres::PgRow) -> Result<Booking, AppError> {
         check_in_date: row.try_get("check_in_date")?,
         check_out_date: row.try_get("check_out_date")?,
         total_price: row.try_get("total_price")?,
-        status: match row.try_get::<&str, _>("status")? {
+        status: match row.try_get_unchecked::<&str, _>("status")? {
             "pending" => BookingStatus::Pending,
             "approved" => BookingStatus::Approved,
             "denied" => BookingStatus::Denied,
@@ -79,6 +114,171 @@ pub fn row_to_booking(row: sqlx::postgres::PgRow) -> Result<Booking, AppError> {
         },
         guest_count: row.try_get("guest_count")?,
         special_requests: row.try_get("special_requests")?,
+        room_type_id: row.try_get("room_type_id")?,
+        units: row.try_get("units")?,
+        accepted_house_rules: row
+            .try_get::<Option<sqlx::types::Json<HouseRules>>, _>("accepted_house_rules")?
+            .map(|rules| rules.0),
+        house_rules_accepted_at: row.try_get("house_rules_accepted_at")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+// Helper function to convert database row to RoomType
+pub fn row_to_room_type(row: sqlx::postgres::PgRow) -> Result<RoomType, AppError> {
+    Ok(RoomType {
+        id: row.try_get("id")?,
+        property_id: row.try_get("property_id")?,
+        name: row.try_get("name")?,
+        description: row.try_get("description")?,
+        kind: match row.try_get_unchecked::<&str, _>("kind")? {
+            "room" => UnitKind::Room,
+            "dorm_bed" => UnitKind::DormBed,
+            _ => return Err(AppError::Internal("Invalid unit kind".to_string())),
+        },
+        max_guests: row.try_get("max_guests")?,
+        price_per_night: row.try_get("price_per_night")?,
+        total_units: row.try_get("total_units")?,
+        is_active: row.try_get("is_active")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+// Helper function to convert database row to Amenity
+pub fn row_to_amenity(row: sqlx::postgres::PgRow) -> Result<Amenity, AppError> {
+    Ok(Amenity {
+        key: row.try_get("key")?,
+        label: row.try_get("label")?,
+        category: row.try_get("category")?,
+        icon: row.try_get("icon")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+// Helper function to convert database row to PropertyPhoto
+pub fn row_to_photo(row: sqlx::postgres::PgRow) -> Result<PropertyPhoto, AppError> {
+    Ok(PropertyPhoto {
+        id: row.try_get("id")?,
+        property_id: row.try_get("property_id")?,
+        storage_key: row.try_get("storage_key")?,
+        external_url: row.try_get("external_url")?,
+        content_type: row.try_get("content_type")?,
+        width: row.try_get("width")?,
+        height: row.try_get("height")?,
+        size_bytes: row.try_get("size_bytes")?,
+        caption: row.try_get("caption")?,
+        position: row.try_get("position")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+// Helper function to convert database row to Review
+pub fn row_to_review(row: sqlx::postgres::PgRow) -> Result<Review, AppError> {
+    Ok(Review {
+        id: row.try_get("id")?,
+        booking_id: row.try_get("booking_id")?,
+        property_id: row.try_get("property_id")?,
+        guest_id: row.try_get("guest_id")?,
+        overall_rating: row.try_get("overall_rating")?,
+        cleanliness_rating: row.try_get("cleanliness_rating")?,
+        accuracy_rating: row.try_get("accuracy_rating")?,
+        location_rating: row.try_get("location_rating")?,
+        value_rating: row.try_get("value_rating")?,
+        comment: row.try_get("comment")?,
+        published_at: row.try_get("published_at")?,
+        host_reply: row.try_get("host_reply")?,
+        host_replied_at: row.try_get("host_replied_at")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+// Helper function to convert database row to GuestReview
+pub fn row_to_guest_review(row: sqlx::postgres::PgRow) -> Result<GuestReview, AppError> {
+    Ok(GuestReview {
+        id: row.try_get("id")?,
+        booking_id: row.try_get("booking_id")?,
+        guest_id: row.try_get("guest_id")?,
+        host_id: row.try_get("host_id")?,
+        overall_rating: row.try_get("overall_rating")?,
+        cleanliness_rating: row.try_get("cleanliness_rating")?,
+        communication_rating: row.try_get("communication_rating")?,
+        house_rules_rating: row.try_get("house_rules_rating")?,
+        comment: row.try_get("comment")?,
+        published_at: row.try_get("published_at")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+// Helper function to convert database row to SavedSearch
+pub fn row_to_saved_search(row: sqlx::postgres::PgRow) -> Result<SavedSearch, AppError> {
+    let filters: sqlx::types::Json<PropertyFilters> = row.try_get("filters")?;
+
+    Ok(SavedSearch {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        name: row.try_get("name")?,
+        filters: filters.0,
+        alerts_enabled: row.try_get("alerts_enabled")?,
+        last_checked_at: row.try_get("last_checked_at")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+// Helper function to convert database row to Notification
+pub fn row_to_notification(row: sqlx::postgres::PgRow) -> Result<Notification, AppError> {
+    Ok(Notification {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        kind: NotificationKind::parse(row.try_get("kind")?)
+            .ok_or_else(|| AppError::Internal("Invalid notification kind".to_string()))?,
+        title: row.try_get("title")?,
+        body: row.try_get("body")?,
+        data: row.try_get("data")?,
+        read_at: row.try_get("read_at")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+// Helper function to convert database row to Session
+pub fn row_to_session(row: sqlx::postgres::PgRow) -> Result<Session, AppError> {
+    Ok(Session {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        user_agent: row.try_get("user_agent")?,
+        ip_address: row.try_get("ip_address")?,
+        expires_at: row.try_get("expires_at")?,
+        last_seen_at: row.try_get("last_seen_at")?,
+        revoked_at: row.try_get("revoked_at")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+// Helper function to convert database row to RefreshToken
+pub fn row_to_refresh_token(row: sqlx::postgres::PgRow) -> Result<RefreshToken, AppError> {
+    Ok(RefreshToken {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        session_id: row.try_get("session_id")?,
+        expires_at: row.try_get("expires_at")?,
+        used_at: row.try_get("used_at")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+// Helper function to convert database row to Wishlist
+pub fn row_to_wishlist(row: sqlx::postgres::PgRow) -> Result<Wishlist, AppError> {
+    Ok(Wishlist {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        name: row.try_get("name")?,
+        share_token: row.try_get("share_token")?,
         created_at: row.try_get("created_at")?,
         updated_at: row.try_get("updated_at")?,
     })
@@ -102,7 +302,7 @@ pub async fn create_user(
     let row = sqlx::query(
         r#"
         INSERT INTO users (email, password_hash, first_name, last_name, role)
-        VALUES ($1, $2, $3, $4, $5)
+        VALUES ($1, $2, $3, $4, $5::user_role)
         RETURNING *
         "#
     )
@@ -141,33 +341,48 @@ pub async fn get_user_by_id(pool: &PgPool, id: Uuid) -> Result<Option<User>, App
     }
 }
 
+// Batch load users keyed by id, in a single query regardless of how many ids are given
+pub async fn get_users_by_ids(pool: &PgPool, ids: &[Uuid]) -> Result<HashMap<Uuid, User>, AppError> {
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+
+    let rows = sqlx::query("SELECT * FROM users WHERE id = ANY($1)")
+        .bind(&ids)
+        .fetch_all(pool)
+        .await?;
+
+    rows.into_iter()
+        .map(|row| row_to_user(row).map(|user| (user.id, user)))
+        .collect()
+}
+
 // Property operations
 pub async fn create_property_db(
     pool: &PgPool,
     owner_id: Uuid,
     request: &CreatePropertyRequest,
 ) -> Result<Property, AppError> {
-    let property_type_str = match request.property_type {
-        PropertyType::Hotel => "hotel",
-        PropertyType::Hostel => "hostel",
-        PropertyType::Apartment => "apartment",
-    };
-
     let row = sqlx::query(
         r#"
         INSERT INTO properties (
             owner_id, title, description, property_type, location, address, city, country,
             postal_code, latitude, longitude, price_per_night, max_guests, bedrooms,
-            bathrooms, amenities, images
+            bathrooms, amenities, attributes, check_in_from, check_in_until, check_out_until,
+            pets_allowed, smoking_allowed, events_allowed, children_allowed, quiet_hours_start,
+            quiet_hours_end, additional_rules, timezone
+        )
+        VALUES (
+            $1, $2, $3, $4::property_type, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
+            $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
         RETURNING *
         "#
     )
     .bind(owner_id)
     .bind(&request.title)
     .bind(&request.description)
-    .bind(property_type_str)
+    .bind(request.property_type.as_str())
     .bind(&request.location)
     .bind(&request.address)
     .bind(&request.city)
@@ -180,41 +395,344 @@ pub async fn create_property_db(
     .bind(request.bedrooms)
     .bind(request.bathrooms)
     .bind(&request.amenities)
-    .bind(&request.images)
+    .bind(sqlx::types::Json(&request.attributes))
+    .bind(request.house_rules.check_in_from)
+    .bind(request.house_rules.check_in_until)
+    .bind(request.house_rules.check_out_until)
+    .bind(request.house_rules.pets_allowed)
+    .bind(request.house_rules.smoking_allowed)
+    .bind(request.house_rules.events_allowed)
+    .bind(request.house_rules.children_allowed)
+    .bind(request.house_rules.quiet_hours_start)
+    .bind(request.house_rules.quiet_hours_end)
+    .bind(&request.house_rules.additional_rules)
+    .bind(&request.timezone)
     .fetch_one(pool)
     .await?;
 
     row_to_property(row)
 }
 
-pub async fn get_properties_with_filters(
+pub async fn update_property_timezone(
     pool: &PgPool,
-    filters: &PropertyFilters,
-) -> Result<(Vec<Property>, i64), AppError> {
-    // For now, return a simple query without complex filtering
-    // In production, you'd implement proper dynamic query building
+    property_id: Uuid,
+    timezone: Option<&str>,
+) -> Result<Property, AppError> {
+    let row = sqlx::query("UPDATE properties SET timezone = $2 WHERE id = $1 RETURNING *")
+        .bind(property_id)
+        .bind(timezone)
+        .fetch_one(pool)
+        .await?;
+
+    row_to_property(row)
+}
+
+// Derive the timezone of listings created before timezones were stored
+pub async fn backfill_property_timezones(pool: &PgPool) -> Result<u64, AppError> {
     let rows = sqlx::query(
-        "SELECT * FROM properties WHERE is_active = true ORDER BY created_at DESC LIMIT $1 OFFSET $2"
+        "SELECT id, latitude, longitude FROM properties WHERE timezone IS NULL AND latitude IS NOT NULL AND longitude IS NOT NULL"
     )
-    .bind(filters.per_page.unwrap_or(10))
-    .bind((filters.page.unwrap_or(1) - 1) * filters.per_page.unwrap_or(10))
     .fetch_all(pool)
     .await?;
 
+    let mut property_ids = Vec::with_capacity(rows.len());
+    let mut timezones = Vec::with_capacity(rows.len());
+    for row in rows {
+        property_ids.push(row.try_get::<Uuid, _>("id")?);
+        timezones.push(timezone::lookup(row.try_get("latitude")?, row.try_get("longitude")?));
+    }
+
+    let result = sqlx::query(
+        r#"
+        UPDATE properties p
+        SET timezone = t.timezone
+        FROM UNNEST($1::UUID[], $2::TEXT[]) AS t(id, timezone)
+        WHERE p.id = t.id AND p.timezone IS NULL
+        "#
+    )
+    .bind(&property_ids)
+    .bind(&timezones)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn update_house_rules(
+    pool: &PgPool,
+    property_id: Uuid,
+    rules: &HouseRules,
+) -> Result<Property, AppError> {
+    let row = sqlx::query(
+        r#"
+        UPDATE properties
+        SET check_in_from = $2, check_in_until = $3, check_out_until = $4, pets_allowed = $5,
+            smoking_allowed = $6, events_allowed = $7, children_allowed = $8,
+            quiet_hours_start = $9, quiet_hours_end = $10, additional_rules = $11
+        WHERE id = $1
+        RETURNING *
+        "#
+    )
+    .bind(property_id)
+    .bind(rules.check_in_from)
+    .bind(rules.check_in_until)
+    .bind(rules.check_out_until)
+    .bind(rules.pets_allowed)
+    .bind(rules.smoking_allowed)
+    .bind(rules.events_allowed)
+    .bind(rules.children_allowed)
+    .bind(rules.quiet_hours_start)
+    .bind(rules.quiet_hours_end)
+    .bind(&rules.additional_rules)
+    .fetch_one(pool)
+    .await?;
+
+    row_to_property(row)
+}
+
+// Append WHERE conditions shared by the listing query and its count query
+fn push_property_filters(query: &mut QueryBuilder<'_, Postgres>, filters: &PropertyFilters) {
+    if let Some(location) = filters.location.as_deref().map(str::trim).filter(|l| !l.is_empty()) {
+        let pattern = format!("%{}%", escape_like(location));
+        query
+            .push(" AND (location ILIKE ")
+            .push_bind(pattern.clone())
+            .push(" OR city ILIKE ")
+            .push_bind(pattern.clone())
+            .push(" OR country ILIKE ")
+            .push_bind(pattern)
+            .push(")");
+    }
+
+    if let Some(property_type) = &filters.property_type {
+        query
+            .push(" AND property_type = ")
+            .push_bind(property_type.as_str())
+            .push("::property_type");
+    }
+
+    if let Some(min_price) = filters.min_price {
+        query.push(" AND price_per_night >= ").push_bind(min_price);
+    }
+
+    if let Some(max_price) = filters.max_price {
+        query.push(" AND price_per_night <= ").push_bind(max_price);
+    }
+
+    if let Some(guests) = filters.max_guests {
+        query.push(" AND max_guests >= ").push_bind(guests);
+    }
+
+    // Room-type aware availability, see the property_is_available SQL function
+    if let Some((check_in_date, check_out_date)) = filters.stay_dates() {
+        query
+            .push(" AND property_is_available(properties.id, ")
+            .push_bind(check_in_date)
+            .push(", ")
+            .push_bind(check_out_date)
+            .push(")");
+    }
+
+    let amenity_keys = filters.amenity_keys();
+    if !amenity_keys.is_empty() {
+        query.push(" AND amenities @> ").push_bind(amenity_keys);
+    }
+
+    if let Some(attributes) = filters.attribute_filter() {
+        query.push(" AND attributes @> ").push_bind(sqlx::types::Json(attributes));
+    }
+
+    let rule_filters = [
+        ("pets_allowed", filters.pets_allowed),
+        ("smoking_allowed", filters.smoking_allowed),
+        ("events_allowed", filters.events_allowed),
+        ("children_allowed", filters.children_allowed),
+    ];
+    for (column, value) in rule_filters {
+        if let Some(value) = value {
+            query.push(format!(" AND {} = ", column)).push_bind(value);
+        }
+    }
+
+    // Listings without coordinates cannot be ranked by distance
+    if filters.sort() == PropertySort::Distance {
+        query.push(" AND approximate_latitude IS NOT NULL AND approximate_longitude IS NOT NULL");
+    }
+}
+
+fn distance_origin(filters: &PropertyFilters) -> Result<(f64, f64), AppError> {
+    match (filters.lat, filters.lng) {
+        (Some(lat), Some(lng)) => Ok((lat, lng)),
+        _ => Err(AppError::Validation(
+            "lat and lng are required when sorting by distance".to_string(),
+        )),
+    }
+}
+
+// Active, filtered properties with their sort key exposed as `sort_key`, aliased as `listed`
+fn push_listing_source(
+    query: &mut QueryBuilder<'_, Postgres>,
+    filters: &PropertyFilters,
+) -> Result<(), AppError> {
+    query.push("SELECT * FROM (SELECT properties.*, ");
+
+    match filters.sort() {
+        PropertySort::Newest => {
+            query.push("created_at");
+        }
+        PropertySort::PriceAsc | PropertySort::PriceDesc => {
+            query.push("price_per_night");
+        }
+        PropertySort::Rating => {
+            query.push("COALESCE(average_rating, 0)");
+        }
+        PropertySort::Distance => {
+            // Haversine great-circle distance in kilometres. Measured to the public,
+            // approximate location: exact distances in cursors would reveal the address.
+            let (lat, lng) = distance_origin(filters)?;
+            query
+                .push("6371.0 * 2 * ASIN(LEAST(1, SQRT(POWER(SIN(RADIANS(approximate_latitude - ")
+                .push_bind(lat)
+                .push(") / 2), 2) + COS(RADIANS(")
+                .push_bind(lat)
+                .push(")) * COS(RADIANS(approximate_latitude)) * POWER(SIN(RADIANS(approximate_longitude - ")
+                .push_bind(lng)
+                .push(") / 2), 2))))");
+        }
+    }
+
+    query.push(" AS sort_key FROM properties WHERE is_active = true AND status = 'published'");
+    push_property_filters(query, filters);
+    query.push(") listed");
+
+    Ok(())
+}
+
+fn push_listing_order(query: &mut QueryBuilder<'_, Postgres>, sort: PropertySort) {
+    if sort.is_ascending() {
+        query.push(" ORDER BY sort_key ASC, id ASC");
+    } else {
+        query.push(" ORDER BY sort_key DESC, id DESC");
+    }
+}
+
+fn row_to_listing_cursor(
+    row: &sqlx::postgres::PgRow,
+    sort: PropertySort,
+) -> Result<ListingCursor, AppError> {
+    let key = match sort {
+        PropertySort::Newest => CursorKey::CreatedAt(row.try_get("sort_key")?),
+        PropertySort::PriceAsc | PropertySort::PriceDesc => CursorKey::Price(row.try_get("sort_key")?),
+        PropertySort::Rating => CursorKey::Rating(row.try_get("sort_key")?),
+        PropertySort::Distance => CursorKey::Distance(row.try_get("sort_key")?),
+    };
+
+    Ok(ListingCursor {
+        sort,
+        key,
+        id: row.try_get("id")?,
+    })
+}
+
+pub async fn get_properties_with_filters(
+    pool: &PgPool,
+    filters: &PropertyFilters,
+) -> Result<(Vec<Property>, i64), AppError> {
+    let per_page = filters.per_page();
+    let offset = (filters.page() - 1) * per_page;
+
+    let mut query = QueryBuilder::new("");
+    push_listing_source(&mut query, filters)?;
+    push_listing_order(&mut query, filters.sort());
+    query
+        .push(" LIMIT ")
+        .push_bind(per_page)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    let rows = query.build().fetch_all(pool).await?;
+
     let properties: Result<Vec<Property>, AppError> = rows.into_iter()
         .map(row_to_property)
         .collect();
     let properties = properties?;
 
-    let total = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(*) FROM properties WHERE is_active = true"
-    )
-    .fetch_one(pool)
-    .await?;
+    let mut count_query = QueryBuilder::new(
+        "SELECT COUNT(*) FROM properties WHERE is_active = true AND status = 'published'"
+    );
+    push_property_filters(&mut count_query, filters);
+
+    let total = count_query
+        .build_query_scalar::<i64>()
+        .fetch_one(pool)
+        .await?;
 
     Ok((properties, total))
 }
 
+// Keyset pagination: rows strictly after `cursor` in the requested sort order.
+// Returns the page and, if more rows follow, the cursor for the next page.
+pub async fn get_properties_after_cursor(
+    pool: &PgPool,
+    filters: &PropertyFilters,
+    cursor: Option<&ListingCursor>,
+) -> Result<(Vec<Property>, Option<ListingCursor>), AppError> {
+    let sort = filters.sort();
+    let per_page = filters.per_page();
+
+    let mut query = QueryBuilder::new("");
+    push_listing_source(&mut query, filters)?;
+
+    if let Some(cursor) = cursor {
+        if cursor.sort != sort {
+            return Err(AppError::Validation(
+                "Cursor does not match the requested sort order".to_string(),
+            ));
+        }
+
+        query.push(" WHERE (sort_key, id) ");
+        query.push(if sort.is_ascending() { "> (" } else { "< (" });
+        match (&cursor.key, sort) {
+            (CursorKey::CreatedAt(created_at), PropertySort::Newest) => {
+                query.push_bind(*created_at);
+            }
+            (CursorKey::Price(price), PropertySort::PriceAsc | PropertySort::PriceDesc) => {
+                query.push_bind(*price);
+            }
+            (CursorKey::Rating(rating), PropertySort::Rating) => {
+                query.push_bind(*rating);
+            }
+            (CursorKey::Distance(distance), PropertySort::Distance) => {
+                query.push_bind(*distance);
+            }
+            _ => return Err(AppError::Validation("Invalid cursor".to_string())),
+        }
+        query.push(", ").push_bind(cursor.id).push(")");
+    }
+
+    push_listing_order(&mut query, sort);
+    // Fetch one extra row to find out whether another page exists
+    query.push(" LIMIT ").push_bind(per_page + 1);
+
+    let mut rows = query.build().fetch_all(pool).await?;
+
+    let next_cursor = if rows.len() as i64 > per_page {
+        rows.truncate(per_page as usize);
+        match rows.last() {
+            Some(row) => Some(row_to_listing_cursor(row, sort)?),
+            None => None,
+        }
+    } else {
+        None
+    };
+
+    let properties: Result<Vec<Property>, AppError> = rows.into_iter()
+        .map(row_to_property)
+        .collect();
+
+    Ok((properties?, next_cursor))
+}
+
 pub async fn get_property_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Property>, AppError> {
     let row = sqlx::query("SELECT * FROM properties WHERE id = $1 AND is_active = true")
         .bind(id)
@@ -227,6 +745,39 @@ pub async fn get_property_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Proper
     }
 }
 
+// Batch load properties keyed by id. Inactive properties are included so that
+// existing bookings still resolve after a listing is taken down.
+pub async fn get_properties_by_ids(
+    pool: &PgPool,
+    ids: &[Uuid],
+) -> Result<HashMap<Uuid, Property>, AppError> {
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+
+    let rows = sqlx::query("SELECT * FROM properties WHERE id = ANY($1)")
+        .bind(&ids)
+        .fetch_all(pool)
+        .await?;
+
+    rows.into_iter()
+        .map(|row| row_to_property(row).map(|property| (property.id, property)))
+        .collect()
+}
+
+// Only published listings are visible to guests
+pub async fn get_published_property_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Property>, AppError> {
+    let row = sqlx::query("SELECT * FROM properties WHERE id = $1 AND is_active = true AND status = 'published'")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_property(row)?)),
+        None => Ok(None),
+    }
+}
+
 pub async fn get_properties_by_owner(
     pool: &PgPool,
     owner_id: Uuid,
@@ -243,54 +794,428 @@ pub async fn get_properties_by_owner(
     properties
 }
 
-// Booking operations
-pub async fn create_booking_db(
-    pool: &PgPool,
-    property_id: Uuid,
-    user_id: Uuid,
-    check_in_date: Date,
-    check_out_date: Date,
-    total_price: i32,
-    guest_count: i32,
-    special_requests: Option<&str>,
-) -> Result<Booking, AppError> {
+// Listing moderation operations
+pub async fn submit_property_for_review(pool: &PgPool, id: Uuid) -> Result<Property, AppError> {
     let row = sqlx::query(
         r#"
-        INSERT INTO bookings (
-            property_id, user_id, check_in_date, check_out_date, total_price, guest_count, special_requests
-        )
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        UPDATE properties
+        SET status = 'pending_review', submitted_at = NOW(), rejection_reason = NULL
+        WHERE id = $1 AND status IN ('draft', 'rejected')
         RETURNING *
         "#
     )
-    .bind(property_id)
-    .bind(user_id)
-    .bind(check_in_date)
-    .bind(check_out_date)
-    .bind(total_price)
-    .bind(guest_count)
-    .bind(special_requests)
-    .fetch_one(pool)
-    .await?;
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    // Another request moved the listing on since it was checked
+    .ok_or_else(|| AppError::BadRequest("Only draft or rejected listings can be submitted for review".to_string()))?;
 
-    row_to_booking(row)
+    row_to_property(row)
 }
 
-pub async fn get_booking_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Booking>, AppError> {
-    let row = sqlx::query("SELECT * FROM bookings WHERE id = $1")
-        .bind(id)
-        .fetch_optional(pool)
-        .await?;
+// Record an admin decision on a listing that is pending review
+pub async fn review_property(
+    pool: &PgPool,
+    id: Uuid,
+    admin_id: Uuid,
+    status: ListingStatus,
+    rejection_reason: Option<&str>,
+) -> Result<Property, AppError> {
+    let status_str = match status {
+        ListingStatus::Draft => "draft",
+        ListingStatus::PendingReview => "pending_review",
+        ListingStatus::Published => "published",
+        ListingStatus::Rejected => "rejected",
+    };
 
-    match row {
-        Some(row) => Ok(Some(row_to_booking(row)?)),
-        None => Ok(None),
-    }
+    let row = sqlx::query(
+        r#"
+        UPDATE properties
+        SET status = $2::listing_status, rejection_reason = $3, reviewed_at = NOW(), reviewed_by = $4
+        WHERE id = $1 AND status = 'pending_review'
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(status_str)
+    .bind(rejection_reason)
+    .bind(admin_id)
+    .fetch_optional(pool)
+    .await?
+    // Already decided by another admin, or withdrawn meanwhile
+    .ok_or_else(|| AppError::BadRequest("Only listings pending review can be approved or rejected".to_string()))?;
+
+    row_to_property(row)
 }
 
-pub async fn get_bookings_by_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<Booking>, AppError> {
-    let rows = sqlx::query("SELECT * FROM bookings WHERE user_id = $1 ORDER BY created_at DESC")
-        .bind(user_id)
+// Moderation queue, oldest submission first
+pub async fn get_properties_pending_review(
+    pool: &PgPool,
+    page: i64,
+    per_page: i64,
+) -> Result<(Vec<Property>, i64), AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT * FROM properties
+        WHERE status = 'pending_review' AND is_active = true
+        ORDER BY submitted_at ASC, id ASC
+        LIMIT $1 OFFSET $2
+        "#
+    )
+    .bind(per_page)
+    .bind((page - 1) * per_page)
+    .fetch_all(pool)
+    .await?;
+
+    let properties: Result<Vec<Property>, AppError> = rows.into_iter()
+        .map(row_to_property)
+        .collect();
+
+    let total = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM properties WHERE status = 'pending_review' AND is_active = true"
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((properties?, total))
+}
+
+// Property photo operations
+pub async fn get_photos_by_property(
+    pool: &PgPool,
+    property_id: Uuid,
+) -> Result<Vec<PropertyPhoto>, AppError> {
+    let rows = sqlx::query("SELECT * FROM property_photos WHERE property_id = $1 ORDER BY position, created_at")
+        .bind(property_id)
+        .fetch_all(pool)
+        .await?;
+
+    rows.into_iter().map(row_to_photo).collect()
+}
+
+// Batch load photos for several properties, grouped by property in display order
+pub async fn get_photos_by_property_ids(
+    pool: &PgPool,
+    property_ids: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<PropertyPhoto>>, AppError> {
+    let rows = sqlx::query(
+        "SELECT * FROM property_photos WHERE property_id = ANY($1) ORDER BY property_id, position, created_at"
+    )
+    .bind(property_ids)
+    .fetch_all(pool)
+    .await?;
+
+    let mut photos: HashMap<Uuid, Vec<PropertyPhoto>> = HashMap::new();
+    for row in rows {
+        let photo = row_to_photo(row)?;
+        photos.entry(photo.property_id).or_default().push(photo);
+    }
+
+    Ok(photos)
+}
+
+pub async fn get_photo_by_id(pool: &PgPool, id: Uuid) -> Result<Option<PropertyPhoto>, AppError> {
+    let row = sqlx::query("SELECT * FROM property_photos WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_photo(row)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn count_photos_for_property(pool: &PgPool, property_id: Uuid) -> Result<i64, AppError> {
+    let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM property_photos WHERE property_id = $1")
+        .bind(property_id)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(count)
+}
+
+// New photos are appended after the property's last photo
+#[allow(clippy::too_many_arguments)]
+pub async fn create_photo(
+    pool: &PgPool,
+    id: Uuid,
+    property_id: Uuid,
+    storage_key: &str,
+    content_type: &str,
+    width: i32,
+    height: i32,
+    size_bytes: i64,
+    caption: Option<&str>,
+) -> Result<PropertyPhoto, AppError> {
+    let mut tx = pool.begin().await?;
+
+    // Concurrent uploads to one property take turns, so the cap holds
+    sqlx::query("SELECT id FROM properties WHERE id = $1 FOR UPDATE")
+        .bind(property_id)
+        .execute(&mut *tx)
+        .await?;
+    let photo_count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM property_photos WHERE property_id = $1")
+        .bind(property_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if photo_count >= MAX_PHOTOS_PER_PROPERTY {
+        return Err(AppError::BadRequest(format!(
+            "A property can have at most {} photos",
+            MAX_PHOTOS_PER_PROPERTY
+        )));
+    }
+
+    let row = sqlx::query(
+        r#"
+        INSERT INTO property_photos (
+            id, property_id, storage_key, content_type, width, height, size_bytes, caption, position
+        )
+        VALUES (
+            $1, $2, $3, $4, $5, $6, $7, $8,
+            (SELECT COALESCE(MAX(position) + 1, 0) FROM property_photos WHERE property_id = $2)
+        )
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(property_id)
+    .bind(storage_key)
+    .bind(content_type)
+    .bind(width)
+    .bind(height)
+    .bind(size_bytes)
+    .bind(caption)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    row_to_photo(row)
+}
+
+pub async fn update_photo_caption(
+    pool: &PgPool,
+    id: Uuid,
+    caption: Option<&str>,
+) -> Result<PropertyPhoto, AppError> {
+    let row = sqlx::query("UPDATE property_photos SET caption = $2 WHERE id = $1 RETURNING *")
+        .bind(id)
+        .bind(caption)
+        .fetch_one(pool)
+        .await?;
+
+    row_to_photo(row)
+}
+
+// Positions are rewritten as 0..n following the order of `photo_ids`
+pub async fn reorder_photos(
+    pool: &PgPool,
+    property_id: Uuid,
+    photo_ids: &[Uuid],
+) -> Result<Vec<PropertyPhoto>, AppError> {
+    sqlx::query(
+        r#"
+        UPDATE property_photos p
+        SET position = ordered.ord - 1
+        FROM unnest($2::UUID[]) WITH ORDINALITY AS ordered(id, ord)
+        WHERE p.id = ordered.id AND p.property_id = $1
+        "#
+    )
+    .bind(property_id)
+    .bind(photo_ids)
+    .execute(pool)
+    .await?;
+
+    get_photos_by_property(pool, property_id).await
+}
+
+pub async fn delete_photo(pool: &PgPool, id: Uuid) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM property_photos WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// Amenity catalog operations
+pub async fn get_amenities(pool: &PgPool) -> Result<Vec<Amenity>, AppError> {
+    let rows = sqlx::query("SELECT * FROM amenities ORDER BY category, label")
+        .fetch_all(pool)
+        .await?;
+
+    rows.into_iter().map(row_to_amenity).collect()
+}
+
+pub async fn get_amenity_by_key(pool: &PgPool, key: &str) -> Result<Option<Amenity>, AppError> {
+    let row = sqlx::query("SELECT * FROM amenities WHERE key = $1")
+        .bind(key)
+        .fetch_optional(pool)
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_amenity(row)?)),
+        None => Ok(None),
+    }
+}
+
+// Returns the subset of `keys` that are not in the catalog
+pub async fn find_unknown_amenity_keys(
+    pool: &PgPool,
+    keys: &[String],
+) -> Result<Vec<String>, AppError> {
+    let unknown = sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT k FROM unnest($1::TEXT[]) AS k
+        WHERE NOT EXISTS (SELECT 1 FROM amenities a WHERE a.key = k)
+        "#
+    )
+    .bind(keys)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(unknown)
+}
+
+pub async fn create_amenity(
+    pool: &PgPool,
+    request: &CreateAmenityRequest,
+) -> Result<Amenity, AppError> {
+    let row = sqlx::query(
+        r#"
+        INSERT INTO amenities (key, label, category, icon)
+        VALUES ($1, $2, $3, $4)
+        RETURNING *
+        "#
+    )
+    .bind(&request.key)
+    .bind(&request.label)
+    .bind(&request.category)
+    .bind(&request.icon)
+    .fetch_one(pool)
+    .await?;
+
+    row_to_amenity(row)
+}
+
+pub async fn update_amenity(
+    pool: &PgPool,
+    key: &str,
+    request: &UpdateAmenityRequest,
+) -> Result<Option<Amenity>, AppError> {
+    let row = sqlx::query(
+        r#"
+        UPDATE amenities
+        SET label = COALESCE($2, label),
+            category = COALESCE($3, category),
+            icon = COALESCE($4, icon)
+        WHERE key = $1
+        RETURNING *
+        "#
+    )
+    .bind(key)
+    .bind(&request.label)
+    .bind(&request.category)
+    .bind(&request.icon)
+    .fetch_optional(pool)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_amenity(row)?)),
+        None => Ok(None),
+    }
+}
+
+// Removes the amenity from the catalog and from every property that lists it
+pub async fn delete_amenity(pool: &PgPool, key: &str) -> Result<bool, AppError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("UPDATE properties SET amenities = array_remove(amenities, $1) WHERE amenities @> ARRAY[$1]::TEXT[]")
+        .bind(key)
+        .execute(&mut *tx)
+        .await?;
+
+    let result = sqlx::query("DELETE FROM amenities WHERE key = $1")
+        .bind(key)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Booking operations
+#[allow(clippy::too_many_arguments)]
+pub async fn create_booking_db(
+    pool: &PgPool,
+    property_id: Uuid,
+    user_id: Uuid,
+    check_in_date: Date,
+    check_out_date: Date,
+    total_price: i32,
+    guest_count: i32,
+    special_requests: Option<&str>,
+    accepted_house_rules: Option<&HouseRules>,
+) -> Result<Booking, AppError> {
+    let row = sqlx::query(
+        r#"
+        INSERT INTO bookings (
+            property_id, user_id, check_in_date, check_out_date, total_price, guest_count, special_requests,
+            accepted_house_rules, house_rules_accepted_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, CASE WHEN $8::JSONB IS NULL THEN NULL ELSE NOW() END)
+        RETURNING *
+        "#
+    )
+    .bind(property_id)
+    .bind(user_id)
+    .bind(check_in_date)
+    .bind(check_out_date)
+    .bind(total_price)
+    .bind(guest_count)
+    .bind(special_requests)
+    .bind(accepted_house_rules.map(sqlx::types::Json))
+    .fetch_one(pool)
+    .await?;
+
+    row_to_booking(row)
+}
+
+// Whether the user has an approved or completed stay at the property
+pub async fn has_confirmed_booking(pool: &PgPool, user_id: Uuid, property_id: Uuid) -> Result<bool, AppError> {
+    let confirmed = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM bookings
+            WHERE user_id = $1 AND property_id = $2 AND status IN ('approved', 'completed')
+        )
+        "#
+    )
+    .bind(user_id)
+    .bind(property_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(confirmed)
+}
+
+pub async fn get_booking_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Booking>, AppError> {
+    let row = sqlx::query("SELECT * FROM bookings WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_booking(row)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn get_bookings_by_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<Booking>, AppError> {
+    let rows = sqlx::query("SELECT * FROM bookings WHERE user_id = $1 ORDER BY created_at DESC")
+        .bind(user_id)
         .fetch_all(pool)
         .await?;
 
@@ -330,7 +1255,7 @@ pub async fn update_booking_status(
     let row = sqlx::query(
         r#"
         UPDATE bookings 
-        SET status = $1, updated_at = NOW()
+        SET status = $1::booking_status, updated_at = NOW()
         WHERE id = $2
         RETURNING *
         "#
@@ -343,34 +1268,92 @@ pub async fn update_booking_status(
     row_to_booking(row)
 }
 
-// Check property availability
+// Mark approved stays whose check-out date has passed as completed
+pub async fn complete_finished_bookings(pool: &PgPool) -> Result<u64, AppError> {
+    // Local time is at most 14 hours ahead of UTC, so only stays checking out by
+    // tomorrow (UTC) can be over; the property's local check-out time decides
+    let rows = sqlx::query(
+        r#"
+        SELECT b.id, b.check_out_date, p.check_out_until, p.timezone
+        FROM bookings b
+        JOIN properties p ON p.id = b.property_id
+        WHERE b.status = 'approved' AND b.check_out_date <= (NOW() AT TIME ZONE 'UTC')::DATE + 1
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let now = OffsetDateTime::now_utc();
+    let mut booking_ids = Vec::new();
+    for row in rows {
+        let timezone_name: Option<String> = row.try_get("timezone")?;
+        let check_out = timezone::local_datetime(
+            timezone_name.as_deref().unwrap_or(timezone::DEFAULT_TIMEZONE),
+            row.try_get::<Date, _>("check_out_date")?,
+            row.try_get::<Time, _>("check_out_until")?,
+        );
+        if check_out <= now {
+            booking_ids.push(row.try_get::<Uuid, _>("id")?);
+        }
+    }
+
+    if booking_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let result = sqlx::query(
+        r#"
+        UPDATE bookings
+        SET status = 'completed', updated_at = NOW()
+        WHERE id = ANY($1) AND status = 'approved'
+        "#
+    )
+    .bind(&booking_ids)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Check property availability: a free room type unit, or no overlapping
+// whole-property booking for properties without room types
 pub async fn check_property_availability(
     pool: &PgPool,
     property_id: Uuid,
     check_in_date: Date,
     check_out_date: Date,
 ) -> Result<bool, AppError> {
-    let row = sqlx::query(
+    let is_available = sqlx::query_scalar::<_, bool>("SELECT property_is_available($1, $2, $3)")
+        .bind(property_id)
+        .bind(check_in_date)
+        .bind(check_out_date)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(is_available)
+}
+
+// Of the given properties, those not available for the whole stay
+pub async fn get_unavailable_property_ids(
+    pool: &PgPool,
+    property_ids: &[Uuid],
+    check_in_date: Date,
+    check_out_date: Date,
+) -> Result<HashSet<Uuid>, AppError> {
+    let ids = sqlx::query_scalar::<_, Uuid>(
         r#"
-        SELECT COUNT(*) as count
-        FROM bookings
-        WHERE property_id = $1 
-        AND status IN ('pending', 'approved')
-        AND (
-            (check_in_date <= $2 AND check_out_date >= $2) OR
-            (check_in_date <= $3 AND check_out_date >= $3) OR
-            (check_in_date >= $2 AND check_out_date <= $3)
-        )
+        SELECT id
+        FROM UNNEST($1::UUID[]) AS id
+        WHERE NOT property_is_available(id, $2, $3)
         "#
     )
-    .bind(property_id)
+    .bind(property_ids)
     .bind(check_in_date)
     .bind(check_out_date)
-    .fetch_one(pool)
+    .fetch_all(pool)
     .await?;
 
-    let count: i64 = row.try_get("count")?;
-    Ok(count == 0)
+    Ok(ids.into_iter().collect())
 }
 
 // Calculate total price for a booking
@@ -389,3 +1372,2283 @@ pub async fn calculate_booking_price(
 
     Ok(total_price)
 }
+
+// Escape LIKE wildcards in user input so it can be used as a literal prefix
+pub fn escape_like(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Location autocomplete: prefix matches rank first, then trigram word similarity
+pub async fn get_search_suggestions(
+    pool: &PgPool,
+    query: &str,
+    limit: i64,
+) -> Result<Vec<SearchSuggestion>, AppError> {
+    let prefix = format!("{}%", escape_like(query));
+
+    let rows = sqlx::query(
+        r#"
+        SELECT kind, value, city, country, listing_count, score
+        FROM (
+            SELECT 'city' AS kind, city AS value, city, country, COUNT(*) AS listing_count,
+                   (word_similarity($1, city) + CASE WHEN city ILIKE $2 THEN 1 ELSE 0 END)::REAL AS score
+            FROM properties
+            WHERE is_active = true AND status = 'published' AND ($1 <% city OR city ILIKE $2)
+            GROUP BY city, country
+
+            UNION ALL
+
+            SELECT 'country', country, NULL::VARCHAR, country, COUNT(*),
+                   (word_similarity($1, country) + CASE WHEN country ILIKE $2 THEN 1 ELSE 0 END)::REAL
+            FROM properties
+            WHERE is_active = true AND status = 'published' AND ($1 <% country OR country ILIKE $2)
+            GROUP BY country
+
+            UNION ALL
+
+            SELECT 'location', location, city, country, COUNT(*),
+                   (word_similarity($1, location) + CASE WHEN location ILIKE $2 THEN 1 ELSE 0 END)::REAL
+            FROM properties
+            WHERE is_active = true AND status = 'published' AND ($1 <% location OR location ILIKE $2)
+            GROUP BY location, city, country
+        ) suggestions
+        ORDER BY score DESC, listing_count DESC, value
+        LIMIT $3
+        "#
+    )
+    .bind(query)
+    .bind(&prefix)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(SearchSuggestion {
+                kind: match row.try_get::<&str, _>("kind")? {
+                    "city" => SuggestionKind::City,
+                    "country" => SuggestionKind::Country,
+                    "location" => SuggestionKind::Location,
+                    _ => return Err(AppError::Internal("Invalid suggestion kind".to_string())),
+                },
+                value: row.try_get("value")?,
+                city: row.try_get("city")?,
+                country: row.try_get("country")?,
+                listing_count: row.try_get("listing_count")?,
+                score: row.try_get("score")?,
+            })
+        })
+        .collect()
+}
+
+// Review operations
+pub async fn get_review_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Review>, AppError> {
+    let row = sqlx::query("SELECT * FROM reviews WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_review(row)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn get_review_by_booking(pool: &PgPool, booking_id: Uuid) -> Result<Option<Review>, AppError> {
+    let row = sqlx::query("SELECT * FROM reviews WHERE booking_id = $1")
+        .bind(booking_id)
+        .fetch_optional(pool)
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_review(row)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn get_guest_review_by_booking(
+    pool: &PgPool,
+    booking_id: Uuid,
+) -> Result<Option<GuestReview>, AppError> {
+    let row = sqlx::query("SELECT * FROM guest_reviews WHERE booking_id = $1")
+        .bind(booking_id)
+        .fetch_optional(pool)
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_guest_review(row)?)),
+        None => Ok(None),
+    }
+}
+
+fn already_reviewed(error: sqlx::Error) -> AppError {
+    match error {
+        sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+            AppError::BadRequest("This booking has already been reviewed".to_string())
+        }
+        e => AppError::from(e),
+    }
+}
+
+// Serializes the guest's and the host's submissions for one booking, so the
+// second writer always sees the first and publishes both
+async fn lock_booking(tx: &mut sqlx::Transaction<'_, Postgres>, booking_id: Uuid) -> Result<(), AppError> {
+    sqlx::query("SELECT id FROM bookings WHERE id = $1 FOR UPDATE")
+        .bind(booking_id)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(())
+}
+
+// Publish both sides of a booking's reviews once both have been submitted
+async fn publish_if_both_reviewed(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    booking_id: Uuid,
+) -> Result<bool, AppError> {
+    let both_submitted = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (SELECT 1 FROM reviews WHERE booking_id = $1)
+            AND EXISTS (SELECT 1 FROM guest_reviews WHERE booking_id = $1)
+        "#
+    )
+    .bind(booking_id)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    if !both_submitted {
+        return Ok(false);
+    }
+
+    sqlx::query("UPDATE reviews SET published_at = NOW() WHERE booking_id = $1 AND published_at IS NULL")
+        .bind(booking_id)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query("UPDATE guest_reviews SET published_at = NOW() WHERE booking_id = $1 AND published_at IS NULL")
+        .bind(booking_id)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(true)
+}
+
+// Insert a guest's review; publishes it (and refreshes the property's rating) if the host already reviewed
+pub async fn create_review_db(
+    pool: &PgPool,
+    booking: &Booking,
+    review_data: &CreateReviewRequest,
+) -> Result<Review, AppError> {
+    let mut tx = pool.begin().await?;
+    lock_booking(&mut tx, booking.id).await?;
+
+    let row = sqlx::query(
+        r#"
+        INSERT INTO reviews (
+            booking_id, property_id, guest_id, overall_rating, cleanliness_rating,
+            accuracy_rating, location_rating, value_rating, comment
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING *
+        "#
+    )
+    .bind(booking.id)
+    .bind(booking.property_id)
+    .bind(booking.user_id)
+    .bind(review_data.overall_rating)
+    .bind(review_data.cleanliness_rating)
+    .bind(review_data.accuracy_rating)
+    .bind(review_data.location_rating)
+    .bind(review_data.value_rating)
+    .bind(&review_data.comment)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(already_reviewed)?;
+    let review = row_to_review(row)?;
+
+    let review = if publish_if_both_reviewed(&mut tx, booking.id).await? {
+        refresh_property_rating(&mut tx, booking.property_id).await?;
+        let row = sqlx::query("SELECT * FROM reviews WHERE id = $1")
+            .bind(review.id)
+            .fetch_one(&mut *tx)
+            .await?;
+        row_to_review(row)?
+    } else {
+        review
+    };
+
+    tx.commit().await?;
+
+    Ok(review)
+}
+
+// Insert a host's review of the guest; publishes both sides if the guest already reviewed
+pub async fn create_guest_review_db(
+    pool: &PgPool,
+    booking: &Booking,
+    host_id: Uuid,
+    review_data: &CreateGuestReviewRequest,
+) -> Result<GuestReview, AppError> {
+    let mut tx = pool.begin().await?;
+    lock_booking(&mut tx, booking.id).await?;
+
+    let row = sqlx::query(
+        r#"
+        INSERT INTO guest_reviews (
+            booking_id, guest_id, host_id, overall_rating, cleanliness_rating,
+            communication_rating, house_rules_rating, comment
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING *
+        "#
+    )
+    .bind(booking.id)
+    .bind(booking.user_id)
+    .bind(host_id)
+    .bind(review_data.overall_rating)
+    .bind(review_data.cleanliness_rating)
+    .bind(review_data.communication_rating)
+    .bind(review_data.house_rules_rating)
+    .bind(&review_data.comment)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(already_reviewed)?;
+    let guest_review = row_to_guest_review(row)?;
+
+    let guest_review = if publish_if_both_reviewed(&mut tx, booking.id).await? {
+        refresh_property_rating(&mut tx, booking.property_id).await?;
+        let row = sqlx::query("SELECT * FROM guest_reviews WHERE id = $1")
+            .bind(guest_review.id)
+            .fetch_one(&mut *tx)
+            .await?;
+        row_to_guest_review(row)?
+    } else {
+        guest_review
+    };
+
+    tx.commit().await?;
+
+    Ok(guest_review)
+}
+
+// Publish every review whose window has closed without the other side responding
+pub async fn publish_expired_reviews(pool: &PgPool, window_days: i64) -> Result<u64, AppError> {
+    // The window closes at the end of the deadline day in property-local time;
+    // that can only have happened once the deadline day has started in UTC
+    let rows = sqlx::query(
+        r#"
+        SELECT b.id, b.check_out_date, p.timezone
+        FROM bookings b
+        JOIN properties p ON p.id = b.property_id
+        WHERE b.check_out_date + $1::INTEGER <= (NOW() AT TIME ZONE 'UTC')::DATE
+        AND (
+            EXISTS (SELECT 1 FROM reviews r WHERE r.booking_id = b.id AND r.published_at IS NULL)
+            OR EXISTS (SELECT 1 FROM guest_reviews g WHERE g.booking_id = b.id AND g.published_at IS NULL)
+        )
+        "#
+    )
+    .bind(window_days as i32)
+    .fetch_all(pool)
+    .await?;
+
+    let now = OffsetDateTime::now_utc();
+    let mut booking_ids = Vec::new();
+    for row in rows {
+        let timezone_name: Option<String> = row.try_get("timezone")?;
+        let check_out_date: Date = row.try_get("check_out_date")?;
+        let closes_at = timezone::local_datetime(
+            timezone_name.as_deref().unwrap_or(timezone::DEFAULT_TIMEZONE),
+            check_out_date + time::Duration::days(window_days + 1),
+            Time::MIDNIGHT,
+        );
+        if closes_at <= now {
+            booking_ids.push(row.try_get::<Uuid, _>("id")?);
+        }
+    }
+
+    if booking_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let property_ids = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        UPDATE reviews
+        SET published_at = NOW()
+        WHERE booking_id = ANY($1) AND published_at IS NULL
+        RETURNING property_id
+        "#
+    )
+    .bind(&booking_ids)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let guest_reviews = sqlx::query(
+        "UPDATE guest_reviews SET published_at = NOW() WHERE booking_id = ANY($1) AND published_at IS NULL"
+    )
+    .bind(&booking_ids)
+    .execute(&mut *tx)
+    .await?;
+
+    let mut refreshed = Vec::new();
+    for property_id in &property_ids {
+        if !refreshed.contains(property_id) {
+            refresh_property_rating(&mut tx, *property_id).await?;
+            refreshed.push(*property_id);
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(property_ids.len() as u64 + guest_reviews.rows_affected())
+}
+
+// Property aggregates only count published reviews
+async fn refresh_property_rating(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    property_id: Uuid,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE properties
+        SET average_rating = stats.average_rating, review_count = stats.review_count
+        FROM (
+            SELECT AVG(overall_rating)::DOUBLE PRECISION AS average_rating, COUNT(*)::INTEGER AS review_count
+            FROM reviews
+            WHERE property_id = $1 AND published_at IS NOT NULL
+        ) AS stats
+        WHERE properties.id = $1
+        "#
+    )
+    .bind(property_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+// Published reviews, newest first
+pub async fn get_reviews_by_property(
+    pool: &PgPool,
+    property_id: Uuid,
+    page: i64,
+    per_page: i64,
+) -> Result<(Vec<Review>, i64), AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT * FROM reviews
+        WHERE property_id = $1 AND published_at IS NOT NULL
+        ORDER BY created_at DESC, id DESC
+        LIMIT $2 OFFSET $3
+        "#
+    )
+    .bind(property_id)
+    .bind(per_page)
+    .bind((page - 1) * per_page)
+    .fetch_all(pool)
+    .await?;
+
+    let reviews: Result<Vec<Review>, AppError> = rows.into_iter()
+        .map(row_to_review)
+        .collect();
+
+    let total = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM reviews WHERE property_id = $1 AND published_at IS NOT NULL"
+    )
+    .bind(property_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((reviews?, total))
+}
+
+// Record the host's single public reply; None if the review already has one
+pub async fn add_host_reply(pool: &PgPool, review_id: Uuid, reply: &str) -> Result<Option<Review>, AppError> {
+    let row = sqlx::query(
+        r#"
+        UPDATE reviews
+        SET host_reply = $2, host_replied_at = NOW()
+        WHERE id = $1 AND host_reply IS NULL
+        RETURNING *
+        "#
+    )
+    .bind(review_id)
+    .bind(reply)
+    .fetch_optional(pool)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_review(row)?)),
+        None => Ok(None),
+    }
+}
+
+// Published host reviews of a guest, newest first
+pub async fn get_guest_reviews_by_guest(
+    pool: &PgPool,
+    guest_id: Uuid,
+    page: i64,
+    per_page: i64,
+) -> Result<(Vec<GuestReview>, i64), AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT * FROM guest_reviews
+        WHERE guest_id = $1 AND published_at IS NOT NULL
+        ORDER BY created_at DESC, id DESC
+        LIMIT $2 OFFSET $3
+        "#
+    )
+    .bind(guest_id)
+    .bind(per_page)
+    .bind((page - 1) * per_page)
+    .fetch_all(pool)
+    .await?;
+
+    let reviews: Result<Vec<GuestReview>, AppError> = rows.into_iter()
+        .map(row_to_guest_review)
+        .collect();
+
+    let total = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM guest_reviews WHERE guest_id = $1 AND published_at IS NOT NULL"
+    )
+    .bind(guest_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((reviews?, total))
+}
+
+pub async fn get_guest_rating_summaries(
+    pool: &PgPool,
+    guest_ids: &[Uuid],
+) -> Result<HashMap<Uuid, GuestRatingSummary>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT guest_id, AVG(overall_rating)::DOUBLE PRECISION AS average_rating, COUNT(*) AS review_count
+        FROM guest_reviews
+        WHERE guest_id = ANY($1) AND published_at IS NOT NULL
+        GROUP BY guest_id
+        "#
+    )
+    .bind(guest_ids)
+    .fetch_all(pool)
+    .await?;
+
+    let mut summaries = HashMap::with_capacity(rows.len());
+    for row in rows {
+        summaries.insert(
+            row.try_get("guest_id")?,
+            GuestRatingSummary {
+                average_rating: row.try_get("average_rating")?,
+                review_count: row.try_get("review_count")?,
+            },
+        );
+    }
+
+    Ok(summaries)
+}
+
+// Wishlist operations
+pub async fn create_wishlist(pool: &PgPool, user_id: Uuid, name: &str) -> Result<Wishlist, AppError> {
+    let row = sqlx::query("INSERT INTO wishlists (user_id, name) VALUES ($1, $2) RETURNING *")
+        .bind(user_id)
+        .bind(name)
+        .fetch_one(pool)
+        .await?;
+
+    row_to_wishlist(row)
+}
+
+// A user's wishlists with their item counts, most recently created first
+pub async fn get_wishlists_by_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<(Wishlist, i64)>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT w.*, COUNT(i.property_id) AS item_count
+        FROM wishlists w
+        LEFT JOIN wishlist_items i ON i.wishlist_id = w.id
+        WHERE w.user_id = $1
+        GROUP BY w.id
+        ORDER BY w.created_at DESC
+        "#
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            let item_count: i64 = row.try_get("item_count")?;
+            Ok((row_to_wishlist(row)?, item_count))
+        })
+        .collect()
+}
+
+pub async fn get_wishlist_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Wishlist>, AppError> {
+    let row = sqlx::query("SELECT * FROM wishlists WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_wishlist(row)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn get_wishlist_by_share_token(pool: &PgPool, share_token: &str) -> Result<Option<Wishlist>, AppError> {
+    let row = sqlx::query("SELECT * FROM wishlists WHERE share_token = $1")
+        .bind(share_token)
+        .fetch_optional(pool)
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_wishlist(row)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn rename_wishlist(pool: &PgPool, id: Uuid, name: &str) -> Result<Wishlist, AppError> {
+    let row = sqlx::query("UPDATE wishlists SET name = $2 WHERE id = $1 RETURNING *")
+        .bind(id)
+        .bind(name)
+        .fetch_one(pool)
+        .await?;
+
+    row_to_wishlist(row)
+}
+
+// Passing None revokes the share link
+pub async fn set_wishlist_share_token(
+    pool: &PgPool,
+    id: Uuid,
+    share_token: Option<&str>,
+) -> Result<Wishlist, AppError> {
+    let row = sqlx::query("UPDATE wishlists SET share_token = $2 WHERE id = $1 RETURNING *")
+        .bind(id)
+        .bind(share_token)
+        .fetch_one(pool)
+        .await?;
+
+    row_to_wishlist(row)
+}
+
+pub async fn delete_wishlist(pool: &PgPool, id: Uuid) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM wishlists WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// Adding a property that is already saved is a no-op
+pub async fn add_wishlist_item(pool: &PgPool, wishlist_id: Uuid, property_id: Uuid) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO wishlist_items (wishlist_id, property_id)
+        VALUES ($1, $2)
+        ON CONFLICT (wishlist_id, property_id) DO NOTHING
+        "#
+    )
+    .bind(wishlist_id)
+    .bind(property_id)
+    .execute(pool)
+    .await?;
+
+    sqlx::query("UPDATE wishlists SET updated_at = NOW() WHERE id = $1")
+        .bind(wishlist_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn remove_wishlist_item(pool: &PgPool, wishlist_id: Uuid, property_id: Uuid) -> Result<bool, AppError> {
+    let result = sqlx::query("DELETE FROM wishlist_items WHERE wishlist_id = $1 AND property_id = $2")
+        .bind(wishlist_id)
+        .bind(property_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Most recently added first
+pub async fn get_wishlist_items(pool: &PgPool, wishlist_id: Uuid) -> Result<Vec<WishlistItem>, AppError> {
+    let rows = sqlx::query(
+        "SELECT * FROM wishlist_items WHERE wishlist_id = $1 ORDER BY created_at DESC, property_id"
+    )
+    .bind(wishlist_id)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(WishlistItem {
+                wishlist_id: row.try_get("wishlist_id")?,
+                property_id: row.try_get("property_id")?,
+                created_at: row.try_get("created_at")?,
+            })
+        })
+        .collect()
+}
+
+// Saved search operations
+pub async fn create_saved_search(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    filters: &PropertyFilters,
+    alerts_enabled: bool,
+) -> Result<SavedSearch, AppError> {
+    let row = sqlx::query(
+        r#"
+        INSERT INTO saved_searches (user_id, name, filters, alerts_enabled, last_checked_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        RETURNING *
+        "#
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(sqlx::types::Json(filters))
+    .bind(alerts_enabled)
+    .fetch_one(pool)
+    .await?;
+
+    row_to_saved_search(row)
+}
+
+pub async fn get_saved_searches_by_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<SavedSearch>, AppError> {
+    let rows = sqlx::query("SELECT * FROM saved_searches WHERE user_id = $1 ORDER BY created_at DESC")
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+    let searches: Result<Vec<SavedSearch>, AppError> = rows.into_iter()
+        .map(row_to_saved_search)
+        .collect();
+
+    searches
+}
+
+pub async fn count_saved_searches_for_user(pool: &PgPool, user_id: Uuid) -> Result<i64, AppError> {
+    let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM saved_searches WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(count)
+}
+
+pub async fn get_saved_search_by_id(pool: &PgPool, id: Uuid) -> Result<Option<SavedSearch>, AppError> {
+    let row = sqlx::query("SELECT * FROM saved_searches WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_saved_search(row)?)),
+        None => Ok(None),
+    }
+}
+
+// Fields left as None keep their current value
+pub async fn update_saved_search(
+    pool: &PgPool,
+    id: Uuid,
+    name: Option<&str>,
+    alerts_enabled: Option<bool>,
+) -> Result<SavedSearch, AppError> {
+    let row = sqlx::query(
+        r#"
+        UPDATE saved_searches
+        SET name = COALESCE($2, name), alerts_enabled = COALESCE($3, alerts_enabled)
+        WHERE id = $1
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(name)
+    .bind(alerts_enabled)
+    .fetch_one(pool)
+    .await?;
+
+    row_to_saved_search(row)
+}
+
+pub async fn delete_saved_search(pool: &PgPool, id: Uuid) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM saved_searches WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// Saved searches due for the alert matcher, least recently checked first
+pub async fn get_saved_searches_for_alerts(pool: &PgPool, limit: i64) -> Result<Vec<SavedSearch>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT * FROM saved_searches
+        WHERE alerts_enabled = true
+        ORDER BY last_checked_at ASC NULLS FIRST, id
+        LIMIT $1
+        "#
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    let searches: Result<Vec<SavedSearch>, AppError> = rows.into_iter()
+        .map(row_to_saved_search)
+        .collect();
+
+    searches
+}
+
+// Published listings matching the filters as (id, title, effective price, published at),
+// newest first
+pub async fn get_listing_prices_matching(
+    pool: &PgPool,
+    filters: &PropertyFilters,
+    limit: i64,
+) -> Result<Vec<(Uuid, String, i32, Option<OffsetDateTime>)>, AppError> {
+    let mut query = QueryBuilder::new(
+        r#"
+        SELECT id, title, effective_nightly_price(id) AS price_per_night, reviewed_at
+        FROM properties WHERE is_active = true AND status = 'published'
+        "#
+    );
+    push_property_filters(&mut query, filters);
+    query.push(" ORDER BY created_at DESC, id DESC LIMIT ").push_bind(limit);
+
+    let rows = query.build().fetch_all(pool).await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok((
+                row.try_get("id")?,
+                row.try_get("title")?,
+                row.try_get("price_per_night")?,
+                row.try_get("reviewed_at")?,
+            ))
+        })
+        .collect()
+}
+
+// Last seen price of every listing the saved search has matched
+pub async fn get_saved_search_matches(
+    pool: &PgPool,
+    saved_search_id: Uuid,
+) -> Result<HashMap<Uuid, i32>, AppError> {
+    let rows = sqlx::query("SELECT property_id, price_per_night FROM saved_search_matches WHERE saved_search_id = $1")
+        .bind(saved_search_id)
+        .fetch_all(pool)
+        .await?;
+
+    let mut matches = HashMap::with_capacity(rows.len());
+    for row in rows {
+        matches.insert(row.try_get("property_id")?, row.try_get("price_per_night")?);
+    }
+
+    Ok(matches)
+}
+
+// Record current prices of matched listings and mark the search as checked as of
+// `checked_at`. The alerts raised by the check are stored in the same transaction,
+// so they are neither lost nor sent twice.
+pub async fn record_saved_search_matches(
+    pool: &PgPool,
+    saved_search_id: Uuid,
+    checked_at: OffsetDateTime,
+    property_ids: &[Uuid],
+    prices: &[i32],
+    notifications: &[NewNotification],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    insert_notifications(&mut tx, notifications).await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO saved_search_matches (saved_search_id, property_id, price_per_night)
+        SELECT $1, property_id, price_per_night
+        FROM UNNEST($2::UUID[], $3::INTEGER[]) AS current(property_id, price_per_night)
+        ON CONFLICT (saved_search_id, property_id)
+        DO UPDATE SET price_per_night = EXCLUDED.price_per_night
+        "#
+    )
+    .bind(saved_search_id)
+    .bind(property_ids)
+    .bind(prices)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE saved_searches SET last_checked_at = $2 WHERE id = $1")
+        .bind(saved_search_id)
+        .bind(checked_at)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// Published properties on users' wishlists with their effective price, least
+// recently checked first
+pub async fn get_watched_prices(pool: &PgPool, limit: i64) -> Result<Vec<WatchedPrice>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT watched.user_id, p.id AS property_id, p.title,
+               effective_nightly_price(p.id) AS price_per_night,
+               seen.price_per_night AS last_seen_price
+        FROM (
+            SELECT DISTINCT w.user_id, wi.property_id
+            FROM wishlist_items wi
+            JOIN wishlists w ON w.id = wi.wishlist_id
+        ) watched
+        JOIN properties p ON p.id = watched.property_id AND p.is_active = true AND p.status = 'published'
+        LEFT JOIN wishlist_price_watches seen
+            ON seen.user_id = watched.user_id AND seen.property_id = watched.property_id
+        ORDER BY seen.checked_at ASC NULLS FIRST, watched.user_id, p.id
+        LIMIT $1
+        "#
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(WatchedPrice {
+                user_id: row.try_get("user_id")?,
+                property_id: row.try_get("property_id")?,
+                title: row.try_get("title")?,
+                price: row.try_get("price_per_night")?,
+                last_seen_price: row.try_get("last_seen_price")?,
+            })
+        })
+        .collect()
+}
+
+// Record the effective prices users have now seen, together with the price drop
+// alerts raised for them, and forget properties that left every wishlist
+pub async fn record_watched_prices(
+    pool: &PgPool,
+    user_ids: &[Uuid],
+    property_ids: &[Uuid],
+    prices: &[i32],
+    notifications: &[NewNotification],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    insert_notifications(&mut tx, notifications).await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO wishlist_price_watches (user_id, property_id, price_per_night)
+        SELECT user_id, property_id, price_per_night
+        FROM UNNEST($1::UUID[], $2::UUID[], $3::INTEGER[]) AS current(user_id, property_id, price_per_night)
+        ON CONFLICT (user_id, property_id)
+        DO UPDATE SET price_per_night = EXCLUDED.price_per_night, checked_at = NOW()
+        "#
+    )
+    .bind(user_ids)
+    .bind(property_ids)
+    .bind(prices)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM wishlist_price_watches seen
+        WHERE NOT EXISTS (
+            SELECT 1 FROM wishlist_items wi
+            JOIN wishlists w ON w.id = wi.wishlist_id
+            WHERE w.user_id = seen.user_id AND wi.property_id = seen.property_id
+        )
+        "#
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// Session and refresh token operations
+pub async fn create_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    client: &ClientInfo,
+    refresh_token_hash: &str,
+    expires_at: OffsetDateTime,
+) -> Result<Session, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let row = sqlx::query(
+        r#"
+        INSERT INTO sessions (user_id, user_agent, ip_address, expires_at)
+        VALUES ($1, $2, $3, $4)
+        RETURNING *
+        "#
+    )
+    .bind(user_id)
+    .bind(client.user_agent.as_deref())
+    .bind(client.ip_address.as_deref())
+    .bind(expires_at)
+    .fetch_one(&mut *tx)
+    .await?;
+    let session = row_to_session(row)?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens (user_id, session_id, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#
+    )
+    .bind(user_id)
+    .bind(session.id)
+    .bind(refresh_token_hash)
+    .bind(expires_at)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(session)
+}
+
+pub async fn get_session_by_id(pool: &PgPool, session_id: Uuid) -> Result<Option<Session>, AppError> {
+    let row = sqlx::query("SELECT * FROM sessions WHERE id = $1")
+        .bind(session_id)
+        .fetch_optional(pool)
+        .await?;
+
+    row.map(row_to_session).transpose()
+}
+
+// Sessions that can still be refreshed, most recently used first
+pub async fn get_active_sessions_by_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<Session>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT * FROM sessions
+        WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        ORDER BY last_seen_at DESC
+        "#
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter().map(row_to_session).collect()
+}
+
+pub async fn get_refresh_token_by_hash(pool: &PgPool, token_hash: &str) -> Result<Option<RefreshToken>, AppError> {
+    let row = sqlx::query("SELECT * FROM refresh_tokens WHERE token_hash = $1")
+        .bind(token_hash)
+        .fetch_optional(pool)
+        .await?;
+
+    row.map(row_to_refresh_token).transpose()
+}
+
+// Exchange a refresh token for the next one in its session. None when the token
+// was already used or the session revoked, including by a concurrent refresh.
+pub async fn rotate_refresh_token(
+    pool: &PgPool,
+    token: &RefreshToken,
+    client: &ClientInfo,
+    next_token_hash: &str,
+    expires_at: OffsetDateTime,
+) -> Result<Option<RefreshToken>, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let used = sqlx::query(
+        r#"
+        UPDATE refresh_tokens SET used_at = NOW()
+        WHERE id = $1 AND used_at IS NULL
+            AND EXISTS (SELECT 1 FROM sessions WHERE id = $2 AND revoked_at IS NULL)
+        "#
+    )
+    .bind(token.id)
+    .bind(token.session_id)
+    .execute(&mut *tx)
+    .await?;
+    if used.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    let row = sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens (user_id, session_id, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4)
+        RETURNING *
+        "#
+    )
+    .bind(token.user_id)
+    .bind(token.session_id)
+    .bind(next_token_hash)
+    .bind(expires_at)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE sessions
+        SET expires_at = $2, last_seen_at = NOW(),
+            user_agent = COALESCE($3, user_agent), ip_address = COALESCE($4, ip_address)
+        WHERE id = $1
+        "#
+    )
+    .bind(token.session_id)
+    .bind(expires_at)
+    .bind(client.user_agent.as_deref())
+    .bind(client.ip_address.as_deref())
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    row_to_refresh_token(row).map(Some)
+}
+
+// Revoke one of the user's sessions; false when there is no such active session
+pub async fn revoke_session(pool: &PgPool, user_id: Uuid, session_id: Uuid) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        "UPDATE sessions SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Number of sessions revoked
+pub async fn revoke_user_sessions(pool: &PgPool, user_id: Uuid) -> Result<u64, AppError> {
+    let result = sqlx::query(
+        "UPDATE sessions SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()",
+    )
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Login throttling. `scope` is "account" (lowercased email) or "ip".
+pub async fn get_login_lock(pool: &PgPool, keys: &[(&str, &str)]) -> Result<Option<OffsetDateTime>, AppError> {
+    let (scopes, keys): (Vec<&str>, Vec<&str>) = keys.iter().copied().unzip();
+
+    let locked_until = sqlx::query_scalar(
+        r#"
+        SELECT MAX(locked_until) FROM login_throttles
+        WHERE (scope, key) IN (SELECT * FROM UNNEST($1::varchar[], $2::varchar[]))
+          AND locked_until > NOW()
+        "#
+    )
+    .bind(&scopes)
+    .bind(&keys)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(locked_until)
+}
+
+// Counts a failed attempt and returns the failures so far. The count starts
+// over once no attempt has failed for `window`.
+pub async fn record_login_failure(
+    pool: &PgPool,
+    scope: &str,
+    key: &str,
+    window: time::Duration,
+) -> Result<i32, AppError> {
+    let failures = sqlx::query_scalar(
+        r#"
+        INSERT INTO login_throttles (scope, key, failures)
+        VALUES ($1, $2, 1)
+        ON CONFLICT (scope, key) DO UPDATE SET
+            failures = CASE
+                WHEN login_throttles.last_failed_at < NOW() - $3 THEN 1
+                ELSE login_throttles.failures + 1
+            END,
+            last_failed_at = NOW()
+        RETURNING failures
+        "#
+    )
+    .bind(scope)
+    .bind(key)
+    .bind(window)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(failures)
+}
+
+pub async fn lock_login(pool: &PgPool, scope: &str, key: &str, until: OffsetDateTime) -> Result<(), AppError> {
+    sqlx::query("UPDATE login_throttles SET locked_until = $3 WHERE scope = $1 AND key = $2")
+        .bind(scope)
+        .bind(key)
+        .bind(until)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// Forgets failures and any lock. Returns whether there was anything to clear.
+pub async fn clear_login_failures(pool: &PgPool, scope: &str, key: &str) -> Result<bool, AppError> {
+    let result = sqlx::query("DELETE FROM login_throttles WHERE scope = $1 AND key = $2")
+        .bind(scope)
+        .bind(key)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Two-factor authentication operations
+
+// Stores a new secret for an account that has not enabled two-factor yet
+pub async fn set_totp_secret(pool: &PgPool, user_id: Uuid, secret: &str) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        "UPDATE users SET totp_secret = $2, totp_last_step = NULL WHERE id = $1 AND totp_enabled_at IS NULL",
+    )
+    .bind(user_id)
+    .bind(secret)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_totp_secret(pool: &PgPool, user_id: Uuid) -> Result<Option<String>, AppError> {
+    let secret: Option<Option<String>> = sqlx::query_scalar("SELECT totp_secret FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(secret.flatten())
+}
+
+// Records that the code for `step` was used. False when that step (or a later
+// one) was already used, so each code works only once.
+pub async fn claim_totp_step(pool: &PgPool, user_id: Uuid, step: i64) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        "UPDATE users SET totp_last_step = $2 WHERE id = $1 AND (totp_last_step IS NULL OR totp_last_step < $2)",
+    )
+    .bind(user_id)
+    .bind(step)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+async fn store_recovery_codes(
+    conn: &mut sqlx::PgConnection,
+    user_id: Uuid,
+    code_hashes: &[String],
+) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query("INSERT INTO recovery_codes (user_id, code_hash) SELECT $1, UNNEST($2::varchar[])")
+        .bind(user_id)
+        .bind(code_hashes)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn enable_two_factor(pool: &PgPool, user_id: Uuid, code_hashes: &[String]) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("UPDATE users SET totp_enabled_at = NOW() WHERE id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    store_recovery_codes(&mut tx, user_id, code_hashes).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// Replaces all of the user's recovery codes, used or not
+pub async fn replace_recovery_codes(pool: &PgPool, user_id: Uuid, code_hashes: &[String]) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+    store_recovery_codes(&mut tx, user_id, code_hashes).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn disable_two_factor(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "UPDATE users SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL WHERE id = $1",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM login_challenges WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// Marks an unused recovery code as used. False when there is no such code.
+pub async fn use_recovery_code(pool: &PgPool, user_id: Uuid, code_hash: &str) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE recovery_codes SET used_at = NOW()
+        WHERE id = (
+            SELECT id FROM recovery_codes
+            WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+            LIMIT 1
+        )
+        "#
+    )
+    .bind(user_id)
+    .bind(code_hash)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn create_login_challenge(
+    pool: &PgPool,
+    user_id: Uuid,
+    token_hash: &str,
+    expires_at: OffsetDateTime,
+) -> Result<(), AppError> {
+    sqlx::query("INSERT INTO login_challenges (user_id, token_hash, expires_at) VALUES ($1, $2, $3)")
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// An unused, unexpired challenge that has not run out of attempts
+pub async fn get_login_challenge(
+    pool: &PgPool,
+    token_hash: &str,
+    max_attempts: i32,
+) -> Result<Option<LoginChallenge>, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT * FROM login_challenges
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW() AND failed_attempts < $2
+        "#
+    )
+    .bind(token_hash)
+    .bind(max_attempts)
+    .fetch_optional(pool)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Some(LoginChallenge {
+            id: row.try_get("id")?,
+            user_id: row.try_get("user_id")?,
+            failed_attempts: row.try_get("failed_attempts")?,
+            expires_at: row.try_get("expires_at")?,
+        })),
+        None => Ok(None),
+    }
+}
+
+pub async fn record_login_challenge_failure(pool: &PgPool, challenge_id: Uuid) -> Result<(), AppError> {
+    sqlx::query("UPDATE login_challenges SET failed_attempts = failed_attempts + 1 WHERE id = $1")
+        .bind(challenge_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// Uses up the challenge. False when a concurrent request already used it.
+pub async fn complete_login_challenge(pool: &PgPool, challenge_id: Uuid) -> Result<bool, AppError> {
+    let result = sqlx::query("UPDATE login_challenges SET used_at = NOW() WHERE id = $1 AND used_at IS NULL")
+        .bind(challenge_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// External identity (OpenID Connect) operations
+pub async fn create_oidc_login_state(
+    pool: &PgPool,
+    state_hash: &str,
+    login_state: &OidcLoginState,
+    expires_at: OffsetDateTime,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO oidc_login_states (state_hash, provider, code_verifier, nonce, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#
+    )
+    .bind(state_hash)
+    .bind(&login_state.provider)
+    .bind(&login_state.code_verifier)
+    .bind(&login_state.nonce)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Removes and returns an unexpired sign-in state; each can be used once
+pub async fn take_oidc_login_state(pool: &PgPool, state_hash: &str) -> Result<Option<OidcLoginState>, AppError> {
+    // Sign-ins that were never finished
+    sqlx::query("DELETE FROM oidc_login_states WHERE expires_at < NOW()")
+        .execute(pool)
+        .await?;
+
+    let row = sqlx::query("DELETE FROM oidc_login_states WHERE state_hash = $1 RETURNING *")
+        .bind(state_hash)
+        .fetch_optional(pool)
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(OidcLoginState {
+            provider: row.try_get("provider")?,
+            code_verifier: row.try_get("code_verifier")?,
+            nonce: row.try_get("nonce")?,
+        })),
+        None => Ok(None),
+    }
+}
+
+// The user linked to a provider account, recording the sign-in
+pub async fn record_identity_login(
+    pool: &PgPool,
+    provider: &str,
+    subject: &str,
+    email: Option<&str>,
+) -> Result<Option<User>, AppError> {
+    let row = sqlx::query(
+        r#"
+        WITH identity AS (
+            UPDATE user_identities SET last_login_at = NOW(), email = COALESCE($3, email)
+            WHERE provider = $1 AND subject = $2
+            RETURNING user_id
+        )
+        SELECT users.* FROM users JOIN identity ON identity.user_id = users.id
+        "#
+    )
+    .bind(provider)
+    .bind(subject)
+    .bind(email)
+    .fetch_optional(pool)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_user(row)?)),
+        None => Ok(None),
+    }
+}
+
+// Links a provider account to a user. The provider has verified the email
+// address, so the user's address counts as verified too.
+pub async fn link_identity(
+    pool: &PgPool,
+    user_id: Uuid,
+    provider: &str,
+    subject: &str,
+    email: &str,
+    replacement_password_hash: Option<&str>,
+) -> Result<User, AppError> {
+    let mut tx = pool.begin().await?;
+
+    // Anyone could have registered the account while its email was unverified:
+    // replace the password, drop their second factor and API keys, and sign
+    // them out before the address's owner takes it over
+    if let Some(password_hash) = replacement_password_hash {
+        let claimed = sqlx::query(
+            r#"
+            UPDATE users
+            SET password_hash = $2, totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL
+            WHERE id = $1 AND email_verified_at IS NULL
+            "#
+        )
+        .bind(user_id)
+        .bind(password_hash)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if claimed {
+            sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM login_challenges WHERE user_id = $1")
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("UPDATE api_keys SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL")
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("UPDATE sessions SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL")
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    sqlx::query("INSERT INTO user_identities (user_id, provider, subject, email) VALUES ($1, $2, $3, $4)")
+        .bind(user_id)
+        .bind(provider)
+        .bind(subject)
+        .bind(email)
+        .execute(&mut *tx)
+        .await?;
+
+    let row = sqlx::query(
+        "UPDATE users SET email_verified_at = COALESCE(email_verified_at, NOW()) WHERE id = $1 RETURNING *",
+    )
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    row_to_user(row)
+}
+
+// API key operations
+fn row_to_api_key(row: sqlx::postgres::PgRow) -> Result<ApiKey, AppError> {
+    Ok(ApiKey {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        name: row.try_get("name")?,
+        prefix: row.try_get("prefix")?,
+        scopes: row.try_get("scopes")?,
+        expires_at: row.try_get("expires_at")?,
+        last_used_at: row.try_get("last_used_at")?,
+        revoked_at: row.try_get("revoked_at")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+pub async fn create_api_key(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    prefix: &str,
+    key_hash: &str,
+    scopes: &[String],
+    expires_at: Option<OffsetDateTime>,
+) -> Result<ApiKey, AppError> {
+    let row = sqlx::query(
+        r#"
+        INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING *
+        "#
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(prefix)
+    .bind(key_hash)
+    .bind(scopes)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await?;
+
+    row_to_api_key(row)
+}
+
+// Keys that have not been revoked, including expired ones so owners can see them
+pub async fn get_api_keys_by_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<ApiKey>, AppError> {
+    let rows = sqlx::query("SELECT * FROM api_keys WHERE user_id = $1 AND revoked_at IS NULL ORDER BY created_at DESC")
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+    rows.into_iter().map(row_to_api_key).collect()
+}
+
+pub async fn count_api_keys_for_user(pool: &PgPool, user_id: Uuid) -> Result<i64, AppError> {
+    let count = sqlx::query_scalar("SELECT COUNT(*) FROM api_keys WHERE user_id = $1 AND revoked_at IS NULL")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(count)
+}
+
+pub async fn revoke_api_key(pool: &PgPool, user_id: Uuid, key_id: Uuid) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        "UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(key_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// A usable (unrevoked, unexpired) key. Last use is recorded at most once a minute.
+pub async fn get_usable_api_key(pool: &PgPool, prefix: &str, key_hash: &str) -> Result<Option<ApiKey>, AppError> {
+    let row = sqlx::query(
+        r#"
+        WITH usable AS (
+            SELECT * FROM api_keys
+            WHERE prefix = $1 AND key_hash = $2 AND revoked_at IS NULL
+                AND (expires_at IS NULL OR expires_at > NOW())
+        ), used AS (
+            UPDATE api_keys SET last_used_at = NOW()
+            WHERE id IN (SELECT id FROM usable)
+                AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')
+        )
+        SELECT * FROM usable
+        "#
+    )
+    .bind(prefix)
+    .bind(key_hash)
+    .fetch_optional(pool)
+    .await?;
+
+    row.map(row_to_api_key).transpose()
+}
+
+// Email verification operations
+pub async fn create_email_verification_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    token_hash: &str,
+    expires_at: OffsetDateTime,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO email_verification_tokens (user_id, token_hash, expires_at)
+        VALUES ($1, $2, $3)
+        "#
+    )
+    .bind(user_id)
+    .bind(token_hash)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// When the latest verification email was sent, and how many were sent since `since`
+pub async fn get_email_verification_activity(
+    pool: &PgPool,
+    user_id: Uuid,
+    since: OffsetDateTime,
+) -> Result<(Option<OffsetDateTime>, i64), AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT MAX(created_at) AS last_sent_at, COUNT(*) FILTER (WHERE created_at > $2) AS recent
+        FROM email_verification_tokens
+        WHERE user_id = $1
+        "#
+    )
+    .bind(user_id)
+    .bind(since)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((row.try_get("last_sent_at")?, row.try_get("recent")?))
+}
+
+// Use a verification token. Returns the verified user, or None when the token
+// is unknown, used or expired.
+pub async fn verify_email_with_token(pool: &PgPool, token_hash: &str) -> Result<Option<User>, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let user_id: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        UPDATE email_verification_tokens SET used_at = NOW()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+        RETURNING user_id
+        "#
+    )
+    .bind(token_hash)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(user_id) = user_id else {
+        return Ok(None);
+    };
+
+    // Links from earlier emails are no longer needed
+    sqlx::query("UPDATE email_verification_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let row = sqlx::query(
+        "UPDATE users SET email_verified_at = COALESCE(email_verified_at, NOW()) WHERE id = $1 RETURNING *",
+    )
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    row_to_user(row).map(Some)
+}
+
+// Password reset operations
+pub async fn create_password_reset_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    token_hash: &str,
+    expires_at: OffsetDateTime,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO password_reset_tokens (user_id, token_hash, expires_at)
+        VALUES ($1, $2, $3)
+        "#
+    )
+    .bind(user_id)
+    .bind(token_hash)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Counts a reset request against each (scope, key, daily limit), unless one
+// of them is still cooling down or has used up its day. Returns whether the
+// request is allowed; a refused request counts against none of them.
+pub async fn record_password_reset_request(
+    pool: &PgPool,
+    throttles: &[(&str, &str, time::Duration, i64)],
+) -> Result<bool, AppError> {
+    let mut tx = pool.begin().await?;
+
+    for (scope, key, cooldown, daily_limit) in throttles {
+        // The upsert locks the row, so concurrent requests are counted one at a time
+        let allowed = sqlx::query_scalar::<_, i32>(
+            r#"
+            INSERT INTO password_reset_throttles (scope, key)
+            VALUES ($1, $2)
+            ON CONFLICT (scope, key) DO UPDATE SET
+                requests = CASE
+                    WHEN password_reset_throttles.window_started_at < NOW() - INTERVAL '1 day' THEN 1
+                    ELSE password_reset_throttles.requests + 1
+                END,
+                window_started_at = CASE
+                    WHEN password_reset_throttles.window_started_at < NOW() - INTERVAL '1 day' THEN NOW()
+                    ELSE password_reset_throttles.window_started_at
+                END,
+                last_requested_at = NOW()
+            WHERE password_reset_throttles.last_requested_at <= NOW() - $3
+              AND (password_reset_throttles.window_started_at < NOW() - INTERVAL '1 day'
+                   OR password_reset_throttles.requests < $4)
+            RETURNING requests
+            "#
+        )
+        .bind(scope)
+        .bind(key)
+        .bind(*cooldown)
+        .bind(*daily_limit as i32)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if allowed.is_none() {
+            return Ok(false);
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+// Use a reset token: set the new password, invalidate the user's other reset
+// tokens and sign out every session. Returns the user, or None when the token
+// is unknown, used or expired.
+pub async fn reset_password_with_token(
+    pool: &PgPool,
+    token_hash: &str,
+    password_hash: &str,
+) -> Result<Option<User>, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let user_id: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        UPDATE password_reset_tokens SET used_at = NOW()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+        RETURNING user_id
+        "#
+    )
+    .bind(token_hash)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(user_id) = user_id else {
+        return Ok(None);
+    };
+
+    let row = sqlx::query("UPDATE users SET password_hash = $2 WHERE id = $1 AND is_active = true RETURNING *")
+        .bind(user_id)
+        .bind(password_hash)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    sqlx::query("UPDATE password_reset_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE sessions SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    row_to_user(row).map(Some)
+}
+
+// Notification operations
+async fn insert_notifications(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    notifications: &[NewNotification],
+) -> Result<(), AppError> {
+    for notification in notifications {
+        sqlx::query(
+            r#"
+            INSERT INTO notifications (user_id, kind, title, body, data)
+            VALUES ($1, $2, $3, $4, $5)
+            "#
+        )
+        .bind(notification.user_id)
+        .bind(notification.kind.as_str())
+        .bind(&notification.title)
+        .bind(&notification.body)
+        .bind(&notification.data)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+// Newest first, with the total for the page and the user's overall unread count
+pub async fn get_notifications_by_user(
+    pool: &PgPool,
+    user_id: Uuid,
+    unread_only: bool,
+    page: i64,
+    per_page: i64,
+) -> Result<(Vec<Notification>, i64, i64), AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT * FROM notifications
+        WHERE user_id = $1 AND (NOT $2 OR read_at IS NULL)
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3 OFFSET $4
+        "#
+    )
+    .bind(user_id)
+    .bind(unread_only)
+    .bind(per_page)
+    .bind((page - 1) * per_page)
+    .fetch_all(pool)
+    .await?;
+
+    let notifications: Result<Vec<Notification>, AppError> = rows.into_iter()
+        .map(row_to_notification)
+        .collect();
+
+    let counts = sqlx::query(
+        r#"
+        SELECT COUNT(*) FILTER (WHERE NOT $2 OR read_at IS NULL) AS total,
+               COUNT(*) FILTER (WHERE read_at IS NULL) AS unread
+        FROM notifications
+        WHERE user_id = $1
+        "#
+    )
+    .bind(user_id)
+    .bind(unread_only)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((notifications?, counts.try_get("total")?, counts.try_get("unread")?))
+}
+
+// Returns None if the notification does not exist or belongs to someone else
+pub async fn mark_notification_read(
+    pool: &PgPool,
+    id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<Notification>, AppError> {
+    let row = sqlx::query(
+        r#"
+        UPDATE notifications
+        SET read_at = COALESCE(read_at, NOW())
+        WHERE id = $1 AND user_id = $2
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_notification(row)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn mark_all_notifications_read(pool: &PgPool, user_id: Uuid) -> Result<u64, AppError> {
+    let result = sqlx::query("UPDATE notifications SET read_at = NOW() WHERE user_id = $1 AND read_at IS NULL")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Room type operations
+pub async fn create_room_type(
+    pool: &PgPool,
+    property_id: Uuid,
+    request: &CreateRoomTypeRequest,
+    kind: UnitKind,
+) -> Result<RoomType, AppError> {
+    let row = sqlx::query(
+        r#"
+        INSERT INTO room_types (property_id, name, description, kind, max_guests, price_per_night, total_units)
+        VALUES ($1, $2, $3, $4::unit_kind, $5, $6, $7)
+        RETURNING *
+        "#
+    )
+    .bind(property_id)
+    .bind(request.name.trim())
+    .bind(&request.description)
+    .bind(kind.as_str())
+    .bind(request.max_guests)
+    .bind(request.price_per_night)
+    .bind(request.total_units)
+    .fetch_one(pool)
+    .await?;
+
+    row_to_room_type(row)
+}
+
+pub async fn get_room_types_by_property(
+    pool: &PgPool,
+    property_id: Uuid,
+    include_inactive: bool,
+) -> Result<Vec<RoomType>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT * FROM room_types
+        WHERE property_id = $1 AND (is_active = true OR $2)
+        ORDER BY price_per_night ASC, created_at ASC
+        "#
+    )
+    .bind(property_id)
+    .bind(include_inactive)
+    .fetch_all(pool)
+    .await?;
+
+    let room_types: Result<Vec<RoomType>, AppError> = rows.into_iter()
+        .map(row_to_room_type)
+        .collect();
+
+    room_types
+}
+
+pub async fn get_room_type_by_id(pool: &PgPool, id: Uuid) -> Result<Option<RoomType>, AppError> {
+    let row = sqlx::query("SELECT * FROM room_types WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(row_to_room_type(row)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn has_active_room_types(pool: &PgPool, property_id: Uuid) -> Result<bool, AppError> {
+    let exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM room_types WHERE property_id = $1 AND is_active = true)"
+    )
+    .bind(property_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(exists)
+}
+
+// Pending or approved bookings that take the whole property rather than units of a room type
+pub async fn has_whole_property_bookings(pool: &PgPool, property_id: Uuid) -> Result<bool, AppError> {
+    let exists = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM bookings
+            WHERE property_id = $1 AND room_type_id IS NULL AND status IN ('pending', 'approved')
+        )
+        "#
+    )
+    .bind(property_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(exists)
+}
+
+// Locks a room type against bookings and capacity changes until the
+// transaction ends, returning its current unit count
+async fn lock_room_type(tx: &mut sqlx::Transaction<'_, Postgres>, room_type_id: Uuid) -> Result<i32, AppError> {
+    let total_units = sqlx::query_scalar::<_, i32>("SELECT total_units FROM room_types WHERE id = $1 FOR UPDATE")
+        .bind(room_type_id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    Ok(total_units)
+}
+
+// Capacity can't drop below the units already booked on any night from
+// start_date until the last booked check-out (or end_date, if earlier)
+async fn check_booked_units(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    room_type_id: Uuid,
+    start_date: Date,
+    end_date: Option<Date>,
+    units: i32,
+) -> Result<(), AppError> {
+    let overbooked = sqlx::query(
+        r#"
+        SELECT night.date::DATE AS date, SUM(b.units)::INTEGER AS booked_units
+        FROM generate_series(
+            $2::DATE,
+            LEAST($3::DATE, (
+                SELECT MAX(check_out_date) FROM bookings
+                WHERE room_type_id = $1 AND status IN ('pending', 'approved')
+            )) - 1,
+            INTERVAL '1 day'
+        ) AS night(date)
+        JOIN bookings b ON b.room_type_id = $1
+            AND b.status IN ('pending', 'approved')
+            AND b.check_in_date <= night.date
+            AND b.check_out_date > night.date
+        GROUP BY night.date
+        HAVING SUM(b.units) > $4
+        ORDER BY night.date
+        LIMIT 1
+        "#
+    )
+    .bind(room_type_id)
+    .bind(start_date)
+    .bind(end_date)
+    .bind(units)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    match overbooked {
+        Some(row) => {
+            let date: Date = row.try_get("date")?;
+            let booked_units: i32 = row.try_get("booked_units")?;
+            Err(AppError::Validation(format!("{} units are already booked on {}", booked_units, date)))
+        }
+        None => Ok(()),
+    }
+}
+
+// Fields left as None keep their current value
+pub async fn update_room_type(
+    pool: &PgPool,
+    id: Uuid,
+    request: &UpdateRoomTypeRequest,
+) -> Result<RoomType, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let current_units = lock_room_type(&mut tx, id).await?;
+    // Nights that are already booked keep at least their booked units
+    if let Some(total_units) = request.total_units.filter(|&units| units < current_units) {
+        let yesterday = OffsetDateTime::now_utc().date() - time::Duration::days(1);
+        check_booked_units(&mut tx, id, yesterday, None, total_units).await?;
+    }
+
+    let row = sqlx::query(
+        r#"
+        UPDATE room_types
+        SET name = COALESCE($2, name),
+            description = COALESCE($3, description),
+            max_guests = COALESCE($4, max_guests),
+            price_per_night = COALESCE($5, price_per_night),
+            total_units = COALESCE($6, total_units),
+            is_active = COALESCE($7, is_active)
+        WHERE id = $1
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(request.name.as_deref().map(str::trim))
+    .bind(&request.description)
+    .bind(request.max_guests)
+    .bind(request.price_per_night)
+    .bind(request.total_units)
+    .bind(request.is_active)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    row_to_room_type(row)
+}
+
+// Upsert per-night overrides for start_date..=end_date; no values clears them
+pub async fn set_room_type_inventory(
+    pool: &PgPool,
+    room_type_id: Uuid,
+    request: &SetInventoryRequest,
+) -> Result<(), AppError> {
+    if request.units_available.is_none() && request.price_override.is_none() {
+        sqlx::query("DELETE FROM room_type_inventory WHERE room_type_id = $1 AND date BETWEEN $2 AND $3")
+            .bind(room_type_id)
+            .bind(request.start_date)
+            .bind(request.end_date)
+            .execute(pool)
+            .await?;
+        return Ok(());
+    }
+
+    let mut tx = pool.begin().await?;
+
+    if let Some(units_available) = request.units_available {
+        let total_units = lock_room_type(&mut tx, room_type_id).await?;
+        if units_available > total_units {
+            return Err(AppError::Validation(format!("This room type has only {} units", total_units)));
+        }
+        let end_date = request.end_date.next_day().unwrap_or(request.end_date);
+        check_booked_units(&mut tx, room_type_id, request.start_date, Some(end_date), units_available).await?;
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO room_type_inventory (room_type_id, date, units_available, price_override)
+        SELECT $1, night::DATE, $4, $5
+        FROM generate_series($2::DATE, $3::DATE, INTERVAL '1 day') AS night
+        ON CONFLICT (room_type_id, date)
+        DO UPDATE SET units_available = EXCLUDED.units_available, price_override = EXCLUDED.price_override
+        "#
+    )
+    .bind(room_type_id)
+    .bind(request.start_date)
+    .bind(request.end_date)
+    .bind(request.units_available)
+    .bind(request.price_override)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// Night-by-night capacity, bookings and price for [start_date, end_date)
+pub async fn get_room_type_inventory(
+    pool: &PgPool,
+    room_type_id: Uuid,
+    start_date: Date,
+    end_date: Date,
+) -> Result<Vec<InventoryDay>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT night.date::DATE AS date,
+               COALESCE(inv.units_available, rt.total_units) AS total_units,
+               COALESCE((
+                   SELECT SUM(b.units)
+                   FROM bookings b
+                   WHERE b.room_type_id = rt.id
+                   AND b.status IN ('pending', 'approved')
+                   AND b.check_in_date <= night.date
+                   AND b.check_out_date > night.date
+               ), 0)::INTEGER AS booked_units,
+               COALESCE(inv.price_override, rt.price_per_night) AS price_per_night
+        FROM room_types rt
+        CROSS JOIN generate_series($2::DATE, $3::DATE - 1, INTERVAL '1 day') AS night(date)
+        LEFT JOIN room_type_inventory inv ON inv.room_type_id = rt.id AND inv.date = night.date::DATE
+        WHERE rt.id = $1
+        ORDER BY night.date
+        "#
+    )
+    .bind(room_type_id)
+    .bind(start_date)
+    .bind(end_date)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            let total_units: i32 = row.try_get("total_units")?;
+            let booked_units: i32 = row.try_get("booked_units")?;
+            Ok(InventoryDay {
+                date: row.try_get("date")?,
+                total_units,
+                booked_units,
+                available_units: (total_units - booked_units).max(0),
+                price_per_night: row.try_get("price_per_night")?,
+            })
+        })
+        .collect()
+}
+
+// Free units and one-unit stay price of every active room type of a property
+pub async fn get_room_type_availability(
+    pool: &PgPool,
+    property_id: Uuid,
+    check_in_date: Date,
+    check_out_date: Date,
+) -> Result<HashMap<Uuid, RoomTypeAvailability>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT rt.id,
+               GREATEST(room_type_available_units(rt.id, $2, $3), 0) AS available_units,
+               (
+                   SELECT SUM(COALESCE(inv.price_override, rt.price_per_night))
+                   FROM generate_series($2::DATE, $3::DATE - 1, INTERVAL '1 day') AS night(date)
+                   LEFT JOIN room_type_inventory inv ON inv.room_type_id = rt.id AND inv.date = night.date::DATE
+               )::BIGINT AS price_per_unit
+        FROM room_types rt
+        WHERE rt.property_id = $1 AND rt.is_active = true
+        "#
+    )
+    .bind(property_id)
+    .bind(check_in_date)
+    .bind(check_out_date)
+    .fetch_all(pool)
+    .await?;
+
+    let nights = (check_out_date - check_in_date).whole_days();
+    let mut availability = HashMap::with_capacity(rows.len());
+    for row in rows {
+        availability.insert(
+            row.try_get("id")?,
+            RoomTypeAvailability {
+                available_units: row.try_get("available_units")?,
+                nights,
+                price_per_unit: row.try_get("price_per_unit")?,
+            },
+        );
+    }
+
+    Ok(availability)
+}
+
+// Reserve units of a room type. The room type row is locked so concurrent
+// bookings cannot oversell the last units.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_room_booking(
+    pool: &PgPool,
+    room_type: &RoomType,
+    user_id: Uuid,
+    check_in_date: Date,
+    check_out_date: Date,
+    units: i32,
+    guest_count: i32,
+    special_requests: Option<&str>,
+    accepted_house_rules: Option<&HouseRules>,
+) -> Result<Booking, AppError> {
+    let mut tx = pool.begin().await?;
+
+    lock_room_type(&mut tx, room_type.id).await?;
+
+    let row = sqlx::query(
+        r#"
+        SELECT room_type_available_units($1, $2, $3) AS available_units,
+               (
+                   SELECT SUM(COALESCE(inv.price_override, rt.price_per_night))
+                   FROM room_types rt
+                   CROSS JOIN generate_series($2::DATE, $3::DATE - 1, INTERVAL '1 day') AS night(date)
+                   LEFT JOIN room_type_inventory inv ON inv.room_type_id = rt.id AND inv.date = night.date::DATE
+                   WHERE rt.id = $1
+               )::BIGINT AS price_per_unit
+        "#
+    )
+    .bind(room_type.id)
+    .bind(check_in_date)
+    .bind(check_out_date)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let available_units: i32 = row.try_get("available_units")?;
+    if available_units < units {
+        return Err(AppError::BadRequest(format!(
+            "Only {} units of this room type are available for the selected dates",
+            available_units.max(0)
+        )));
+    }
+
+    let price_per_unit: i64 = row.try_get("price_per_unit")?;
+    let total_price = i32::try_from(price_per_unit * i64::from(units))
+        .map_err(|_| AppError::BadRequest("Booking total is too large".to_string()))?;
+
+    let row = sqlx::query(
+        r#"
+        INSERT INTO bookings (
+            property_id, user_id, check_in_date, check_out_date, total_price, guest_count,
+            special_requests, room_type_id, units, accepted_house_rules, house_rules_accepted_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, CASE WHEN $10::JSONB IS NULL THEN NULL ELSE NOW() END)
+        RETURNING *
+        "#
+    )
+    .bind(room_type.property_id)
+    .bind(user_id)
+    .bind(check_in_date)
+    .bind(check_out_date)
+    .bind(total_price)
+    .bind(guest_count)
+    .bind(special_requests)
+    .bind(room_type.id)
+    .bind(units)
+    .bind(accepted_house_rules.map(sqlx::types::Json))
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    row_to_booking(row)
+}
@@ -0,0 +1,141 @@
+use crate::{config::Config, error::AppError};
+use async_trait::async_trait;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+// Delivery attempts per email before it is dropped
+const MAX_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Clone)]
+pub struct OutgoingEmail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// Delivery backend for outbound email
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &OutgoingEmail) -> Result<(), AppError>;
+}
+
+// Sends through an SMTP relay, upgrading the connection with STARTTLS
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(host: &str, port: u16, credentials: Option<(String, String)>, from: &str) -> Result<Self, AppError> {
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+            .map_err(|e| AppError::Internal(format!("Invalid SMTP relay {}: {}", host, e)))?
+            .port(port);
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        let from = from
+            .parse()
+            .map_err(|e| AppError::Internal(format!("Invalid sender address {}: {}", from, e)))?;
+
+        Ok(SmtpMailer { transport: builder.build(), from })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &OutgoingEmail) -> Result<(), AppError> {
+        let to = email
+            .to
+            .parse()
+            .map_err(|e| AppError::BadRequest(format!("Invalid recipient address: {}", e)))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(email.subject.as_str())
+            .body(email.body.clone())
+            .map_err(|e| AppError::Internal(format!("Failed to build email: {}", e)))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| AppError::Internal(format!("SMTP delivery failed: {}", e)))?;
+
+        Ok(())
+    }
+}
+
+// Writes emails to the log instead of sending them, for local development.
+// Logged bodies contain one-time links, so never use this in production.
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: &OutgoingEmail) -> Result<(), AppError> {
+        tracing::info!("Email to {}: {}\n{}", email.to, email.subject, email.body);
+        Ok(())
+    }
+}
+
+// SMTP when a relay is configured, otherwise the log
+pub fn mailer_from_config(config: &Config) -> Result<Arc<dyn Mailer>, AppError> {
+    match &config.smtp_host {
+        Some(host) => {
+            let credentials = config.smtp_username.clone().zip(config.smtp_password.clone());
+            Ok(Arc::new(SmtpMailer::new(host, config.smtp_port, credentials, &config.email_from)?))
+        }
+        None => {
+            tracing::warn!("SMTP_HOST is not set; outbound email is only logged");
+            Ok(Arc::new(LogMailer))
+        }
+    }
+}
+
+// Queue of emails delivered in the background, so requests never wait on (or
+// reveal anything through the timing of) delivery. Emails still queued when the
+// process stops are lost; users can ask for a new link.
+#[derive(Clone)]
+pub struct EmailOutbox {
+    queue: mpsc::UnboundedSender<OutgoingEmail>,
+}
+
+impl EmailOutbox {
+    // Starts the delivery worker; must be called within a Tokio runtime
+    pub fn start(mailer: Arc<dyn Mailer>) -> Self {
+        let (queue, mut pending) = mpsc::unbounded_channel::<OutgoingEmail>();
+
+        tokio::spawn(async move {
+            while let Some(email) = pending.recv().await {
+                // One failing recipient must not hold up the rest of the queue
+                let mailer = mailer.clone();
+                tokio::spawn(async move { deliver(mailer.as_ref(), &email).await });
+            }
+        });
+
+        EmailOutbox { queue }
+    }
+
+    pub fn queue(&self, email: OutgoingEmail) {
+        if self.queue.send(email).is_err() {
+            tracing::error!("Email delivery worker has stopped; dropping email");
+        }
+    }
+}
+
+async fn deliver(mailer: &dyn Mailer, email: &OutgoingEmail) {
+    for attempt in 1..=MAX_ATTEMPTS {
+        match mailer.send(email).await {
+            Ok(()) => return,
+            Err(e) if attempt < MAX_ATTEMPTS => {
+                tracing::warn!("Email to {} failed (attempt {}): {}", email.to, attempt, e);
+                tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await;
+            }
+            Err(e) => tracing::error!("Giving up on email to {}: {}", email.to, e),
+        }
+    }
+}
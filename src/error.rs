@@ -21,6 +21,9 @@ pub enum AppError {
     
     #[error("Bad request: {0}")]
     BadRequest(String),
+
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
     
     #[error("Internal server error: {0}")]
     Internal(String),
@@ -71,6 +74,11 @@ impl ResponseError for AppError {
                 "Bad Request",
                 msg.as_str(),
             ),
+            AppError::TooManyRequests(msg) => (
+                actix_web::http::StatusCode::TOO_MANY_REQUESTS,
+                "Too Many Requests",
+                msg.as_str(),
+            ),
             AppError::Internal(msg) => (
                 actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
                 "Internal Server Error",
@@ -1,10 +1,23 @@
 use crate::{
-    auth::{extract_auth_user, hash_password, verify_password, create_jwt},
+    alerts::{seed_saved_search, MAX_SAVED_SEARCHES_PER_USER},
+    auth::{
+        dummy_verify_password, generate_api_key, generate_recovery_codes, generate_token, generate_totp_secret,
+        hash_password, hash_recovery_code, hash_token, totp, totp_step, verify_password, AuthUser, ClientInfo, JwtKeys,
+        RequireAdmin, RequireGuest, RequireOwner,
+    },
+    cache::SuggestionCache,
+    config::Config,
     db::*,
+    email::{EmailOutbox, OutgoingEmail},
+    oidc::{IdTokenClaims, OidcClient},
     error::AppResult,
+    images::{photo_key, process_photo, MAX_PHOTOS_PER_PROPERTY, THUMBNAIL_CONTENT_TYPE},
     models::*,
+    storage::BlobStore,
 };
-use actix_web::{web, HttpRequest, HttpResponse};
+use actix_multipart::Multipart;
+use actix_web::{http::header, web, HttpResponse};
+use futures_util::TryStreamExt;
 use time::{Date, OffsetDateTime};
 use uuid::Uuid;
 use validator::Validate;
@@ -12,6 +25,8 @@ use validator::Validate;
 // Auth handlers
 pub async fn register(
     pool: web::Data<sqlx::PgPool>,
+    config: web::Data<Config>,
+    outbox: web::Data<EmailOutbox>,
     user_data: web::Json<CreateUserRequest>,
 ) -> AppResult<HttpResponse> {
     user_data.validate()?;
@@ -38,479 +53,2718 @@ pub async fn register(
     )
     .await?;
 
-    let response = UserResponse {
+    send_verification_email(&pool, &config, &outbox, &user).await?;
+
+    Ok(HttpResponse::Created().json(to_user_response(&user)))
+}
+
+pub async fn login(
+    pool: web::Data<sqlx::PgPool>,
+    keys: web::Data<JwtKeys>,
+    config: web::Data<Config>,
+    client: ClientInfo,
+    login_data: web::Json<LoginRequest>,
+) -> AppResult<HttpResponse> {
+    login_data.validate()?;
+
+    let account_key = login_data.email.to_lowercase();
+    let throttle_keys = client_throttle_keys(&account_key, &client);
+    check_login_lock(&pool, &throttle_keys).await?;
+
+    // Unknown emails still pay for a password check, so response times do not
+    // tell them apart from wrong passwords
+    let user = get_user_by_email(&pool, &login_data.email).await?;
+    let is_valid = match &user {
+        Some(user) => verify_password(&login_data.password, &user.password_hash).await?,
+        None => {
+            dummy_verify_password(&login_data.password).await?;
+            false
+        }
+    };
+    let user = match user {
+        Some(user) if is_valid => user,
+        _ => {
+            record_failed_login(&pool, &config, &throttle_keys).await?;
+            return Err(crate::error::AppError::Authentication(
+                "Invalid email or password".to_string(),
+            ));
+        }
+    };
+
+    // Check if user is active
+    if !user.is_active {
+        return Err(crate::error::AppError::Authentication(
+            "Account is deactivated".to_string(),
+        ));
+    }
+
+    // With two-factor enabled the password only earns a challenge; failures are
+    // cleared once the code is verified too
+    if user.totp_enabled_at.is_some() {
+        return two_factor_challenge(&pool, &config, &user).await;
+    }
+
+    // A correct password clears the account's failures; the IP's stay, so one
+    // working account does not reset an attacker's count
+    clear_login_failures(&pool, "account", &account_key).await?;
+
+    let response = start_session(&pool, &keys, &config, &client, &user).await?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+async fn two_factor_challenge(pool: &sqlx::PgPool, config: &Config, user: &User) -> AppResult<HttpResponse> {
+    let challenge_token = generate_token()?;
+    let lifetime = time::Duration::minutes(config.login_challenge_minutes);
+    create_login_challenge(pool, user.id, &hash_token(&challenge_token), OffsetDateTime::now_utc() + lifetime).await?;
+
+    Ok(HttpResponse::Ok().json(TwoFactorChallengeResponse {
+        two_factor_required: true,
+        challenge_token,
+        expires_in: lifetime.whole_seconds(),
+    }))
+}
+
+// Each login is its own session
+async fn start_session(
+    pool: &sqlx::PgPool,
+    keys: &JwtKeys,
+    config: &Config,
+    client: &ClientInfo,
+    user: &User,
+) -> AppResult<LoginResponse> {
+    let refresh_token = generate_token()?;
+    let session = create_session(pool, user.id, client, &hash_token(&refresh_token), refresh_expiry(config)).await?;
+    let token = keys.create_jwt(user.id, user.role.clone(), Some(session.id))?;
+
+    Ok(LoginResponse {
+        token,
+        refresh_token,
+        expires_in: keys.expires_in(),
+        user: to_user_response(user),
+    })
+}
+
+// Keys that throttle requests by account (lowercased email) and by client IP
+fn client_throttle_keys<'a>(account_key: &'a str, client: &'a ClientInfo) -> Vec<(&'static str, &'a str)> {
+    let mut throttle_keys = vec![("account", account_key)];
+    if let Some(ip) = &client.ip_address {
+        throttle_keys.push(("ip", ip.as_str()));
+    }
+    throttle_keys
+}
+
+async fn check_login_lock(pool: &sqlx::PgPool, throttle_keys: &[(&str, &str)]) -> AppResult<()> {
+    if let Some(locked_until) = get_login_lock(pool, throttle_keys).await? {
+        let wait = (locked_until - OffsetDateTime::now_utc()).whole_seconds().max(1);
+        return Err(crate::error::AppError::TooManyRequests(format!(
+            "Too many failed login attempts; try again in {} seconds",
+            wait
+        )));
+    }
+
+    Ok(())
+}
+
+async fn record_failed_login(
+    pool: &sqlx::PgPool,
+    config: &Config,
+    throttle_keys: &[(&str, &str)],
+) -> AppResult<()> {
+    let window = time::Duration::minutes(config.login_failure_window_minutes);
+
+    for &(scope, key) in throttle_keys {
+        let failures = record_login_failure(pool, scope, key, window).await?;
+        let allowed = if scope == "ip" { config.login_ip_max_failures } else { config.login_max_failures };
+        if failures >= allowed {
+            let doublings = (failures - allowed).min(30) as u32;
+            let lockout = config
+                .login_lockout_base_secs
+                .saturating_mul(1 << doublings)
+                .min(config.login_lockout_max_secs);
+            lock_login(pool, scope, key, OffsetDateTime::now_utc() + time::Duration::seconds(lockout)).await?;
+            tracing::warn!("Login locked for {} {} after {} failures", scope, key, failures);
+        }
+    }
+
+    Ok(())
+}
+
+fn refresh_expiry(config: &Config) -> OffsetDateTime {
+    OffsetDateTime::now_utc() + time::Duration::days(config.refresh_token_days as i64)
+}
+
+pub async fn refresh(
+    pool: web::Data<sqlx::PgPool>,
+    keys: web::Data<JwtKeys>,
+    config: web::Data<Config>,
+    client: ClientInfo,
+    refresh_data: web::Json<RefreshTokenRequest>,
+) -> AppResult<HttpResponse> {
+    refresh_data.validate()?;
+
+    let invalid = || crate::error::AppError::Authentication("Invalid refresh token".to_string());
+
+    let token = get_refresh_token_by_hash(&pool, &hash_token(&refresh_data.refresh_token))
+        .await?
+        .ok_or_else(invalid)?;
+
+    let session = get_session_by_id(&pool, token.session_id)
+        .await?
+        .filter(|session| session.revoked_at.is_none())
+        .ok_or_else(invalid)?;
+
+    if token.expires_at <= OffsetDateTime::now_utc() {
+        return Err(invalid());
+    }
+
+    // Only the latest token of a session is ever valid. Presenting an older one
+    // means someone else holds a copy, so end the whole session.
+    if token.used_at.is_some() {
+        tracing::warn!("Refresh token reused for user {}, revoking session {}", token.user_id, session.id);
+        revoke_session(&pool, token.user_id, session.id).await?;
+        return Err(invalid());
+    }
+
+    let user = get_user_by_id(&pool, token.user_id)
+        .await?
+        .filter(|user| user.is_active)
+        .ok_or_else(invalid)?;
+
+    let next_token = generate_token()?;
+    let rotated = rotate_refresh_token(&pool, &token, &client, &hash_token(&next_token), refresh_expiry(&config)).await?;
+    if rotated.is_none() {
+        // Lost a race with another refresh of the same token
+        revoke_session(&pool, token.user_id, session.id).await?;
+        return Err(invalid());
+    }
+
+    Ok(HttpResponse::Ok().json(TokenResponse {
+        token: keys.create_jwt(user.id, user.role, Some(session.id))?,
+        refresh_token: next_token,
+        expires_in: keys.expires_in(),
+    }))
+}
+
+// Ends the session the refresh token belongs to. Works with an expired access token.
+pub async fn logout(
+    pool: web::Data<sqlx::PgPool>,
+    refresh_data: web::Json<RefreshTokenRequest>,
+) -> AppResult<HttpResponse> {
+    refresh_data.validate()?;
+
+    if let Some(token) = get_refresh_token_by_hash(&pool, &hash_token(&refresh_data.refresh_token)).await? {
+        revoke_session(&pool, token.user_id, token.session_id).await?;
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Log out of all devices
+pub async fn logout_all(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+) -> AppResult<HttpResponse> {
+    let revoked = revoke_user_sessions(&pool, auth_user.id).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "revoked_sessions": revoked })))
+}
+
+async fn send_verification_email(
+    pool: &sqlx::PgPool,
+    config: &Config,
+    outbox: &EmailOutbox,
+    user: &User,
+) -> AppResult<()> {
+    let token = generate_token()?;
+    let expires_at = OffsetDateTime::now_utc() + time::Duration::hours(config.email_verification_token_hours);
+    create_email_verification_token(pool, user.id, &hash_token(&token), expires_at).await?;
+
+    outbox.queue(OutgoingEmail {
+        to: user.email.clone(),
+        subject: "Confirm your email address".to_string(),
+        body: format!(
+            "Hi {},\n\nPlease confirm your email address by opening this link within {} hours:\n\n{}/verify-email?token={}\n",
+            user.first_name,
+            config.email_verification_token_hours,
+            config.app_base_url.trim_end_matches('/'),
+            token,
+        ),
+    });
+
+    Ok(())
+}
+
+pub async fn verify_email(
+    pool: web::Data<sqlx::PgPool>,
+    verify_data: web::Json<VerifyEmailRequest>,
+) -> AppResult<HttpResponse> {
+    verify_data.validate()?;
+
+    let user = verify_email_with_token(&pool, &hash_token(&verify_data.token))
+        .await?
+        .ok_or_else(|| crate::error::AppError::BadRequest("Invalid or expired verification token".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(to_user_response(&user)))
+}
+
+pub async fn resend_verification_email(
+    pool: web::Data<sqlx::PgPool>,
+    config: web::Data<Config>,
+    outbox: web::Data<EmailOutbox>,
+    auth_user: AuthUser,
+) -> AppResult<HttpResponse> {
+    let user = get_user_by_id(&pool, auth_user.id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("User not found".to_string()))?;
+
+    if user.email_verified_at.is_some() {
+        return Err(crate::error::AppError::BadRequest("Email address is already verified".to_string()));
+    }
+
+    let now = OffsetDateTime::now_utc();
+    let (last_sent_at, sent_today) =
+        get_email_verification_activity(&pool, user.id, now - time::Duration::days(1)).await?;
+    let cooldown = time::Duration::seconds(config.email_verification_resend_secs);
+    if last_sent_at.is_some_and(|sent_at| now - sent_at < cooldown) {
+        return Err(crate::error::AppError::TooManyRequests(
+            "A verification email was just sent; please wait before asking for another".to_string(),
+        ));
+    }
+    if sent_today >= config.email_verification_daily_limit {
+        return Err(crate::error::AppError::TooManyRequests(
+            "Too many verification emails today; please try again tomorrow".to_string(),
+        ));
+    }
+
+    send_verification_email(&pool, &config, &outbox, &user).await?;
+
+    Ok(HttpResponse::Accepted().json(serde_json::json!({ "message": "Verification email sent" })))
+}
+
+// Always answers the same way, so the response does not tell whether an
+// account exists for the address
+pub async fn forgot_password(
+    pool: web::Data<sqlx::PgPool>,
+    config: web::Data<Config>,
+    outbox: web::Data<EmailOutbox>,
+    client: ClientInfo,
+    request_data: web::Json<ForgotPasswordRequest>,
+) -> AppResult<HttpResponse> {
+    request_data.validate()?;
+
+    let accepted = || {
+        HttpResponse::Accepted().json(serde_json::json!({
+            "message": "If an account exists for this email, a reset link has been sent"
+        }))
+    };
+
+    // Limits apply whether or not the account exists, and a limited request
+    // gets the same answer, just without an email
+    let account_key = request_data.email.to_lowercase();
+    // No cooldown per IP, so people sharing an address are only held to the daily cap
+    let throttles: Vec<_> = client_throttle_keys(&account_key, &client)
+        .into_iter()
+        .map(|(scope, key)| match scope {
+            "ip" => (scope, key, time::Duration::ZERO, config.password_reset_ip_daily_limit),
+            _ => (
+                scope,
+                key,
+                time::Duration::seconds(config.password_reset_resend_secs),
+                config.password_reset_daily_limit,
+            ),
+        })
+        .collect();
+    if !record_password_reset_request(&pool, &throttles).await? {
+        return Ok(accepted());
+    }
+
+    let user = get_user_by_email(&pool, &request_data.email).await?;
+    if let Some(user) = user.filter(|user| user.is_active) {
+        let token = generate_token()?;
+        let expires_at = OffsetDateTime::now_utc() + time::Duration::minutes(config.password_reset_token_minutes);
+        create_password_reset_token(&pool, user.id, &hash_token(&token), expires_at).await?;
+
+        outbox.queue(OutgoingEmail {
+            to: user.email,
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Hi {},\n\nUse this link to choose a new password. It expires in {} minutes and works once:\n\n{}/reset-password?token={}\n\nIf you did not ask for this, you can ignore this email.\n",
+                user.first_name,
+                config.password_reset_token_minutes,
+                config.app_base_url.trim_end_matches('/'),
+                token,
+            ),
+        });
+    }
+
+    Ok(accepted())
+}
+
+pub async fn reset_password(
+    pool: web::Data<sqlx::PgPool>,
+    outbox: web::Data<EmailOutbox>,
+    reset_data: web::Json<ResetPasswordRequest>,
+) -> AppResult<HttpResponse> {
+    reset_data.validate()?;
+
+    let password_hash = hash_password(&reset_data.new_password).await?;
+    let user = reset_password_with_token(&pool, &hash_token(&reset_data.token), &password_hash)
+        .await?
+        .ok_or_else(|| crate::error::AppError::BadRequest("Invalid or expired reset token".to_string()))?;
+    clear_login_failures(&pool, "account", &user.email.to_lowercase()).await?;
+
+    outbox.queue(OutgoingEmail {
+        to: user.email,
+        subject: "Your password was changed".to_string(),
+        body: format!(
+            "Hi {},\n\nThe password for your account was just changed and all devices were signed out.\nIf this was not you, reset your password again and contact support.\n",
+            user.first_name,
+        ),
+    });
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Two-factor authentication handlers
+
+// Wrong codes allowed per login challenge before the password must be entered again
+const MAX_LOGIN_CHALLENGE_ATTEMPTS: i32 = 5;
+
+// Checks an authenticator code (each one works once) or, when allowed, uses up
+// a recovery code
+async fn check_two_factor_code(
+    pool: &sqlx::PgPool,
+    config: &Config,
+    user_id: Uuid,
+    email: &str,
+    code: &str,
+    allow_recovery_code: bool,
+) -> AppResult<bool> {
+    let secret = get_totp_secret(pool, user_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::BadRequest("Two-factor authentication is not set up".to_string()))?;
+
+    if let Some(step) = totp_step(&totp(&secret, &config.totp_issuer, email)?, code) {
+        return claim_totp_step(pool, user_id, step).await;
+    }
+
+    if allow_recovery_code {
+        return use_recovery_code(pool, user_id, &hash_recovery_code(code)).await;
+    }
+
+    Ok(false)
+}
+
+fn invalid_two_factor_code() -> crate::error::AppError {
+    crate::error::AppError::BadRequest("Invalid two-factor code".to_string())
+}
+
+pub async fn setup_two_factor(
+    pool: web::Data<sqlx::PgPool>,
+    config: web::Data<Config>,
+    auth_user: AuthUser,
+) -> AppResult<HttpResponse> {
+    let secret = generate_totp_secret()?;
+    if !set_totp_secret(&pool, auth_user.id, &secret).await? {
+        return Err(crate::error::AppError::BadRequest(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+
+    let otpauth_url = totp(&secret, &config.totp_issuer, &auth_user.email)?.get_url();
+
+    Ok(HttpResponse::Ok().json(TwoFactorSetupResponse { secret, otpauth_url }))
+}
+
+// Enables two-factor once the user proves their authenticator has the secret
+pub async fn confirm_two_factor(
+    pool: web::Data<sqlx::PgPool>,
+    config: web::Data<Config>,
+    auth_user: AuthUser,
+    code_data: web::Json<TwoFactorCodeRequest>,
+) -> AppResult<HttpResponse> {
+    code_data.validate()?;
+
+    if auth_user.two_factor_enabled {
+        return Err(crate::error::AppError::BadRequest(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+    if !check_two_factor_code(&pool, &config, auth_user.id, &auth_user.email, &code_data.code, false).await? {
+        return Err(invalid_two_factor_code());
+    }
+
+    let recovery_codes = generate_recovery_codes()?;
+    let code_hashes: Vec<String> = recovery_codes.iter().map(|code| hash_recovery_code(code)).collect();
+    enable_two_factor(&pool, auth_user.id, &code_hashes).await?;
+
+    Ok(HttpResponse::Ok().json(RecoveryCodesResponse { recovery_codes }))
+}
+
+pub async fn disable_two_factor_handler(
+    pool: web::Data<sqlx::PgPool>,
+    config: web::Data<Config>,
+    auth_user: AuthUser,
+    code_data: web::Json<TwoFactorCodeRequest>,
+) -> AppResult<HttpResponse> {
+    code_data.validate()?;
+
+    if auth_user.role == UserRole::Admin {
+        return Err(crate::error::AppError::Authorization(
+            "Two-factor authentication is mandatory for admins".to_string(),
+        ));
+    }
+    if !auth_user.two_factor_enabled {
+        return Err(crate::error::AppError::BadRequest(
+            "Two-factor authentication is not enabled".to_string(),
+        ));
+    }
+    if !check_two_factor_code(&pool, &config, auth_user.id, &auth_user.email, &code_data.code, true).await? {
+        return Err(invalid_two_factor_code());
+    }
+
+    disable_two_factor(&pool, auth_user.id).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Replaces the recovery codes; needs a code from the authenticator itself
+pub async fn regenerate_recovery_codes(
+    pool: web::Data<sqlx::PgPool>,
+    config: web::Data<Config>,
+    auth_user: AuthUser,
+    code_data: web::Json<TwoFactorCodeRequest>,
+) -> AppResult<HttpResponse> {
+    code_data.validate()?;
+
+    if !auth_user.two_factor_enabled {
+        return Err(crate::error::AppError::BadRequest(
+            "Two-factor authentication is not enabled".to_string(),
+        ));
+    }
+    if !check_two_factor_code(&pool, &config, auth_user.id, &auth_user.email, &code_data.code, false).await? {
+        return Err(invalid_two_factor_code());
+    }
+
+    let recovery_codes = generate_recovery_codes()?;
+    let code_hashes: Vec<String> = recovery_codes.iter().map(|code| hash_recovery_code(code)).collect();
+    replace_recovery_codes(&pool, auth_user.id, &code_hashes).await?;
+
+    Ok(HttpResponse::Ok().json(RecoveryCodesResponse { recovery_codes }))
+}
+
+// Second login step: trades a challenge and a valid code for tokens
+pub async fn verify_two_factor_login(
+    pool: web::Data<sqlx::PgPool>,
+    keys: web::Data<JwtKeys>,
+    config: web::Data<Config>,
+    client: ClientInfo,
+    verify_data: web::Json<VerifyTwoFactorRequest>,
+) -> AppResult<HttpResponse> {
+    verify_data.validate()?;
+
+    let invalid = || crate::error::AppError::Authentication("Invalid or expired login challenge".to_string());
+
+    let challenge = get_login_challenge(&pool, &hash_token(&verify_data.challenge_token), MAX_LOGIN_CHALLENGE_ATTEMPTS)
+        .await?
+        .ok_or_else(invalid)?;
+    let user = get_user_by_id(&pool, challenge.user_id)
+        .await?
+        .filter(|user| user.is_active)
+        .ok_or_else(invalid)?;
+
+    // Wrong codes count towards the same lockout as wrong passwords
+    let account_key = user.email.to_lowercase();
+    let throttle_keys = client_throttle_keys(&account_key, &client);
+    check_login_lock(&pool, &throttle_keys).await?;
+
+    if !check_two_factor_code(&pool, &config, user.id, &user.email, &verify_data.code, true).await? {
+        record_login_challenge_failure(&pool, challenge.id).await?;
+        record_failed_login(&pool, &config, &throttle_keys).await?;
+        return Err(crate::error::AppError::Authentication("Invalid two-factor code".to_string()));
+    }
+
+    if !complete_login_challenge(&pool, challenge.id).await? {
+        return Err(invalid());
+    }
+    clear_login_failures(&pool, "account", &account_key).await?;
+
+    let response = start_session(&pool, &keys, &config, &client, &user).await?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// External sign-in (OpenID Connect) handlers
+
+// Time allowed to sign in at the provider
+const OIDC_LOGIN_MINUTES: i64 = 10;
+
+pub async fn oidc_authorize(
+    pool: web::Data<sqlx::PgPool>,
+    oidc: web::Data<OidcClient>,
+    path: web::Path<String>,
+) -> AppResult<HttpResponse> {
+    let provider = path.into_inner();
+
+    let state = generate_token()?;
+    let login_state = OidcLoginState { provider, code_verifier: generate_token()?, nonce: generate_token()? };
+    let authorization_url = oidc
+        .authorization_url(&login_state.provider, &state, &login_state.nonce, &login_state.code_verifier)
+        .await?;
+    let expires_at = OffsetDateTime::now_utc() + time::Duration::minutes(OIDC_LOGIN_MINUTES);
+    create_oidc_login_state(&pool, &hash_token(&state), &login_state, expires_at).await?;
+
+    Ok(HttpResponse::Ok().json(OidcAuthorizationResponse { authorization_url }))
+}
+
+// Finishes a sign-in with the code and state the provider redirected back with.
+// Answers like `login`: tokens, or a challenge when two-factor is enabled.
+pub async fn oidc_callback(
+    pool: web::Data<sqlx::PgPool>,
+    keys: web::Data<JwtKeys>,
+    config: web::Data<Config>,
+    oidc: web::Data<OidcClient>,
+    client: ClientInfo,
+    path: web::Path<String>,
+    callback_data: web::Json<OidcCallbackRequest>,
+) -> AppResult<HttpResponse> {
+    callback_data.validate()?;
+    let provider = path.into_inner();
+
+    let login_state = take_oidc_login_state(&pool, &hash_token(&callback_data.state))
+        .await?
+        .filter(|login_state| login_state.provider == provider)
+        .ok_or_else(|| crate::error::AppError::BadRequest("Invalid or expired sign-in state".to_string()))?;
+
+    let claims = oidc
+        .exchange_code(&provider, &callback_data.code, &login_state.code_verifier, &login_state.nonce)
+        .await?;
+    let verified_email = claims.email.as_deref().filter(|_| claims.email_verified);
+
+    let user = match record_identity_login(&pool, &provider, &claims.sub, verified_email).await? {
+        Some(user) => user,
+        None => {
+            // New identities are matched to accounts by email, which only the
+            // provider's verification makes trustworthy
+            let email = verified_email.ok_or_else(|| {
+                crate::error::AppError::Authentication(format!(
+                    "{} did not confirm that the email address is verified",
+                    provider
+                ))
+            })?;
+            let (user, replacement_password_hash) = match get_user_by_email(&pool, email).await? {
+                Some(user) if user.email_verified_at.is_some() => (user, None),
+                // Whoever registered an unverified account may not own the address,
+                // so it is taken over with fresh credentials
+                Some(user) => (user, Some(hash_password(&generate_token()?).await?)),
+                None => {
+                    let (first_name, last_name) = identity_names(&claims, email);
+                    // Users signing up this way can set a password with "forgot password"
+                    let password_hash = hash_password(&generate_token()?).await?;
+                    let user = create_user(&pool, email, &password_hash, &first_name, &last_name, UserRole::User).await?;
+                    (user, None)
+                }
+            };
+            link_identity(&pool, user.id, &provider, &claims.sub, email, replacement_password_hash.as_deref()).await?
+        }
+    };
+
+    if !user.is_active {
+        return Err(crate::error::AppError::Authentication(
+            "Account is deactivated".to_string(),
+        ));
+    }
+
+    if user.totp_enabled_at.is_some() {
+        return two_factor_challenge(&pool, &config, &user).await;
+    }
+
+    let response = start_session(&pool, &keys, &config, &client, &user).await?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// Names for a new account, from the ID token or else the email address
+fn identity_names(claims: &IdTokenClaims, email: &str) -> (String, String) {
+    let non_empty = |name: &Option<String>| name.as_deref().map(str::trim).filter(|name| !name.is_empty()).map(String::from);
+
+    match (non_empty(&claims.given_name), non_empty(&claims.family_name)) {
+        (Some(first), last) => (first, last.unwrap_or_default()),
+        (None, _) => match non_empty(&claims.name) {
+            Some(name) => match name.split_once(' ') {
+                Some((first, last)) => (first.to_string(), last.trim().to_string()),
+                None => (name, String::new()),
+            },
+            None => (email.split('@').next().unwrap_or(email).to_string(), String::new()),
+        },
+    }
+}
+
+// API key handlers
+
+// Active keys an owner can have at once
+const MAX_API_KEYS_PER_USER: i64 = 25;
+
+fn to_api_key_response(api_key: ApiKey) -> ApiKeyResponse {
+    ApiKeyResponse {
+        id: api_key.id,
+        name: api_key.name,
+        prefix: api_key.prefix,
+        scopes: api_key.scopes,
+        expires_at: api_key.expires_at,
+        last_used_at: api_key.last_used_at,
+        created_at: api_key.created_at,
+    }
+}
+
+pub async fn create_api_key_handler(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    key_data: web::Json<CreateApiKeyRequest>,
+) -> AppResult<HttpResponse> {
+    key_data.validate()?;
+
+    // Admin accounts are too powerful to hand to an integration
+    if auth_user.role != UserRole::PropertyOwner {
+        return Err(crate::error::AppError::Authorization(
+            "API keys are only available to property owners".to_string(),
+        ));
+    }
+
+    if count_api_keys_for_user(&pool, auth_user.id).await? >= MAX_API_KEYS_PER_USER {
+        return Err(crate::error::AppError::BadRequest(format!(
+            "You can have at most {} API keys",
+            MAX_API_KEYS_PER_USER
+        )));
+    }
+
+    let mut scopes = key_data.scopes.clone();
+    scopes.sort();
+    scopes.dedup();
+    let expires_at = key_data
+        .expires_in_days
+        .map(|days| OffsetDateTime::now_utc() + time::Duration::days(days));
+
+    let (prefix, key) = generate_api_key()?;
+    let api_key = create_api_key(&pool, auth_user.id, &key_data.name, &prefix, &hash_token(&key), &scopes, expires_at).await?;
+
+    Ok(HttpResponse::Created().json(CreatedApiKeyResponse { key, api_key: to_api_key_response(api_key) }))
+}
+
+pub async fn get_my_api_keys(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+) -> AppResult<HttpResponse> {
+    let api_keys = get_api_keys_by_user(&pool, auth_user.id).await?;
+    let response: Vec<ApiKeyResponse> = api_keys.into_iter().map(to_api_key_response).collect();
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn revoke_api_key_handler(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    if !revoke_api_key(&pool, auth_user.id, path.into_inner()).await? {
+        return Err(crate::error::AppError::NotFound("API key not found".to_string()));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Session handlers
+pub async fn get_my_sessions(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+) -> AppResult<HttpResponse> {
+    let sessions = get_active_sessions_by_user(&pool, auth_user.id).await?;
+
+    let response: Vec<SessionResponse> = sessions
+        .into_iter()
+        .map(|session| SessionResponse {
+            current: auth_user.session_id == Some(session.id),
+            id: session.id,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            expires_at: session.expires_at,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn revoke_my_session(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    if !revoke_session(&pool, auth_user.id, path.into_inner()).await? {
+        return Err(crate::error::AppError::NotFound("Session not found".to_string()));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Public keys for verifying access tokens (RFC 7517)
+pub async fn get_jwks(keys: web::Data<JwtKeys>) -> AppResult<HttpResponse> {
+    Ok(HttpResponse::Ok().json(keys.jwks()))
+}
+
+// Property handlers
+pub async fn get_properties(
+    pool: web::Data<sqlx::PgPool>,
+    query: web::Query<PropertyFilters>,
+) -> AppResult<HttpResponse> {
+    let mut filters = query.into_inner();
+    filters.validate()?;
+
+    let per_page = filters.per_page();
+
+    if filters.uses_cursor() {
+        let cursor = match filters.cursor.as_deref() {
+            Some(cursor) => Some(ListingCursor::decode(cursor).ok_or_else(|| {
+                crate::error::AppError::Validation("Invalid cursor".to_string())
+            })?),
+            None => None,
+        };
+        // Following a cursor keeps its sort order unless another one is asked for
+        if let (None, Some(cursor)) = (filters.sort, &cursor) {
+            filters.sort = Some(cursor.sort);
+        }
+
+        let (properties, next_cursor) =
+            get_properties_after_cursor(&pool, &filters, cursor.as_ref()).await?;
+
+        let response = CursorPaginatedResponse {
+            data: build_property_responses(&pool, properties).await?,
+            per_page,
+            has_more: next_cursor.is_some(),
+            next_cursor: next_cursor.map(|cursor| cursor.encode()),
+        };
+
+        return Ok(HttpResponse::Ok().json(response));
+    }
+
+    let (properties, total) = get_properties_with_filters(&pool, &filters).await?;
+
+    let page = filters.page();
+    let total_pages = (total + per_page - 1) / per_page;
+
+    let response = PaginatedResponse {
+        data: build_property_responses(&pool, properties).await?,
+        total,
+        page,
+        per_page,
+        total_pages,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// Convert to PropertyResponse with owner info.
+// Owners are loaded in one batch so the query count does not grow with the page size.
+async fn build_property_responses(
+    pool: &sqlx::PgPool,
+    properties: Vec<Property>,
+) -> AppResult<Vec<PropertyResponse>> {
+    let owner_ids: Vec<Uuid> = properties.iter().map(|property| property.owner_id).collect();
+    let owners = get_users_by_ids(pool, &owner_ids).await?;
+
+    let property_ids: Vec<Uuid> = properties.iter().map(|property| property.id).collect();
+    let mut photos = get_photos_by_property_ids(pool, &property_ids).await?;
+
+    properties
+        .into_iter()
+        .map(|property| {
+            let owner = owners
+                .get(&property.owner_id)
+                .ok_or_else(|| crate::error::AppError::NotFound("Owner not found".to_string()))?;
+            let property_photos = photos.remove(&property.id).unwrap_or_default();
+            Ok(to_property_response(property, owner, property_photos, false))
+        })
+        .collect()
+}
+
+fn to_user_response(user: &User) -> UserResponse {
+    UserResponse {
         id: user.id,
-        email: user.email,
-        first_name: user.first_name,
-        last_name: user.last_name,
-        role: user.role,
+        email: user.email.clone(),
+        first_name: user.first_name.clone(),
+        last_name: user.last_name.clone(),
+        role: user.role.clone(),
+        email_verified: user.email_verified_at.is_some(),
+        two_factor_enabled: user.totp_enabled_at.is_some(),
+    }
+}
+
+// The exact address and coordinates are only included when `exact_location` is set
+fn to_property_response(
+    property: Property,
+    owner: &User,
+    photos: Vec<PropertyPhoto>,
+    exact_location: bool,
+) -> PropertyResponse {
+    let (address, latitude, longitude) = if exact_location {
+        (Some(property.address), property.latitude, property.longitude)
+    } else {
+        (None, property.approximate_latitude, property.approximate_longitude)
+    };
+
+    PropertyResponse {
+        id: property.id,
+        title: property.title,
+        description: property.description,
+        property_type: property.property_type,
+        location: property.location,
+        address,
+        city: property.city,
+        country: property.country,
+        latitude,
+        longitude,
+        exact_location,
+        price_per_night: property.price_per_night,
+        max_guests: property.max_guests,
+        bedrooms: property.bedrooms,
+        bathrooms: property.bathrooms,
+        amenities: property.amenities,
+        attributes: property.attributes,
+        house_rules_version: property.house_rules.version(),
+        house_rules: property.house_rules,
+        timezone: property.timezone,
+        photos: photos.into_iter().map(PhotoResponse::from).collect(),
+        average_rating: property.average_rating,
+        review_count: property.review_count,
+        owner: HostResponse {
+            id: owner.id,
+            first_name: owner.first_name.clone(),
+            email: exact_location.then(|| owner.email.clone()),
+        },
+    }
+}
+
+pub async fn get_property(
+    pool: web::Data<sqlx::PgPool>,
+    viewer: Option<AuthUser>,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let property_id = path.into_inner();
+    
+    let property = get_published_property_by_id(&pool, property_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+
+    let owner = get_user_by_id(&pool, property.owner_id).await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Owner not found".to_string()))?;
+
+    let photos = get_photos_by_property(&pool, property.id).await?;
+
+    // Signing in is optional here; the exact address is for the owner, admins
+    // and guests whose booking has been approved
+    let exact_location = match viewer {
+        Some(viewer) => {
+            viewer.role == UserRole::Admin
+                || viewer.id == property.owner_id
+                || has_confirmed_booking(&pool, viewer.id, property.id).await?
+        }
+        None => false,
+    };
+
+    let response = to_property_response(property, &owner, photos, exact_location);
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn get_amenity_catalog(
+    pool: web::Data<sqlx::PgPool>,
+) -> AppResult<HttpResponse> {
+    let amenities = get_amenities(&pool).await?;
+
+    Ok(HttpResponse::Ok().json(amenities))
+}
+
+// Search handlers
+pub async fn search_suggestions(
+    pool: web::Data<sqlx::PgPool>,
+    cache: web::Data<SuggestionCache>,
+    query: web::Query<SearchSuggestQuery>,
+) -> AppResult<HttpResponse> {
+    query.validate()?;
+
+    let q = query.q.trim().to_lowercase();
+    if q.is_empty() {
+        return Err(crate::error::AppError::Validation(
+            "Search query must not be blank".to_string(),
+        ));
+    }
+    let limit = query.limit.unwrap_or(8);
+
+    let key = (q, limit);
+    if let Some(suggestions) = cache.get(&key) {
+        return Ok(HttpResponse::Ok().json(suggestions));
+    }
+
+    let suggestions = get_search_suggestions(&pool, &key.0, limit).await?;
+    cache.insert(key, suggestions.clone());
+
+    Ok(HttpResponse::Ok().json(suggestions))
+}
+
+pub async fn create_property(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    property_data: web::Json<CreatePropertyRequest>,
+) -> AppResult<HttpResponse> {
+    property_data.validate()?;
+
+    let not_allowed = property_data
+        .attributes
+        .keys_not_allowed_for(&property_data.property_type);
+    if !not_allowed.is_empty() {
+        return Err(crate::error::AppError::Validation(format!(
+            "Attributes not allowed for {}: {}",
+            property_data.property_type.as_str(),
+            not_allowed.join(", ")
+        )));
+    }
+
+    let mut property_data = property_data.into_inner();
+    property_data.amenities = resolve_amenity_keys(&pool, &property_data.amenities).await?;
+    property_data.timezone = crate::timezone::resolve(
+        property_data.timezone.as_deref(),
+        property_data.latitude,
+        property_data.longitude,
+    );
+
+    let property = create_property_db(&pool, auth_user.id, &property_data).await?;
+
+    Ok(HttpResponse::Created().json(property))
+}
+
+// Normalize requested amenities to catalog keys, rejecting anything not in the catalog
+async fn resolve_amenity_keys(pool: &sqlx::PgPool, amenities: &[String]) -> AppResult<Vec<String>> {
+    let mut keys: Vec<String> = Vec::with_capacity(amenities.len());
+    for amenity in amenities {
+        let key = amenity.trim().to_lowercase();
+        if !key.is_empty() && !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    let unknown = find_unknown_amenity_keys(pool, &keys).await?;
+    if !unknown.is_empty() {
+        return Err(crate::error::AppError::Validation(format!(
+            "Unknown amenities: {}",
+            unknown.join(", ")
+        )));
+    }
+
+    Ok(keys)
+}
+
+pub async fn get_my_properties(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+) -> AppResult<HttpResponse> {
+    let properties = get_properties_by_owner(&pool, auth_user.id).await?;
+
+    Ok(HttpResponse::Ok().json(properties))
+}
+
+pub async fn update_house_rules_handler(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<Uuid>,
+    rules: web::Json<HouseRules>,
+) -> AppResult<HttpResponse> {
+    rules.validate()?;
+
+    let property_id = path.into_inner();
+
+    get_owned_property(&pool, &auth_user, property_id).await?;
+
+    // Existing bookings keep the rules their guests accepted
+    let property = update_house_rules(&pool, property_id, &rules).await?;
+
+    Ok(HttpResponse::Ok().json(property.house_rules))
+}
+
+pub async fn update_property_timezone_handler(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<Uuid>,
+    timezone_data: web::Json<UpdateTimezoneRequest>,
+) -> AppResult<HttpResponse> {
+    timezone_data.validate()?;
+
+    let property_id = path.into_inner();
+
+    let property = get_owned_property(&pool, &auth_user, property_id).await?;
+    let timezone = crate::timezone::resolve(
+        timezone_data.timezone.as_deref(),
+        property.latitude,
+        property.longitude,
+    );
+
+    let property = update_property_timezone(&pool, property_id, timezone.as_deref()).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "timezone": property.timezone })))
+}
+
+// Listing moderation handlers
+pub async fn get_listing_checklist(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let property_id = path.into_inner();
+
+    let property = get_owned_property(&pool, &auth_user, property_id).await?;
+    let photo_count = count_photos_for_property(&pool, property_id).await?;
+
+    Ok(HttpResponse::Ok().json(ListingChecklist::for_property(&property, photo_count)))
+}
+
+pub async fn submit_property(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let property_id = path.into_inner();
+
+    let property = get_owned_property(&pool, &auth_user, property_id).await?;
+
+    // Only drafts and rejected listings can be (re)submitted
+    if property.status != ListingStatus::Draft && property.status != ListingStatus::Rejected {
+        return Err(crate::error::AppError::BadRequest(
+            "Only draft or rejected listings can be submitted for review".to_string(),
+        ));
+    }
+
+    ensure_listing_complete(&pool, &property).await?;
+
+    let property = submit_property_for_review(&pool, property_id).await?;
+
+    Ok(HttpResponse::Ok().json(property))
+}
+
+pub async fn get_moderation_queue(
+    pool: web::Data<sqlx::PgPool>,
+    _: RequireAdmin,
+    query: web::Query<PageQuery>,
+) -> AppResult<HttpResponse> {
+    query.validate()?;
+
+    let page = query.page();
+    let per_page = query.per_page();
+    let (properties, total) = get_properties_pending_review(&pool, page, per_page).await?;
+
+    let response = PaginatedResponse {
+        data: build_property_responses(&pool, properties).await?,
+        total,
+        page,
+        per_page,
+        total_pages: (total + per_page - 1) / per_page,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn approve_property(
+    pool: web::Data<sqlx::PgPool>,
+    cache: web::Data<SuggestionCache>,
+    RequireAdmin(auth_user): RequireAdmin,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let property_id = path.into_inner();
+
+    let property = get_property_for_review(&pool, property_id).await?;
+
+    // Photos may have been removed since the listing was submitted
+    ensure_listing_complete(&pool, &property).await?;
+
+    let property = review_property(&pool, property.id, auth_user.id, ListingStatus::Published, None).await?;
+
+    // Newly published locations should show up in autocomplete right away
+    cache.clear();
+
+    Ok(HttpResponse::Ok().json(property))
+}
+
+pub async fn reject_property(
+    pool: web::Data<sqlx::PgPool>,
+    RequireAdmin(auth_user): RequireAdmin,
+    path: web::Path<Uuid>,
+    reject_data: web::Json<RejectListingRequest>,
+) -> AppResult<HttpResponse> {
+    reject_data.validate()?;
+
+    let property_id = path.into_inner();
+
+    let property = get_property_for_review(&pool, property_id).await?;
+
+    let property = review_property(
+        &pool,
+        property.id,
+        auth_user.id,
+        ListingStatus::Rejected,
+        Some(reject_data.reason.trim()),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(property))
+}
+
+async fn ensure_listing_complete(pool: &sqlx::PgPool, property: &Property) -> AppResult<()> {
+    let photo_count = count_photos_for_property(pool, property.id).await?;
+    let checklist = ListingChecklist::for_property(property, photo_count);
+    if !checklist.complete {
+        let missing: Vec<&str> = checklist
+            .items
+            .iter()
+            .filter(|item| !item.complete)
+            .map(|item| item.key)
+            .collect();
+        return Err(crate::error::AppError::Validation(format!(
+            "Listing is incomplete: {}",
+            missing.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+async fn get_property_for_review(
+    pool: &sqlx::PgPool,
+    property_id: Uuid,
+) -> AppResult<Property> {
+    let property = get_property_by_id(pool, property_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+
+    if property.status != ListingStatus::PendingReview {
+        return Err(crate::error::AppError::BadRequest(
+            "Only listings pending review can be approved or rejected".to_string(),
+        ));
+    }
+
+    Ok(property)
+}
+
+// Photo handlers
+pub async fn upload_property_photo(
+    pool: web::Data<sqlx::PgPool>,
+    store: web::Data<dyn BlobStore>,
+    config: web::Data<Config>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<Uuid>,
+    mut payload: Multipart,
+) -> AppResult<HttpResponse> {
+    let property_id = path.into_inner();
+
+    get_owned_property(&pool, &auth_user, property_id).await?;
+
+    // Enforced again when the photo is saved; this only spares reading an
+    // upload that could not be kept
+    if count_photos_for_property(&pool, property_id).await? >= MAX_PHOTOS_PER_PROPERTY {
+        return Err(crate::error::AppError::BadRequest(format!(
+            "A property can have at most {} photos",
+            MAX_PHOTOS_PER_PROPERTY
+        )));
+    }
+
+    // Expect one `file` part and an optional `caption` part
+    let mut file: Option<(String, Vec<u8>)> = None;
+    let mut caption: Option<String> = None;
+    while let Some(mut field) = payload.try_next().await.map_err(multipart_error)? {
+        let name = field.name().map(str::to_string);
+        let limit = match name.as_deref() {
+            Some("file") => config.max_photo_bytes,
+            Some("caption") => 500,
+            _ => 0,
+        };
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.try_next().await.map_err(multipart_error)? {
+            if bytes.len() + chunk.len() > limit {
+                return Err(crate::error::AppError::Validation(match name.as_deref() {
+                    Some("file") => format!("Photo exceeds the maximum size of {} bytes", limit),
+                    Some("caption") => "Caption must be at most 500 bytes".to_string(),
+                    _ => "Unexpected multipart field".to_string(),
+                }));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        match name.as_deref() {
+            Some("file") => {
+                if file.is_some() {
+                    return Err(crate::error::AppError::BadRequest(
+                        "Only one photo can be uploaded per request".to_string(),
+                    ));
+                }
+                let content_type = field
+                    .content_type()
+                    .map(|mime| mime.essence_str().to_string())
+                    .ok_or_else(|| {
+                        crate::error::AppError::Validation("Photo content type is missing".to_string())
+                    })?;
+                file = Some((content_type, bytes));
+            }
+            Some("caption") => {
+                let text = String::from_utf8(bytes).map_err(|_| {
+                    crate::error::AppError::Validation("Caption must be valid UTF-8".to_string())
+                })?;
+                caption = Some(text.trim().to_string()).filter(|text| !text.is_empty());
+            }
+            _ => {}
+        }
+    }
+
+    let (content_type, bytes) = file
+        .ok_or_else(|| crate::error::AppError::Validation("Missing file field".to_string()))?;
+
+    let processed = web::block(move || process_photo(bytes, &content_type))
+        .await
+        .map_err(|e| crate::error::AppError::Internal(e.to_string()))??;
+    let size_bytes = processed.original.len() as i64;
+
+    let photo_id = Uuid::new_v4();
+    let prefix = format!("properties/{}/{}", property_id, photo_id);
+
+    store.put(&photo_key(&prefix, PhotoVariant::Original), processed.original).await?;
+    for (variant, thumbnail) in processed.thumbnails {
+        store.put(&photo_key(&prefix, variant), thumbnail).await?;
+    }
+
+    let photo = create_photo(
+        &pool,
+        photo_id,
+        property_id,
+        &prefix,
+        processed.content_type,
+        processed.width as i32,
+        processed.height as i32,
+        size_bytes,
+        caption.as_deref(),
+    )
+    .await;
+
+    let photo = match photo {
+        Ok(photo) => photo,
+        Err(e) => {
+            delete_photo_blobs(store.get_ref(), &prefix).await;
+            return Err(e);
+        }
+    };
+
+    Ok(HttpResponse::Created().json(PhotoResponse::from(photo)))
+}
+
+pub async fn get_property_photos(
+    pool: web::Data<sqlx::PgPool>,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let property_id = path.into_inner();
+
+    get_published_property_by_id(&pool, property_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+
+    let photos: Vec<PhotoResponse> = get_photos_by_property(&pool, property_id)
+        .await?
+        .into_iter()
+        .map(PhotoResponse::from)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(photos))
+}
+
+pub async fn update_property_photo(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<(Uuid, Uuid)>,
+    photo_data: web::Json<UpdatePhotoRequest>,
+) -> AppResult<HttpResponse> {
+    photo_data.validate()?;
+
+    let (property_id, photo_id) = path.into_inner();
+
+    get_owned_property(&pool, &auth_user, property_id).await?;
+    get_property_photo(&pool, property_id, photo_id).await?;
+
+    let caption = photo_data
+        .caption
+        .as_deref()
+        .map(str::trim)
+        .filter(|caption| !caption.is_empty());
+    let photo = update_photo_caption(&pool, photo_id, caption).await?;
+
+    Ok(HttpResponse::Ok().json(PhotoResponse::from(photo)))
+}
+
+pub async fn reorder_property_photos(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<Uuid>,
+    order_data: web::Json<ReorderPhotosRequest>,
+) -> AppResult<HttpResponse> {
+    order_data.validate()?;
+
+    let property_id = path.into_inner();
+
+    get_owned_property(&pool, &auth_user, property_id).await?;
+
+    // The new order must mention every photo of the property exactly once
+    let mut requested = order_data.photo_ids.clone();
+    requested.sort_unstable();
+    requested.dedup();
+
+    let mut existing: Vec<Uuid> = get_photos_by_property(&pool, property_id)
+        .await?
+        .into_iter()
+        .map(|photo| photo.id)
+        .collect();
+    existing.sort_unstable();
+
+    if requested.len() != order_data.photo_ids.len() || requested != existing {
+        return Err(crate::error::AppError::Validation(
+            "photo_ids must list every photo of the property exactly once".to_string(),
+        ));
+    }
+
+    let photos: Vec<PhotoResponse> = reorder_photos(&pool, property_id, &order_data.photo_ids)
+        .await?
+        .into_iter()
+        .map(PhotoResponse::from)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(photos))
+}
+
+pub async fn delete_property_photo(
+    pool: web::Data<sqlx::PgPool>,
+    store: web::Data<dyn BlobStore>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<(Uuid, Uuid)>,
+) -> AppResult<HttpResponse> {
+    let (property_id, photo_id) = path.into_inner();
+
+    let property = get_owned_property(&pool, &auth_user, property_id).await?;
+    let photo = get_property_photo(&pool, property_id, photo_id).await?;
+
+    // Listings under review or live keep the photos the checklist asks for
+    if matches!(property.status, ListingStatus::PendingReview | ListingStatus::Published)
+        && count_photos_for_property(&pool, property_id).await? <= MIN_PHOTOS
+    {
+        return Err(crate::error::AppError::BadRequest(format!(
+            "Listings under review or published need at least {} photos",
+            MIN_PHOTOS
+        )));
+    }
+
+    delete_photo(&pool, photo_id).await?;
+
+    if let Some(prefix) = photo.storage_key {
+        delete_photo_blobs(store.get_ref(), &prefix).await;
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn serve_photo(
+    pool: web::Data<sqlx::PgPool>,
+    store: web::Data<dyn BlobStore>,
+    viewer: Option<AuthUser>,
+    path: web::Path<(Uuid, PhotoVariant)>,
+) -> AppResult<HttpResponse> {
+    let (photo_id, variant) = path.into_inner();
+
+    let photo = get_photo_by_id(&pool, photo_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Photo not found".to_string()))?;
+
+    // Photos of listings that are not live are only for the owner and admins
+    let property = get_property_by_id(&pool, photo.property_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Photo not found".to_string()))?;
+    let published = property.is_active && property.status == ListingStatus::Published;
+    if !published {
+        let allowed = viewer
+            .is_some_and(|viewer| viewer.role == UserRole::Admin || viewer.id == property.owner_id);
+        if !allowed {
+            return Err(crate::error::AppError::NotFound("Photo not found".to_string()));
+        }
+    }
+
+    let prefix = match (photo.storage_key, photo.external_url) {
+        (Some(prefix), _) => prefix,
+        // Imported photos only exist at their original URL
+        (None, Some(external_url)) => {
+            return Ok(HttpResponse::Found()
+                .insert_header((header::LOCATION, external_url))
+                .finish());
+        }
+        (None, None) => {
+            return Err(crate::error::AppError::NotFound("Photo not found".to_string()));
+        }
+    };
+
+    let bytes = store
+        .get(&photo_key(&prefix, variant))
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Photo not found".to_string()))?;
+
+    let content_type = match variant {
+        PhotoVariant::Original => photo
+            .content_type
+            .unwrap_or_else(|| "application/octet-stream".to_string()),
+        _ => THUMBNAIL_CONTENT_TYPE.to_string(),
+    };
+
+    // Stored blobs never change once written, but an unpublished listing's
+    // photos must not end up in shared caches
+    let cache_control = if published {
+        "public, max-age=31536000, immutable"
+    } else {
+        "private, no-store"
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((header::CACHE_CONTROL, cache_control))
+        .body(bytes))
+}
+
+// Property owners may only manage their own properties; admins may manage any
+async fn get_owned_property(
+    pool: &sqlx::PgPool,
+    auth_user: &AuthUser,
+    property_id: Uuid,
+) -> AppResult<Property> {
+    let property = get_property_by_id(pool, property_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+
+    if auth_user.role == UserRole::PropertyOwner && property.owner_id != auth_user.id {
+        return Err(crate::error::AppError::Authorization(
+            "You can only manage your own properties".to_string(),
+        ));
+    }
+
+    Ok(property)
+}
+
+async fn get_property_photo(
+    pool: &sqlx::PgPool,
+    property_id: Uuid,
+    photo_id: Uuid,
+) -> AppResult<PropertyPhoto> {
+    get_photo_by_id(pool, photo_id)
+        .await?
+        .filter(|photo| photo.property_id == property_id)
+        .ok_or_else(|| crate::error::AppError::NotFound("Photo not found".to_string()))
+}
+
+// Best effort: a leftover blob is harmless, so failures are only logged
+async fn delete_photo_blobs(store: &dyn BlobStore, prefix: &str) {
+    let variants = std::iter::once(PhotoVariant::Original).chain(PhotoVariant::THUMBNAILS);
+    for variant in variants {
+        if let Err(e) = store.delete(&photo_key(prefix, variant)).await {
+            tracing::warn!("Failed to delete photo blob under {}: {}", prefix, e);
+        }
+    }
+}
+
+fn multipart_error(error: actix_multipart::MultipartError) -> crate::error::AppError {
+    crate::error::AppError::BadRequest(format!("Invalid multipart payload: {}", error))
+}
+
+// Room type handlers
+pub async fn get_property_room_types(
+    pool: web::Data<sqlx::PgPool>,
+    path: web::Path<Uuid>,
+    query: web::Query<StayDatesQuery>,
+) -> AppResult<HttpResponse> {
+    query.validate()?;
+
+    let property_id = path.into_inner();
+    get_published_property_by_id(&pool, property_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+
+    let room_types = get_room_types_by_property(&pool, property_id, false).await?;
+
+    let mut availability = match query.dates() {
+        Some((check_in_date, check_out_date)) => {
+            get_room_type_availability(&pool, property_id, check_in_date, check_out_date).await?
+        }
+        None => Default::default(),
+    };
+
+    let response: Vec<RoomTypeResponse> = room_types
+        .into_iter()
+        .map(|room_type| RoomTypeResponse {
+            availability: availability.remove(&room_type.id),
+            room_type,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn create_room_type_handler(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<Uuid>,
+    room_type_data: web::Json<CreateRoomTypeRequest>,
+) -> AppResult<HttpResponse> {
+    room_type_data.validate()?;
+
+    let property = get_owned_property(&pool, &auth_user, path.into_inner()).await?;
+
+    let kind = room_type_data.kind.unwrap_or(UnitKind::Room);
+    match (&property.property_type, kind) {
+        (PropertyType::Hotel, UnitKind::Room) | (PropertyType::Hostel, _) => {}
+        (PropertyType::Hotel, UnitKind::DormBed) => {
+            return Err(crate::error::AppError::Validation(
+                "Dorm beds are only available for hostels".to_string(),
+            ));
+        }
+        _ => {
+            return Err(crate::error::AppError::Validation(
+                "Room types are only available for hotels and hostels".to_string(),
+            ));
+        }
+    }
+
+    // A dorm bed sleeps one guest
+    if kind == UnitKind::DormBed && room_type_data.max_guests != 1 {
+        return Err(crate::error::AppError::Validation(
+            "Dorm beds fit exactly one guest".to_string(),
+        ));
+    }
+
+    ensure_no_whole_property_bookings(&pool, property.id).await?;
+
+    let room_type = create_room_type(&pool, property.id, &room_type_data, kind).await?;
+
+    Ok(HttpResponse::Created().json(room_type))
+}
+
+pub async fn update_room_type_handler(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<(Uuid, Uuid)>,
+    room_type_data: web::Json<UpdateRoomTypeRequest>,
+) -> AppResult<HttpResponse> {
+    room_type_data.validate()?;
+
+    let (property_id, room_type_id) = path.into_inner();
+    let room_type = get_owned_room_type(&pool, &auth_user, property_id, room_type_id).await?;
+
+    if room_type.kind == UnitKind::DormBed && room_type_data.max_guests.is_some_and(|guests| guests != 1) {
+        return Err(crate::error::AppError::Validation(
+            "Dorm beds fit exactly one guest".to_string(),
+        ));
+    }
+
+    if room_type_data.is_active == Some(true) && !room_type.is_active {
+        ensure_no_whole_property_bookings(&pool, property_id).await?;
+    }
+
+    let room_type = update_room_type(&pool, room_type.id, &room_type_data).await?;
+
+    Ok(HttpResponse::Ok().json(room_type))
+}
+
+// Room types are deactivated rather than deleted so existing bookings keep their reference
+pub async fn deactivate_room_type(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<(Uuid, Uuid)>,
+) -> AppResult<HttpResponse> {
+    let (property_id, room_type_id) = path.into_inner();
+    let room_type = get_owned_room_type(&pool, &auth_user, property_id, room_type_id).await?;
+
+    let deactivate = UpdateRoomTypeRequest {
+        name: None,
+        description: None,
+        max_guests: None,
+        price_per_night: None,
+        total_units: None,
+        is_active: Some(false),
+    };
+    update_room_type(&pool, room_type.id, &deactivate).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn get_room_type_calendar(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<(Uuid, Uuid)>,
+    query: web::Query<StayDatesQuery>,
+) -> AppResult<HttpResponse> {
+    query.validate()?;
+
+    let (property_id, room_type_id) = path.into_inner();
+    let room_type = get_owned_room_type(&pool, &auth_user, property_id, room_type_id).await?;
+
+    let (start_date, end_date) = query.dates().ok_or_else(|| {
+        crate::error::AppError::Validation("check_in_date and check_out_date are required".to_string())
+    })?;
+    if (end_date - start_date).whole_days() > MAX_INVENTORY_RANGE_DAYS {
+        return Err(crate::error::AppError::Validation(
+            "Date range is too long".to_string(),
+        ));
+    }
+
+    let calendar = get_room_type_inventory(&pool, room_type.id, start_date, end_date).await?;
+
+    Ok(HttpResponse::Ok().json(calendar))
+}
+
+pub async fn set_room_type_inventory_handler(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<(Uuid, Uuid)>,
+    inventory_data: web::Json<SetInventoryRequest>,
+) -> AppResult<HttpResponse> {
+    inventory_data.validate()?;
+
+    let (property_id, room_type_id) = path.into_inner();
+    let room_type = get_owned_room_type(&pool, &auth_user, property_id, room_type_id).await?;
+
+    set_room_type_inventory(&pool, room_type.id, &inventory_data).await?;
+
+    let end_date = inventory_data.end_date.next_day().unwrap_or(inventory_data.end_date);
+    let calendar = get_room_type_inventory(&pool, room_type.id, inventory_data.start_date, end_date).await?;
+
+    Ok(HttpResponse::Ok().json(calendar))
+}
+
+// Room types take over availability from whole-property bookings, so they
+// can't be switched on while such bookings are still open
+async fn ensure_no_whole_property_bookings(pool: &sqlx::PgPool, property_id: Uuid) -> AppResult<()> {
+    if !has_active_room_types(pool, property_id).await? && has_whole_property_bookings(pool, property_id).await? {
+        return Err(crate::error::AppError::Validation(
+            "This property has open bookings for the whole property; resolve them before adding room types".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn get_owned_room_type(
+    pool: &sqlx::PgPool,
+    auth_user: &AuthUser,
+    property_id: Uuid,
+    room_type_id: Uuid,
+) -> AppResult<RoomType> {
+    let property = get_owned_property(pool, auth_user, property_id).await?;
+
+    get_room_type_by_id(pool, room_type_id)
+        .await?
+        .filter(|room_type| room_type.property_id == property.id)
+        .ok_or_else(|| crate::error::AppError::NotFound("Room type not found".to_string()))
+}
+
+// Booking handlers
+pub async fn create_booking(
+    pool: web::Data<sqlx::PgPool>,
+    config: web::Data<Config>,
+    RequireGuest(auth_user): RequireGuest,
+    booking_data: web::Json<CreateBookingRequest>,
+) -> AppResult<HttpResponse> {
+    booking_data.validate()?;
+
+    if config.require_verified_email_for_booking && !auth_user.email_verified {
+        return Err(crate::error::AppError::Authorization(
+            "Please verify your email address before booking".to_string(),
+        ));
+    }
+
+    let property_id = booking_data.property_id;
+    
+    // Check if property exists and is published
+    let property = get_published_property_by_id(&pool, property_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+
+    // Dates are the property's local calendar days
+    let check_in_date = booking_data.check_in_date;
+    let check_out_date = booking_data.check_out_date;
+    let local_now = property.local_now();
+    if check_in_date < local_now.date() {
+        return Err(crate::error::AppError::Validation(
+            "Check-in date cannot be in the past".to_string(),
+        ));
+    }
+
+    // Same-day stays can only be booked until the latest check-in time,
+    // unless check-in runs past midnight
+    if let Some(check_in_until) = property.house_rules.check_in_until {
+        if check_in_date == local_now.date()
+            && check_in_until > property.house_rules.check_in_from
+            && local_now.time() > check_in_until
+        {
+            return Err(crate::error::AppError::Validation(
+                "Check-in for today has closed at this property".to_string(),
+            ));
+        }
+    }
+
+    // Guests see the rules on the listing and accept them by sending back
+    // their version; rules changed since then have to be reviewed again
+    match booking_data.house_rules_version.as_deref() {
+        None => {
+            return Err(crate::error::AppError::Validation(
+                "You must accept the house rules to book this property".to_string(),
+            ));
+        }
+        Some(version) if version != property.house_rules.version() => {
+            return Err(crate::error::AppError::BadRequest(
+                "The house rules have changed since you accepted them; please review them again".to_string(),
+            ));
+        }
+        Some(_) => {}
+    }
+
+    let units = booking_data.units.unwrap_or(1);
+
+    let booking = if has_active_room_types(&pool, property_id).await? {
+        // Hotels and hostels with room types are booked per unit
+        let room_type_id = booking_data.room_type_id.ok_or_else(|| {
+            crate::error::AppError::Validation("room_type_id is required for this property".to_string())
+        })?;
+        let room_type = get_room_type_by_id(&pool, room_type_id)
+            .await?
+            .filter(|room_type| room_type.property_id == property_id && room_type.is_active)
+            .ok_or_else(|| crate::error::AppError::NotFound("Room type not found".to_string()))?;
+
+        if booking_data.guest_count > units * room_type.max_guests {
+            return Err(crate::error::AppError::Validation(format!(
+                "{} unit(s) of this room type fit at most {} guests",
+                units,
+                units * room_type.max_guests
+            )));
+        }
+
+        create_room_booking(
+            &pool,
+            &room_type,
+            auth_user.id,
+            check_in_date,
+            check_out_date,
+            units,
+            booking_data.guest_count,
+            booking_data.special_requests.as_deref(),
+            Some(&property.house_rules),
+        )
+        .await?
+    } else {
+        if booking_data.room_type_id.is_some() || units != 1 {
+            return Err(crate::error::AppError::Validation(
+                "This property is booked as a whole".to_string(),
+            ));
+        }
+
+        if booking_data.guest_count > property.max_guests {
+            return Err(crate::error::AppError::Validation(format!(
+                "This property fits at most {} guests",
+                property.max_guests
+            )));
+        }
+
+        // Check availability
+        let is_available = check_property_availability(&pool, property_id, check_in_date, check_out_date).await?;
+        if !is_available {
+            return Err(crate::error::AppError::BadRequest(
+                "Property is not available for the selected dates".to_string(),
+            ));
+        }
+
+        // Calculate total price
+        let total_price = calculate_booking_price(&pool, property_id, check_in_date, check_out_date, booking_data.guest_count).await?;
+
+        create_booking_db(
+            &pool,
+            property_id,
+            auth_user.id,
+            check_in_date,
+            check_out_date,
+            total_price,
+            booking_data.guest_count,
+            booking_data.special_requests.as_deref(),
+            Some(&property.house_rules),
+        )
+        .await?
+    };
+
+    Ok(HttpResponse::Created().json(booking))
+}
+
+pub async fn get_my_bookings(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+) -> AppResult<HttpResponse> {
+    
+    let bookings = get_bookings_by_user(&pool, auth_user.id).await?;
+
+    // Load every referenced property, then their owners together with the guest,
+    // so the number of queries stays constant however many bookings there are
+    let property_ids: Vec<Uuid> = bookings.iter().map(|booking| booking.property_id).collect();
+    let properties = get_properties_by_ids(&pool, &property_ids).await?;
+
+    let mut user_ids: Vec<Uuid> = properties.values().map(|property| property.owner_id).collect();
+    user_ids.push(auth_user.id);
+    let users = get_users_by_ids(&pool, &user_ids).await?;
+
+    let photos = get_photos_by_property_ids(&pool, &property_ids).await?;
+
+    let user = users
+        .get(&auth_user.id)
+        .ok_or_else(|| crate::error::AppError::NotFound("User not found".to_string()))?;
+
+    // Convert to BookingResponse with property and user info
+    let mut booking_responses = Vec::new();
+    for booking in bookings {
+        let property = properties
+            .get(&booking.property_id)
+            .cloned()
+            .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+        
+        let owner = users
+            .get(&property.owner_id)
+            .ok_or_else(|| crate::error::AppError::NotFound("Owner not found".to_string()))?;
+
+        let booking_response = BookingResponse {
+            id: booking.id,
+            property: to_property_response(
+                property,
+                owner,
+                photos.get(&booking.property_id).cloned().unwrap_or_default(),
+                matches!(booking.status, BookingStatus::Approved | BookingStatus::Completed),
+            ),
+            user: to_user_response(user),
+            check_in_date: booking.check_in_date,
+            check_out_date: booking.check_out_date,
+            total_price: booking.total_price,
+            status: booking.status,
+            guest_count: booking.guest_count,
+            special_requests: booking.special_requests,
+            room_type_id: booking.room_type_id,
+            units: booking.units,
+            accepted_house_rules: booking.accepted_house_rules,
+            house_rules_accepted_at: booking.house_rules_accepted_at,
+            created_at: booking.created_at,
+        };
+
+        booking_responses.push(booking_response);
+    }
+
+    Ok(HttpResponse::Ok().json(booking_responses))
+}
+
+pub async fn cancel_booking(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let booking_id = path.into_inner();
+    
+    let booking = get_booking_by_id(&pool, booking_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Booking not found".to_string()))?;
+
+    // Users can only cancel their own bookings
+    if auth_user.role == UserRole::User && booking.user_id != auth_user.id {
+        return Err(crate::error::AppError::Authorization(
+            "You can only cancel your own bookings".to_string(),
+        ));
+    }
+
+    let property = get_property_by_id(&pool, booking.property_id).await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+
+    // Property owners can cancel bookings for their properties
+    if auth_user.role == UserRole::PropertyOwner && property.owner_id != auth_user.id {
+        return Err(crate::error::AppError::Authorization(
+            "You can only cancel bookings for your own properties".to_string(),
+        ));
+    }
+
+    // Only pending or approved bookings can be cancelled
+    if booking.status != BookingStatus::Pending && booking.status != BookingStatus::Approved {
+        return Err(crate::error::AppError::BadRequest(
+            "Only pending or approved bookings can be cancelled".to_string(),
+        ));
+    }
+
+    // Approved stays can be cancelled until check-in opens, in property-local time
+    if booking.status == BookingStatus::Approved
+        && OffsetDateTime::now_utc() >= property.stay_starts_at(booking.check_in_date)
+    {
+        return Err(crate::error::AppError::BadRequest(
+            "Stays that have already started cannot be cancelled".to_string(),
+        ));
+    }
+
+    let updated_booking = update_booking_status(&pool, booking_id, BookingStatus::Cancelled).await?;
+
+    Ok(HttpResponse::Ok().json(updated_booking))
+}
+
+pub async fn approve_booking(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let booking_id = path.into_inner();
+
+    let booking = get_booking_by_id(&pool, booking_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Booking not found".to_string()))?;
+
+    // Property owners can only approve bookings for their properties
+    if auth_user.role == UserRole::PropertyOwner {
+        let property = get_property_by_id(&pool, booking.property_id).await?
+            .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+        
+        if property.owner_id != auth_user.id {
+            return Err(crate::error::AppError::Authorization(
+                "You can only approve bookings for your own properties".to_string(),
+            ));
+        }
+    }
+
+    // Only pending bookings can be approved
+    if booking.status != BookingStatus::Pending {
+        return Err(crate::error::AppError::BadRequest(
+            "Only pending bookings can be approved".to_string(),
+        ));
+    }
+
+    let updated_booking = update_booking_status(&pool, booking_id, BookingStatus::Approved).await?;
+
+    Ok(HttpResponse::Ok().json(updated_booking))
+}
+
+pub async fn deny_booking(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let booking_id = path.into_inner();
+
+    let booking = get_booking_by_id(&pool, booking_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Booking not found".to_string()))?;
+
+    // Property owners can only deny bookings for their properties
+    if auth_user.role == UserRole::PropertyOwner {
+        let property = get_property_by_id(&pool, booking.property_id).await?
+            .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+        
+        if property.owner_id != auth_user.id {
+            return Err(crate::error::AppError::Authorization(
+                "You can only deny bookings for your own properties".to_string(),
+            ));
+        }
+    }
+
+    // Only pending bookings can be denied
+    if booking.status != BookingStatus::Pending {
+        return Err(crate::error::AppError::BadRequest(
+            "Only pending bookings can be denied".to_string(),
+        ));
+    }
+
+    let updated_booking = update_booking_status(&pool, booking_id, BookingStatus::Denied).await?;
+
+    Ok(HttpResponse::Ok().json(updated_booking))
+}
+
+// Review handlers
+pub async fn create_review(
+    pool: web::Data<sqlx::PgPool>,
+    config: web::Data<Config>,
+    auth_user: AuthUser,
+    path: web::Path<Uuid>,
+    review_data: web::Json<CreateReviewRequest>,
+) -> AppResult<HttpResponse> {
+    review_data.validate()?;
+
+    let booking_id = path.into_inner();
+
+    let booking = get_booking_by_id(&pool, booking_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Booking not found".to_string()))?;
+
+    // Only the guest who stayed can review
+    if booking.user_id != auth_user.id {
+        return Err(crate::error::AppError::Authorization(
+            "You can only review your own stays".to_string(),
+        ));
+    }
+
+    if booking.status != BookingStatus::Completed {
+        return Err(crate::error::AppError::BadRequest(
+            "Only completed stays can be reviewed".to_string(),
+        ));
+    }
+
+    // The review window closes at the end of the deadline day at the property
+    let property = get_property_by_id(&pool, booking.property_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+    if property.local_today() > booking.review_deadline(config.review_window_days) {
+        return Err(crate::error::AppError::BadRequest(
+            "The review window for this stay has closed".to_string(),
+        ));
+    }
+
+    if get_review_by_booking(&pool, booking_id).await?.is_some() {
+        return Err(crate::error::AppError::BadRequest(
+            "This booking has already been reviewed".to_string(),
+        ));
+    }
+
+    let review = create_review_db(&pool, &booking, &review_data).await?;
+
+    let guest = get_user_by_id(&pool, auth_user.id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("User not found".to_string()))?;
+
+    Ok(HttpResponse::Created().json(to_review_response(review, &guest)))
+}
+
+pub async fn get_property_reviews(
+    pool: web::Data<sqlx::PgPool>,
+    path: web::Path<Uuid>,
+    query: web::Query<PageQuery>,
+) -> AppResult<HttpResponse> {
+    query.validate()?;
+
+    let property_id = path.into_inner();
+
+    get_published_property_by_id(&pool, property_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+
+    let page = query.page();
+    let per_page = query.per_page();
+    let (reviews, total) = get_reviews_by_property(&pool, property_id, page, per_page).await?;
+
+    let guest_ids: Vec<Uuid> = reviews.iter().map(|review| review.guest_id).collect();
+    let guests = get_users_by_ids(&pool, &guest_ids).await?;
+
+    let mut data = Vec::with_capacity(reviews.len());
+    for review in reviews {
+        let guest = guests
+            .get(&review.guest_id)
+            .ok_or_else(|| crate::error::AppError::NotFound("Reviewer not found".to_string()))?;
+        data.push(to_review_response(review, guest));
+    }
+
+    let response = PaginatedResponse {
+        data,
+        total,
+        page,
+        per_page,
+        total_pages: (total + per_page - 1) / per_page,
     };
 
-    Ok(HttpResponse::Created().json(response))
+    Ok(HttpResponse::Ok().json(response))
 }
 
-pub async fn login(
+fn to_review_response(review: Review, guest: &User) -> ReviewResponse {
+    ReviewResponse {
+        id: review.id,
+        booking_id: review.booking_id,
+        overall_rating: review.overall_rating,
+        cleanliness_rating: review.cleanliness_rating,
+        accuracy_rating: review.accuracy_rating,
+        location_rating: review.location_rating,
+        value_rating: review.value_rating,
+        comment: review.comment,
+        reviewer: ReviewerResponse {
+            id: guest.id,
+            first_name: guest.first_name.clone(),
+        },
+        host_reply: match (review.host_reply, review.host_replied_at) {
+            (Some(reply), Some(replied_at)) => Some(HostReplyResponse { reply, replied_at }),
+            _ => None,
+        },
+        published_at: review.published_at,
+        created_at: review.created_at,
+    }
+}
+
+pub async fn reply_to_review(
     pool: web::Data<sqlx::PgPool>,
-    login_data: web::Json<LoginRequest>,
+    auth_user: AuthUser,
+    path: web::Path<Uuid>,
+    reply_data: web::Json<ReviewReplyRequest>,
 ) -> AppResult<HttpResponse> {
-    login_data.validate()?;
+    reply_data.validate()?;
+
+    let review_id = path.into_inner();
+
+    let review = get_review_by_id(&pool, review_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Review not found".to_string()))?;
+
+    let property = get_property_by_id(&pool, review.property_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+
+    // Only the host of the reviewed property can reply
+    if property.owner_id != auth_user.id {
+        return Err(crate::error::AppError::Authorization(
+            "You can only reply to reviews of your own properties".to_string(),
+        ));
+    }
+
+    if review.published_at.is_none() {
+        return Err(crate::error::AppError::NotFound("Review not found".to_string()));
+    }
 
-    // Find user by email
-    let user = get_user_by_email(&pool, &login_data.email)
+    let review = add_host_reply(&pool, review_id, &reply_data.reply)
         .await?
         .ok_or_else(|| {
-            crate::error::AppError::Authentication("Invalid email or password".to_string())
+            crate::error::AppError::BadRequest("This review already has a reply".to_string())
         })?;
 
-    // Verify password
-    let is_valid = verify_password(&login_data.password, &user.password_hash).await?;
-    if !is_valid {
-        return Err(crate::error::AppError::Authentication(
-            "Invalid email or password".to_string(),
+    let guest = get_user_by_id(&pool, review.guest_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Reviewer not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(to_review_response(review, &guest)))
+}
+
+pub async fn create_guest_review(
+    pool: web::Data<sqlx::PgPool>,
+    config: web::Data<Config>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<Uuid>,
+    review_data: web::Json<CreateGuestReviewRequest>,
+) -> AppResult<HttpResponse> {
+    review_data.validate()?;
+
+    let booking_id = path.into_inner();
+
+    let (booking, property) = get_hosted_booking(&pool, &auth_user, booking_id).await?;
+
+    if booking.status != BookingStatus::Completed {
+        return Err(crate::error::AppError::BadRequest(
+            "Only guests of completed stays can be reviewed".to_string(),
         ));
     }
 
-    // Check if user is active
-    if !user.is_active {
-        return Err(crate::error::AppError::Authentication(
-            "Account is deactivated".to_string(),
+    if property.local_today() > booking.review_deadline(config.review_window_days) {
+        return Err(crate::error::AppError::BadRequest(
+            "The review window for this stay has closed".to_string(),
+        ));
+    }
+
+    if get_guest_review_by_booking(&pool, booking_id).await?.is_some() {
+        return Err(crate::error::AppError::BadRequest(
+            "This booking has already been reviewed".to_string(),
         ));
     }
 
-    // Create JWT token
-    let token = create_jwt(user.id, user.role.clone())?;
+    let guest_review = create_guest_review_db(&pool, &booking, auth_user.id, &review_data).await?;
 
-    let response = LoginResponse {
-        token,
-        user: UserResponse {
-            id: user.id,
-            email: user.email,
-            first_name: user.first_name,
-            last_name: user.last_name,
-            role: user.role,
-        },
-    };
+    let host = get_user_by_id(&pool, auth_user.id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("User not found".to_string()))?;
 
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Created().json(to_guest_review_response(guest_review, &host)))
 }
 
-// Property handlers
-pub async fn get_properties(
+// Published reviews of the booking's guest, for the host deciding on the request
+pub async fn get_booking_guest_reviews(
     pool: web::Data<sqlx::PgPool>,
-    query: web::Query<PropertyFilters>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<Uuid>,
+    query: web::Query<PageQuery>,
 ) -> AppResult<HttpResponse> {
-    let filters = query.into_inner();
-    
-    let (properties, total) = get_properties_with_filters(&pool, &filters).await?;
-    
-    let page = filters.page.unwrap_or(1);
-    let per_page = filters.per_page.unwrap_or(10);
-    let total_pages = (total + per_page - 1) / per_page;
+    query.validate()?;
 
-    // Convert to PropertyResponse with owner info
-    let mut property_responses = Vec::new();
-    for property in properties {
-        let owner = get_user_by_id(&pool, property.owner_id).await?
-            .ok_or_else(|| crate::error::AppError::NotFound("Owner not found".to_string()))?;
-        
-        let owner_response = UserResponse {
-            id: owner.id,
-            email: owner.email,
-            first_name: owner.first_name,
-            last_name: owner.last_name,
-            role: owner.role,
-        };
+    let booking_id = path.into_inner();
 
-        let property_response = PropertyResponse {
-            id: property.id,
-            title: property.title,
-            description: property.description,
-            property_type: property.property_type,
-            location: property.location,
-            address: property.address,
-            city: property.city,
-            country: property.country,
-            price_per_night: property.price_per_night,
-            max_guests: property.max_guests,
-            bedrooms: property.bedrooms,
-            bathrooms: property.bathrooms,
-            amenities: property.amenities,
-            images: property.images,
-            owner: owner_response,
-        };
+    let (booking, _) = get_hosted_booking(&pool, &auth_user, booking_id).await?;
 
-        property_responses.push(property_response);
+    let page = query.page();
+    let per_page = query.per_page();
+    let (reviews, total) = get_guest_reviews_by_guest(&pool, booking.user_id, page, per_page).await?;
+
+    let host_ids: Vec<Uuid> = reviews.iter().map(|review| review.host_id).collect();
+    let hosts = get_users_by_ids(&pool, &host_ids).await?;
+
+    let mut data = Vec::with_capacity(reviews.len());
+    for review in reviews {
+        let host = hosts
+            .get(&review.host_id)
+            .ok_or_else(|| crate::error::AppError::NotFound("Reviewer not found".to_string()))?;
+        data.push(to_guest_review_response(review, host));
     }
 
     let response = PaginatedResponse {
-        data: property_responses,
+        data,
         total,
         page,
         per_page,
-        total_pages,
+        total_pages: (total + per_page - 1) / per_page,
     };
 
     Ok(HttpResponse::Ok().json(response))
 }
 
-pub async fn get_property(
+// Hosts may only act on bookings of their own properties; admins may act on any
+async fn get_hosted_booking(
+    pool: &sqlx::PgPool,
+    auth_user: &AuthUser,
+    booking_id: Uuid,
+) -> AppResult<(Booking, Property)> {
+    let booking = get_booking_by_id(pool, booking_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Booking not found".to_string()))?;
+
+    let property = get_property_by_id(pool, booking.property_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+
+    if auth_user.role == UserRole::PropertyOwner && property.owner_id != auth_user.id {
+        return Err(crate::error::AppError::Authorization(
+            "You can only review guests of your own properties".to_string(),
+        ));
+    }
+
+    Ok((booking, property))
+}
+
+fn to_guest_review_response(review: GuestReview, host: &User) -> GuestReviewResponse {
+    GuestReviewResponse {
+        id: review.id,
+        booking_id: review.booking_id,
+        overall_rating: review.overall_rating,
+        cleanliness_rating: review.cleanliness_rating,
+        communication_rating: review.communication_rating,
+        house_rules_rating: review.house_rules_rating,
+        comment: review.comment,
+        host: ReviewerResponse {
+            id: host.id,
+            first_name: host.first_name.clone(),
+        },
+        published_at: review.published_at,
+        created_at: review.created_at,
+    }
+}
+
+// Wishlist handlers
+pub async fn create_wishlist_handler(
     pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+    wishlist_data: web::Json<CreateWishlistRequest>,
+) -> AppResult<HttpResponse> {
+    wishlist_data.validate()?;
+
+    let wishlist = create_wishlist(&pool, auth_user.id, wishlist_data.name.trim()).await?;
+
+    Ok(HttpResponse::Created().json(to_wishlist_summary_response(wishlist, 0)))
+}
+
+pub async fn get_my_wishlists(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+) -> AppResult<HttpResponse> {
+    let wishlists = get_wishlists_by_user(&pool, auth_user.id).await?;
+
+    let response: Vec<WishlistSummaryResponse> = wishlists
+        .into_iter()
+        .map(|(wishlist, item_count)| to_wishlist_summary_response(wishlist, item_count))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn get_wishlist(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
     path: web::Path<Uuid>,
+    query: web::Query<StayDatesQuery>,
 ) -> AppResult<HttpResponse> {
-    let property_id = path.into_inner();
-    
-    let property = get_property_by_id(&pool, property_id)
+    query.validate()?;
+
+    let wishlist = get_own_wishlist(&pool, &auth_user, path.into_inner()).await?;
+
+    let response = build_wishlist_response(&pool, wishlist, query.dates()).await?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// Read-only view of a wishlist through its share link
+pub async fn get_shared_wishlist(
+    pool: web::Data<sqlx::PgPool>,
+    path: web::Path<String>,
+    query: web::Query<StayDatesQuery>,
+) -> AppResult<HttpResponse> {
+    query.validate()?;
+
+    let wishlist = get_wishlist_by_share_token(&pool, &path.into_inner())
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Wishlist not found".to_string()))?;
+
+    let response = build_wishlist_response(&pool, wishlist, query.dates()).await?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn update_wishlist(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+    path: web::Path<Uuid>,
+    wishlist_data: web::Json<UpdateWishlistRequest>,
+) -> AppResult<HttpResponse> {
+    wishlist_data.validate()?;
+
+    let wishlist = get_own_wishlist(&pool, &auth_user, path.into_inner()).await?;
+
+    let wishlist = rename_wishlist(&pool, wishlist.id, wishlist_data.name.trim()).await?;
+
+    let response = build_wishlist_response(&pool, wishlist, None).await?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn delete_wishlist_handler(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let wishlist = get_own_wishlist(&pool, &auth_user, path.into_inner()).await?;
+
+    delete_wishlist(&pool, wishlist.id).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn add_wishlist_property(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+    path: web::Path<Uuid>,
+    item_data: web::Json<AddWishlistItemRequest>,
+) -> AppResult<HttpResponse> {
+    let wishlist = get_own_wishlist(&pool, &auth_user, path.into_inner()).await?;
+
+    // Only listings guests can see can be saved
+    get_published_property_by_id(&pool, item_data.property_id)
         .await?
         .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
 
-    let owner = get_user_by_id(&pool, property.owner_id).await?
-        .ok_or_else(|| crate::error::AppError::NotFound("Owner not found".to_string()))?;
-    
-    let owner_response = UserResponse {
-        id: owner.id,
-        email: owner.email,
-        first_name: owner.first_name,
-        last_name: owner.last_name,
-        role: owner.role,
-    };
+    add_wishlist_item(&pool, wishlist.id, item_data.property_id).await?;
 
-    let response = PropertyResponse {
-        id: property.id,
-        title: property.title,
-        description: property.description,
-        property_type: property.property_type,
-        location: property.location,
-        address: property.address,
-        city: property.city,
-        country: property.country,
-        price_per_night: property.price_per_night,
-        max_guests: property.max_guests,
-        bedrooms: property.bedrooms,
-        bathrooms: property.bathrooms,
-        amenities: property.amenities,
-        images: property.images,
-        owner: owner_response,
+    let response = build_wishlist_response(&pool, wishlist, None).await?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn remove_wishlist_property(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> AppResult<HttpResponse> {
+    let (wishlist_id, property_id) = path.into_inner();
+    let wishlist = get_own_wishlist(&pool, &auth_user, wishlist_id).await?;
+
+    if !remove_wishlist_item(&pool, wishlist.id, property_id).await? {
+        return Err(crate::error::AppError::NotFound(
+            "Property is not in this wishlist".to_string(),
+        ));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Create (or keep) the read-only share link
+pub async fn share_wishlist(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let wishlist = get_own_wishlist(&pool, &auth_user, path.into_inner()).await?;
+
+    let wishlist = match wishlist.share_token {
+        Some(_) => wishlist,
+        None => {
+            let share_token = Uuid::new_v4().simple().to_string();
+            set_wishlist_share_token(&pool, wishlist.id, Some(&share_token)).await?
+        }
     };
 
+    let response = build_wishlist_response(&pool, wishlist, None).await?;
+
     Ok(HttpResponse::Ok().json(response))
 }
 
-pub async fn create_property(
+pub async fn unshare_wishlist(
     pool: web::Data<sqlx::PgPool>,
-    req: HttpRequest,
-    property_data: web::Json<CreatePropertyRequest>,
+    auth_user: AuthUser,
+    path: web::Path<Uuid>,
 ) -> AppResult<HttpResponse> {
-    property_data.validate()?;
-    
-    let auth_user = extract_auth_user(&req)?;
-    
-    // Only property owners and admins can create properties
-    if auth_user.role != UserRole::PropertyOwner && auth_user.role != UserRole::Admin {
-        return Err(crate::error::AppError::Authorization(
-            "Only property owners can create properties".to_string(),
-        ));
-    }
+    let wishlist = get_own_wishlist(&pool, &auth_user, path.into_inner()).await?;
 
-    let property = create_property_db(&pool, auth_user.id, &property_data).await?;
+    let wishlist = set_wishlist_share_token(&pool, wishlist.id, None).await?;
 
-    Ok(HttpResponse::Created().json(property))
+    let response = build_wishlist_response(&pool, wishlist, None).await?;
+
+    Ok(HttpResponse::Ok().json(response))
 }
 
-pub async fn get_my_properties(
-    pool: web::Data<sqlx::PgPool>,
-    req: HttpRequest,
-) -> AppResult<HttpResponse> {
-    let auth_user = extract_auth_user(&req)?;
-    
-    // Only property owners and admins can view their properties
-    if auth_user.role != UserRole::PropertyOwner && auth_user.role != UserRole::Admin {
+async fn get_own_wishlist(
+    pool: &sqlx::PgPool,
+    auth_user: &AuthUser,
+    wishlist_id: Uuid,
+) -> AppResult<Wishlist> {
+    let wishlist = get_wishlist_by_id(pool, wishlist_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Wishlist not found".to_string()))?;
+
+    if wishlist.user_id != auth_user.id {
         return Err(crate::error::AppError::Authorization(
-            "Only property owners can view their properties".to_string(),
+            "You can only manage your own wishlists".to_string(),
         ));
     }
 
-    let properties = get_properties_by_owner(&pool, auth_user.id).await?;
+    Ok(wishlist)
+}
 
-    Ok(HttpResponse::Ok().json(properties))
+// Current listing data for every saved property that is still published,
+// plus availability for the requested dates
+async fn build_wishlist_response(
+    pool: &sqlx::PgPool,
+    wishlist: Wishlist,
+    dates: Option<(Date, Date)>,
+) -> AppResult<WishlistResponse> {
+    let items = get_wishlist_items(pool, wishlist.id).await?;
+
+    let property_ids: Vec<Uuid> = items.iter().map(|item| item.property_id).collect();
+    let mut properties = get_properties_by_ids(pool, &property_ids).await?;
+
+    let unavailable = match dates {
+        Some((check_in_date, check_out_date)) => Some(
+            get_unavailable_property_ids(pool, &property_ids, check_in_date, check_out_date).await?,
+        ),
+        None => None,
+    };
+
+    let mut saved = Vec::with_capacity(items.len());
+    for item in &items {
+        if let Some(property) = properties.remove(&item.property_id) {
+            if property.is_active && property.status == ListingStatus::Published {
+                saved.push((property, item.created_at));
+            }
+        }
+    }
+
+    let added_at: Vec<OffsetDateTime> = saved.iter().map(|(_, added_at)| *added_at).collect();
+    let responses = build_property_responses(pool, saved.into_iter().map(|(property, _)| property).collect()).await?;
+
+    let properties = responses
+        .into_iter()
+        .zip(added_at)
+        .map(|(property, added_at)| WishlistPropertyResponse {
+            available: unavailable.as_ref().map(|unavailable| !unavailable.contains(&property.id)),
+            property,
+            added_at,
+        })
+        .collect();
+
+    Ok(WishlistResponse {
+        id: wishlist.id,
+        name: wishlist.name,
+        share_token: wishlist.share_token,
+        properties,
+        created_at: wishlist.created_at,
+        updated_at: wishlist.updated_at,
+    })
 }
 
-// Booking handlers
-pub async fn create_booking(
-    pool: web::Data<sqlx::PgPool>,
-    req: HttpRequest,
-    booking_data: web::Json<CreateBookingRequest>,
-) -> AppResult<HttpResponse> {
-    booking_data.validate()?;
-    
-    let auth_user = extract_auth_user(&req)?;
-    
-    // Only regular users can create bookings
-    if auth_user.role != UserRole::User {
-        return Err(crate::error::AppError::Authorization(
-            "Only users can create bookings".to_string(),
-        ));
+fn to_wishlist_summary_response(wishlist: Wishlist, item_count: i64) -> WishlistSummaryResponse {
+    WishlistSummaryResponse {
+        id: wishlist.id,
+        name: wishlist.name,
+        item_count,
+        share_token: wishlist.share_token,
+        created_at: wishlist.created_at,
+        updated_at: wishlist.updated_at,
     }
+}
 
-    let property_id = booking_data.property_id;
-    
-    // Check if property exists
-    let _property = get_property_by_id(&pool, property_id)
-        .await?
-        .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
+// Saved search handlers
+pub async fn create_saved_search_handler(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+    search_data: web::Json<CreateSavedSearchRequest>,
+) -> AppResult<HttpResponse> {
+    search_data.validate()?;
 
-    // For now, we'll use a simple date range (next day to next day + 1)
-    // In a real app, you'd get these from the request
-    let check_in_date = Date::from_ordinal_date(
-        OffsetDateTime::now_utc().year(),
-        OffsetDateTime::now_utc().ordinal() + 1
-    ).unwrap_or_else(|_| Date::from_ordinal_date(2024, 1).unwrap());
-    
-    let check_out_date = Date::from_ordinal_date(
-        OffsetDateTime::now_utc().year(),
-        OffsetDateTime::now_utc().ordinal() + 2
-    ).unwrap_or_else(|_| Date::from_ordinal_date(2024, 2).unwrap());
-
-    // Check availability
-    let is_available = check_property_availability(&pool, property_id, check_in_date, check_out_date).await?;
-    if !is_available {
-        return Err(crate::error::AppError::BadRequest(
-            "Property is not available for the selected dates".to_string(),
-        ));
+    if count_saved_searches_for_user(&pool, auth_user.id).await? >= MAX_SAVED_SEARCHES_PER_USER {
+        return Err(crate::error::AppError::BadRequest(format!(
+            "You can save at most {} searches",
+            MAX_SAVED_SEARCHES_PER_USER
+        )));
     }
 
-    // Calculate total price
-    let total_price = calculate_booking_price(&pool, property_id, check_in_date, check_out_date, booking_data.guest_count).await?;
+    let filters = search_data.filters.without_paging();
+    let unknown = find_unknown_amenity_keys(&pool, &filters.amenity_keys()).await?;
+    if !unknown.is_empty() {
+        return Err(crate::error::AppError::Validation(format!(
+            "Unknown amenities: {}",
+            unknown.join(", ")
+        )));
+    }
 
-    let booking = create_booking_db(
+    let search = create_saved_search(
         &pool,
-        property_id,
         auth_user.id,
-        check_in_date,
-        check_out_date,
-        total_price,
-        booking_data.guest_count,
-        booking_data.special_requests.as_deref(),
+        search_data.name.trim(),
+        &filters,
+        search_data.alerts_enabled.unwrap_or(true),
     )
     .await?;
 
-    Ok(HttpResponse::Created().json(booking))
+    // Alerts cover listings that appear from now on
+    seed_saved_search(&pool, &search).await?;
+
+    Ok(HttpResponse::Created().json(search))
 }
 
-pub async fn get_my_bookings(
+pub async fn get_my_saved_searches(
     pool: web::Data<sqlx::PgPool>,
-    req: HttpRequest,
+    auth_user: AuthUser,
 ) -> AppResult<HttpResponse> {
-    let auth_user = extract_auth_user(&req)?;
-    
-    let bookings = get_bookings_by_user(&pool, auth_user.id).await?;
-
-    // Convert to BookingResponse with property and user info
-    let mut booking_responses = Vec::new();
-    for booking in bookings {
-        let property = get_property_by_id(&pool, booking.property_id).await?
-            .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
-        
-        let owner = get_user_by_id(&pool, property.owner_id).await?
-            .ok_or_else(|| crate::error::AppError::NotFound("Owner not found".to_string()))?;
-        
-        let owner_response = UserResponse {
-            id: owner.id,
-            email: owner.email,
-            first_name: owner.first_name,
-            last_name: owner.last_name,
-            role: owner.role,
-        };
+    let searches = get_saved_searches_by_user(&pool, auth_user.id).await?;
 
-        let property_response = PropertyResponse {
-            id: property.id,
-            title: property.title,
-            description: property.description,
-            property_type: property.property_type,
-            location: property.location,
-            address: property.address,
-            city: property.city,
-            country: property.country,
-            price_per_night: property.price_per_night,
-            max_guests: property.max_guests,
-            bedrooms: property.bedrooms,
-            bathrooms: property.bathrooms,
-            amenities: property.amenities,
-            images: property.images,
-            owner: owner_response,
-        };
+    Ok(HttpResponse::Ok().json(searches))
+}
 
-        let user_response = UserResponse {
-            id: auth_user.id,
-            email: auth_user.email.clone(),
-            first_name: "".to_string(), // You'd get this from the user record
-            last_name: "".to_string(),
-            role: auth_user.role.clone(),
-        };
+pub async fn update_saved_search_handler(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+    path: web::Path<Uuid>,
+    search_data: web::Json<UpdateSavedSearchRequest>,
+) -> AppResult<HttpResponse> {
+    search_data.validate()?;
 
-        let booking_response = BookingResponse {
-            id: booking.id,
-            property: property_response,
-            user: user_response,
-            check_in_date: booking.check_in_date,
-            check_out_date: booking.check_out_date,
-            total_price: booking.total_price,
-            status: booking.status,
-            guest_count: booking.guest_count,
-            special_requests: booking.special_requests,
-            created_at: booking.created_at,
-        };
+    let search = get_own_saved_search(&pool, &auth_user, path.into_inner()).await?;
 
-        booking_responses.push(booking_response);
-    }
+    let search = update_saved_search(
+        &pool,
+        search.id,
+        search_data.name.as_deref().map(str::trim),
+        search_data.alerts_enabled,
+    )
+    .await?;
 
-    Ok(HttpResponse::Ok().json(booking_responses))
+    Ok(HttpResponse::Ok().json(search))
 }
 
-pub async fn cancel_booking(
+pub async fn delete_saved_search_handler(
     pool: web::Data<sqlx::PgPool>,
-    req: HttpRequest,
+    auth_user: AuthUser,
     path: web::Path<Uuid>,
 ) -> AppResult<HttpResponse> {
-    let booking_id = path.into_inner();
-    let auth_user = extract_auth_user(&req)?;
-    
-    let booking = get_booking_by_id(&pool, booking_id)
-        .await?
-        .ok_or_else(|| crate::error::AppError::NotFound("Booking not found".to_string()))?;
+    let search = get_own_saved_search(&pool, &auth_user, path.into_inner()).await?;
 
-    // Users can only cancel their own bookings
-    if auth_user.role == UserRole::User && booking.user_id != auth_user.id {
-        return Err(crate::error::AppError::Authorization(
-            "You can only cancel your own bookings".to_string(),
-        ));
-    }
+    delete_saved_search(&pool, search.id).await?;
 
-    // Property owners can cancel bookings for their properties
-    if auth_user.role == UserRole::PropertyOwner {
-        let property = get_property_by_id(&pool, booking.property_id).await?
-            .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
-        
-        if property.owner_id != auth_user.id {
-            return Err(crate::error::AppError::Authorization(
-                "You can only cancel bookings for your own properties".to_string(),
-            ));
-        }
-    }
+    Ok(HttpResponse::NoContent().finish())
+}
 
-    // Only pending or approved bookings can be cancelled
-    if booking.status != BookingStatus::Pending && booking.status != BookingStatus::Approved {
-        return Err(crate::error::AppError::BadRequest(
-            "Only pending or approved bookings can be cancelled".to_string(),
+async fn get_own_saved_search(
+    pool: &sqlx::PgPool,
+    auth_user: &AuthUser,
+    search_id: Uuid,
+) -> AppResult<SavedSearch> {
+    let search = get_saved_search_by_id(pool, search_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Saved search not found".to_string()))?;
+
+    if search.user_id != auth_user.id {
+        return Err(crate::error::AppError::Authorization(
+            "You can only manage your own saved searches".to_string(),
         ));
     }
 
-    let updated_booking = update_booking_status(&pool, booking_id, BookingStatus::Cancelled).await?;
-
-    Ok(HttpResponse::Ok().json(updated_booking))
+    Ok(search)
 }
 
-pub async fn approve_booking(
+// Notification handlers
+pub async fn get_my_notifications(
     pool: web::Data<sqlx::PgPool>,
-    req: HttpRequest,
-    path: web::Path<Uuid>,
+    auth_user: AuthUser,
+    query: web::Query<NotificationQuery>,
 ) -> AppResult<HttpResponse> {
-    let booking_id = path.into_inner();
-    let auth_user = extract_auth_user(&req)?;
-    
-    // Only property owners and admins can approve bookings
-    if auth_user.role != UserRole::PropertyOwner && auth_user.role != UserRole::Admin {
-        return Err(crate::error::AppError::Authorization(
-            "Only property owners can approve bookings".to_string(),
-        ));
-    }
-
-    let booking = get_booking_by_id(&pool, booking_id)
-        .await?
-        .ok_or_else(|| crate::error::AppError::NotFound("Booking not found".to_string()))?;
-
-    // Property owners can only approve bookings for their properties
-    if auth_user.role == UserRole::PropertyOwner {
-        let property = get_property_by_id(&pool, booking.property_id).await?
-            .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
-        
-        if property.owner_id != auth_user.id {
-            return Err(crate::error::AppError::Authorization(
-                "You can only approve bookings for your own properties".to_string(),
-            ));
-        }
-    }
+    query.validate()?;
 
-    // Only pending bookings can be approved
-    if booking.status != BookingStatus::Pending {
-        return Err(crate::error::AppError::BadRequest(
-            "Only pending bookings can be approved".to_string(),
-        ));
-    }
+    let page = query.page();
+    let per_page = query.per_page();
+    let (notifications, total, unread_count) = get_notifications_by_user(
+        &pool,
+        auth_user.id,
+        query.unread_only.unwrap_or(false),
+        page,
+        per_page,
+    )
+    .await?;
 
-    let updated_booking = update_booking_status(&pool, booking_id, BookingStatus::Approved).await?;
+    let response = NotificationListResponse {
+        data: notifications,
+        unread_count,
+        total,
+        page,
+        per_page,
+        total_pages: (total + per_page - 1) / per_page,
+    };
 
-    Ok(HttpResponse::Ok().json(updated_booking))
+    Ok(HttpResponse::Ok().json(response))
 }
 
-pub async fn deny_booking(
+pub async fn read_notification(
     pool: web::Data<sqlx::PgPool>,
-    req: HttpRequest,
+    auth_user: AuthUser,
     path: web::Path<Uuid>,
 ) -> AppResult<HttpResponse> {
-    let booking_id = path.into_inner();
-    let auth_user = extract_auth_user(&req)?;
-    
-    // Only property owners and admins can deny bookings
-    if auth_user.role != UserRole::PropertyOwner && auth_user.role != UserRole::Admin {
-        return Err(crate::error::AppError::Authorization(
-            "Only property owners can deny bookings".to_string(),
-        ));
-    }
-
-    let booking = get_booking_by_id(&pool, booking_id)
+    let notification = mark_notification_read(&pool, path.into_inner(), auth_user.id)
         .await?
-        .ok_or_else(|| crate::error::AppError::NotFound("Booking not found".to_string()))?;
+        .ok_or_else(|| crate::error::AppError::NotFound("Notification not found".to_string()))?;
 
-    // Property owners can only deny bookings for their properties
-    if auth_user.role == UserRole::PropertyOwner {
-        let property = get_property_by_id(&pool, booking.property_id).await?
-            .ok_or_else(|| crate::error::AppError::NotFound("Property not found".to_string()))?;
-        
-        if property.owner_id != auth_user.id {
-            return Err(crate::error::AppError::Authorization(
-                "You can only deny bookings for your own properties".to_string(),
-            ));
-        }
-    }
-
-    // Only pending bookings can be denied
-    if booking.status != BookingStatus::Pending {
-        return Err(crate::error::AppError::BadRequest(
-            "Only pending bookings can be denied".to_string(),
-        ));
-    }
+    Ok(HttpResponse::Ok().json(notification))
+}
 
-    let updated_booking = update_booking_status(&pool, booking_id, BookingStatus::Denied).await?;
+pub async fn read_all_notifications(
+    pool: web::Data<sqlx::PgPool>,
+    auth_user: AuthUser,
+) -> AppResult<HttpResponse> {
+    let updated = mark_all_notifications_read(&pool, auth_user.id).await?;
 
-    Ok(HttpResponse::Ok().json(updated_booking))
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "updated": updated })))
 }
 
 // Admin handlers
 pub async fn create_property_owner(
     pool: web::Data<sqlx::PgPool>,
-    req: HttpRequest,
+    config: web::Data<Config>,
+    outbox: web::Data<EmailOutbox>,
+    _: RequireAdmin,
     user_data: web::Json<CreateUserRequest>,
 ) -> AppResult<HttpResponse> {
     user_data.validate()?;
-    
-    let auth_user = extract_auth_user(&req)?;
-    
-    // Only admins can create property owners
-    if auth_user.role != UserRole::Admin {
-        return Err(crate::error::AppError::Authorization(
-            "Only admins can create property owners".to_string(),
-        ));
-    }
 
     // Check if user already exists
     let existing_user = get_user_by_email(&pool, &user_data.email).await?;
@@ -534,32 +2788,102 @@ pub async fn create_property_owner(
     )
     .await?;
 
-    let response = UserResponse {
-        id: user.id,
-        email: user.email,
-        first_name: user.first_name,
-        last_name: user.last_name,
-        role: user.role,
-    };
+    send_verification_email(&pool, &config, &outbox, &user).await?;
 
-    Ok(HttpResponse::Created().json(response))
+    Ok(HttpResponse::Created().json(to_user_response(&user)))
 }
 
-pub async fn get_property_bookings(
+// Sign a user out everywhere, e.g. after an account compromise
+pub async fn revoke_user_sessions_handler(
     pool: web::Data<sqlx::PgPool>,
-    req: HttpRequest,
+    RequireAdmin(auth_user): RequireAdmin,
     path: web::Path<Uuid>,
 ) -> AppResult<HttpResponse> {
-    let property_id = path.into_inner();
-    let auth_user = extract_auth_user(&req)?;
-    
-    // Only property owners and admins can view property bookings
-    if auth_user.role != UserRole::PropertyOwner && auth_user.role != UserRole::Admin {
-        return Err(crate::error::AppError::Authorization(
-            "Only property owners can view property bookings".to_string(),
+    let user_id = path.into_inner();
+
+    get_user_by_id(&pool, user_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("User not found".to_string()))?;
+
+    let revoked = revoke_user_sessions(&pool, user_id).await?;
+    tracing::info!("Admin {} revoked {} sessions of user {}", auth_user.id, revoked, user_id);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "revoked_sessions": revoked })))
+}
+
+pub async fn unlock_user_login(
+    pool: web::Data<sqlx::PgPool>,
+    RequireAdmin(auth_user): RequireAdmin,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = path.into_inner();
+
+    let user = get_user_by_id(&pool, user_id)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("User not found".to_string()))?;
+
+    let unlocked = clear_login_failures(&pool, "account", &user.email.to_lowercase()).await?;
+    tracing::info!("Admin {} cleared failed logins of user {}", auth_user.id, user_id);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "unlocked": unlocked })))
+}
+
+pub async fn create_amenity_handler(
+    pool: web::Data<sqlx::PgPool>,
+    _: RequireAdmin,
+    amenity_data: web::Json<CreateAmenityRequest>,
+) -> AppResult<HttpResponse> {
+    amenity_data.validate()?;
+
+    if get_amenity_by_key(&pool, &amenity_data.key).await?.is_some() {
+        return Err(crate::error::AppError::BadRequest(
+            "Amenity with this key already exists".to_string(),
         ));
     }
 
+    let amenity = create_amenity(&pool, &amenity_data).await?;
+
+    Ok(HttpResponse::Created().json(amenity))
+}
+
+pub async fn update_amenity_handler(
+    pool: web::Data<sqlx::PgPool>,
+    _: RequireAdmin,
+    path: web::Path<String>,
+    amenity_data: web::Json<UpdateAmenityRequest>,
+) -> AppResult<HttpResponse> {
+    amenity_data.validate()?;
+
+    let key = path.into_inner();
+
+    let amenity = update_amenity(&pool, &key, &amenity_data)
+        .await?
+        .ok_or_else(|| crate::error::AppError::NotFound("Amenity not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(amenity))
+}
+
+pub async fn delete_amenity_handler(
+    pool: web::Data<sqlx::PgPool>,
+    _: RequireAdmin,
+    path: web::Path<String>,
+) -> AppResult<HttpResponse> {
+    let key = path.into_inner();
+
+    if !delete_amenity(&pool, &key).await? {
+        return Err(crate::error::AppError::NotFound("Amenity not found".to_string()));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn get_property_bookings(
+    pool: web::Data<sqlx::PgPool>,
+    RequireOwner(auth_user): RequireOwner,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let property_id = path.into_inner();
+
     // Property owners can only view bookings for their properties
     if auth_user.role == UserRole::PropertyOwner {
         let property = get_property_by_id(&pool, property_id).await?
@@ -574,5 +2898,17 @@ pub async fn get_property_bookings(
 
     let bookings = get_bookings_by_property(&pool, property_id).await?;
 
-    Ok(HttpResponse::Ok().json(bookings))
+    // Hosts see how other hosts rated each guest
+    let guest_ids: Vec<Uuid> = bookings.iter().map(|booking| booking.user_id).collect();
+    let guest_ratings = get_guest_rating_summaries(&pool, &guest_ids).await?;
+
+    let response: Vec<PropertyBookingResponse> = bookings
+        .into_iter()
+        .map(|booking| PropertyBookingResponse {
+            guest_rating: guest_ratings.get(&booking.user_id).cloned().unwrap_or_default(),
+            booking,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(response))
 }
@@ -0,0 +1,116 @@
+use crate::{error::AppError, models::PhotoVariant};
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, metadata::Orientation, DynamicImage, ImageDecoder, ImageFormat,
+    ImageReader, Limits,
+};
+use std::io::Cursor;
+
+pub const ALLOWED_CONTENT_TYPES: [&str; 3] = ["image/jpeg", "image/png", "image/webp"];
+pub const THUMBNAIL_CONTENT_TYPE: &str = "image/jpeg";
+pub const MAX_PHOTOS_PER_PROPERTY: i64 = 50;
+
+// Reject absurd dimensions before allocating a decode buffer
+const MAX_DIMENSION: u32 = 12_000;
+const THUMBNAIL_QUALITY: u8 = 85;
+const ORIGINAL_QUALITY: u8 = 92;
+
+pub struct ProcessedPhoto {
+    pub content_type: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub original: Vec<u8>,
+    pub thumbnails: Vec<(PhotoVariant, Vec<u8>)>,
+}
+
+fn format_for_content_type(content_type: &str) -> Option<(ImageFormat, &'static str)> {
+    match content_type {
+        "image/jpeg" | "image/jpg" => Some((ImageFormat::Jpeg, "image/jpeg")),
+        "image/png" => Some((ImageFormat::Png, "image/png")),
+        "image/webp" => Some((ImageFormat::WebP, "image/webp")),
+        _ => None,
+    }
+}
+
+// Storage key of one variant of a photo stored under `prefix`
+pub fn photo_key(prefix: &str, variant: PhotoVariant) -> String {
+    match variant {
+        PhotoVariant::Original => format!("{}/original", prefix),
+        _ => format!("{}/{}.jpg", prefix, variant.as_str()),
+    }
+}
+
+// Validate an upload against its declared content type, re-encode it without
+// its metadata and render the thumbnail sizes.
+// CPU bound: call from a blocking thread.
+pub fn process_photo(bytes: Vec<u8>, declared_content_type: &str) -> Result<ProcessedPhoto, AppError> {
+    let (declared_format, content_type) = format_for_content_type(declared_content_type)
+        .ok_or_else(|| {
+            AppError::Validation(format!(
+                "Unsupported image type, expected one of: {}",
+                ALLOWED_CONTENT_TYPES.join(", ")
+            ))
+        })?;
+
+    let detected_format = image::guess_format(&bytes)
+        .map_err(|_| AppError::Validation("File is not a supported image".to_string()))?;
+    if detected_format != declared_format {
+        return Err(AppError::Validation(
+            "File content does not match its content type".to_string(),
+        ));
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+
+    let mut reader = ImageReader::with_format(Cursor::new(&bytes), detected_format);
+    reader.limits(limits);
+    let undecodable = |_| AppError::Validation("Image could not be decoded".to_string());
+    let mut decoder = reader.into_decoder().map_err(undecodable)?;
+    // The EXIF block saying which way up the photo is goes away with the rest
+    // of the metadata, so the rotation is applied to the pixels instead
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder).map_err(undecodable)?;
+    image.apply_orientation(orientation);
+
+    // Stored re-encoded rather than as uploaded: originals are served publicly
+    // and EXIF can hold the exact GPS position the photo was taken at
+    let original = encode_original(&image, detected_format)?;
+
+    let mut thumbnails = Vec::with_capacity(PhotoVariant::THUMBNAILS.len());
+    for variant in PhotoVariant::THUMBNAILS {
+        let max = variant.max_dimension().unwrap_or(MAX_DIMENSION);
+        let resized = if image.width() > max || image.height() > max {
+            image.resize(max, max, FilterType::Lanczos3)
+        } else {
+            image.clone()
+        };
+
+        let mut encoded = Vec::new();
+        resized
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, THUMBNAIL_QUALITY))
+            .map_err(|e| AppError::Internal(format!("Failed to encode thumbnail: {}", e)))?;
+        thumbnails.push((variant, encoded));
+    }
+
+    Ok(ProcessedPhoto {
+        content_type,
+        width: image.width(),
+        height: image.height(),
+        original,
+        thumbnails,
+    })
+}
+
+fn encode_original(image: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, AppError> {
+    let mut encoded = Vec::new();
+    let result = match format {
+        ImageFormat::Jpeg => image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, ORIGINAL_QUALITY)),
+        _ => image.write_to(&mut Cursor::new(&mut encoded), format),
+    };
+    result.map_err(|e| AppError::Internal(format!("Failed to encode photo: {}", e)))?;
+    Ok(encoded)
+}
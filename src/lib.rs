@@ -1,14 +1,25 @@
+pub mod alerts;
 pub mod auth;
+pub mod cache;
 pub mod config;
 pub mod db;
+pub mod email;
 pub mod error;
 pub mod handlers;
+pub mod images;
 pub mod models;
+pub mod oidc;
 pub mod routes;
+pub mod storage;
+pub mod timezone;
 
 #[cfg(test)]
 mod tests {
-    use super::models::{UserRole, PropertyType, BookingStatus};
+    use super::models::{UserRole, PropertyType, BookingStatus, PropertyFilters, validate_amenity_key,
+        ListingCursor, CursorKey, PropertySort, MAX_PAGE_SIZE};
+    use super::cache::TtlCache;
+    use super::db::escape_like;
+    use std::time::Duration;
 
     #[test]
     fn test_user_role_serialization() {
@@ -62,5 +73,1927 @@ mod tests {
         let serialized = serde_json::to_string(&status).unwrap();
         assert_eq!(serialized, "\"Completed\"");
     }
-}
 
+    #[test]
+    fn test_review_window_and_ratings() {
+        use super::models::{Booking, CreateReviewRequest};
+        use validator::Validate;
+
+        let booking: Booking = serde_json::from_value(serde_json::json!({
+            "id": uuid::Uuid::new_v4(),
+            "property_id": uuid::Uuid::new_v4(),
+            "user_id": uuid::Uuid::new_v4(),
+            "check_in_date": [2024, 60],
+            "check_out_date": [2024, 63],
+            "total_price": 30000,
+            "status": "Completed",
+            "guest_count": 2,
+            "special_requests": null,
+            "units": 1,
+            "created_at": [2024, 1, 0, 0, 0, 0, 0, 0, 0],
+            "updated_at": [2024, 1, 0, 0, 0, 0, 0, 0, 0]
+        }))
+        .unwrap();
+        assert_eq!(booking.review_deadline(14).ordinal(), 77);
+
+        let mut review = CreateReviewRequest {
+            overall_rating: 5,
+            cleanliness_rating: 4,
+            accuracy_rating: 5,
+            location_rating: 3,
+            value_rating: 1,
+            comment: Some("Great stay".to_string()),
+        };
+        assert!(review.validate().is_ok());
+
+        review.value_rating = 0;
+        assert!(review.validate().is_err());
+        review.value_rating = 6;
+        assert!(review.validate().is_err());
+    }
+
+    #[test]
+    fn test_stay_dates_query() {
+        use super::models::StayDatesQuery;
+        use actix_web::web::Query;
+        use validator::Validate;
+
+        let query = Query::<StayDatesQuery>::from_query("check_in_date=2024-03-01&check_out_date=2024-03-04").unwrap();
+        assert!(query.validate().is_ok());
+        let (check_in, check_out) = query.dates().unwrap();
+        assert_eq!((check_out - check_in).whole_days(), 3);
+
+        let query = Query::<StayDatesQuery>::from_query("").unwrap();
+        assert!(query.validate().is_ok());
+        assert!(query.dates().is_none());
+
+        let query = Query::<StayDatesQuery>::from_query("check_in_date=2024-03-01").unwrap();
+        assert!(query.validate().is_err());
+        let query = Query::<StayDatesQuery>::from_query("check_in_date=2024-03-04&check_out_date=2024-03-01").unwrap();
+        assert!(query.validate().is_err());
+        assert!(Query::<StayDatesQuery>::from_query("check_in_date=03/01/2024").is_err());
+    }
+
+    #[test]
+    fn test_ttl_cache_expiry() {
+        let cache: TtlCache<String, i32> = TtlCache::new(Duration::from_millis(20), 10);
+        cache.insert("paris".to_string(), 3);
+        assert_eq!(cache.get(&"paris".to_string()), Some(3));
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(cache.get(&"paris".to_string()), None);
+    }
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("Paris"), "Paris");
+        assert_eq!(escape_like("100%_off\\"), "100\\%\\_off\\\\");
+    }
+
+    #[test]
+    fn test_amenity_filter_parsing() {
+        let filters: PropertyFilters =
+            serde_json::from_str(r#"{"amenities": " WiFi, parking,,"}"#).unwrap();
+        assert_eq!(filters.amenity_keys(), vec!["wifi", "parking"]);
+
+        assert!(validate_amenity_key("air_conditioning").is_ok());
+        assert!(validate_amenity_key("Wi-Fi").is_err());
+    }
+
+    #[test]
+    fn test_property_attributes() {
+        use super::models::PropertyAttributes;
+        use validator::Validate;
+
+        let attributes: PropertyAttributes =
+            serde_json::from_value(serde_json::json!({"floor": 3, "has_elevator": true})).unwrap();
+        assert!(attributes.keys_not_allowed_for(&PropertyType::Apartment).is_empty());
+        assert_eq!(attributes.keys_not_allowed_for(&PropertyType::Cabin), vec!["floor", "has_elevator"]);
+        assert!(serde_json::from_value::<PropertyAttributes>(serde_json::json!({"moat": true})).is_err());
+
+        let hotel: PropertyAttributes = serde_json::from_value(serde_json::json!({"star_rating": 6})).unwrap();
+        assert!(hotel.validate().is_err());
+
+        assert_eq!(serde_json::to_string(&PropertyType::RoomInHome).unwrap(), "\"RoomInHome\"");
+        assert_eq!(PropertyType::parse(PropertyType::RoomInHome.as_str()), Some(PropertyType::RoomInHome));
+
+        let filters: PropertyFilters =
+            serde_json::from_value(serde_json::json!({"attributes": "has_elevator:true, floor:3"})).unwrap();
+        assert!(filters.validate().is_ok());
+        assert_eq!(filters.attribute_filter(), Some(attributes));
+
+        let filters: PropertyFilters = serde_json::from_value(serde_json::json!({"attributes": "floor:high"})).unwrap();
+        assert!(filters.validate().is_err());
+    }
+
+    #[test]
+    fn test_timezone_lookup() {
+        use super::timezone;
+        use time::macros::{date, time};
+
+        assert_eq!(timezone::lookup(48.85, 2.35), "Europe/Paris");
+        assert_eq!(timezone::lookup(40.71, -74.0), "America/New_York");
+        assert_eq!(timezone::lookup(-33.87, 151.21), "Australia/Sydney");
+        // Middle of the Pacific, far from any zone's principal location
+        assert_eq!(timezone::lookup(-45.0, -120.0), "Etc/GMT+8");
+        assert_eq!(timezone::canonical_name("Etc/GMT+8"), Some("Etc/GMT+8"));
+
+        assert_eq!(timezone::canonical_name("Asia/Tokyo"), Some("Asia/Tokyo"));
+        assert_eq!(timezone::canonical_name("Mars/Olympus_Mons"), None);
+        assert_eq!(
+            timezone::resolve(Some("Asia/Tokyo"), Some(48.85), Some(2.35)).as_deref(),
+            Some("Asia/Tokyo")
+        );
+        assert_eq!(timezone::resolve(None, None, None), None);
+
+        // 11:00 in New York is 15:00 UTC in summer and 16:00 UTC in winter
+        let summer = timezone::local_datetime("America/New_York", date!(2025 - 07 - 01), time!(11:00));
+        assert_eq!(summer.to_offset(time::UtcOffset::UTC).time(), time!(15:00));
+        let winter = timezone::local_datetime("America/New_York", date!(2025 - 01 - 15), time!(11:00));
+        assert_eq!(winter.to_offset(time::UtcOffset::UTC).time(), time!(16:00));
+    }
+
+    #[test]
+    fn test_jwt_key_rotation() {
+        use super::{auth::JwtKeys, config::Config};
+        use ring::{rand::SystemRandom, signature::Ed25519KeyPair};
+
+        let user_id = uuid::Uuid::new_v4();
+        let old = Config {
+            jwt_secret: "old-secret".to_string(),
+            jwt_kid: "2025-01".to_string(),
+            ..Config::default()
+        };
+        let old_keys = JwtKeys::from_config(&old).unwrap();
+        let old_token = old_keys.create_jwt(user_id, UserRole::User, None).unwrap();
+        // Shared secrets are never published
+        assert!(old_keys.jwks().keys.is_empty());
+
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pem_path = std::env::temp_dir().join(format!("jwt-{}.pem", user_id));
+        std::fs::write(&pem_path, pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref()))).unwrap();
+
+        // Sign with the new key, keep accepting the old one
+        let rotated = Config {
+            jwt_signing_kid: Some("2025-06".to_string()),
+            jwt_private_keys: vec![("2025-06".to_string(), pem_path.display().to_string())],
+            ..old.clone()
+        };
+        let keys = JwtKeys::from_config(&rotated).unwrap();
+        std::fs::remove_file(&pem_path).unwrap();
+
+        assert_eq!(keys.verify_jwt(&old_token).unwrap().sub, user_id.to_string());
+        let new_token = keys.create_jwt(user_id, UserRole::Admin, None).unwrap();
+        let header = jsonwebtoken::decode_header(&new_token).unwrap();
+        assert_eq!(header.alg, jsonwebtoken::Algorithm::EdDSA);
+        assert_eq!(header.kid.as_deref(), Some("2025-06"));
+        assert_eq!(keys.verify_jwt(&new_token).unwrap().role, UserRole::Admin);
+        // Servers that have not loaded the new key yet reject its tokens
+        assert!(old_keys.verify_jwt(&new_token).is_err());
+
+        let jwks = serde_json::to_value(keys.jwks()).unwrap();
+        assert_eq!(jwks["keys"].as_array().unwrap().len(), 1);
+        assert_eq!(jwks["keys"][0]["kid"], "2025-06");
+        assert_eq!(jwks["keys"][0]["kty"], "OKP");
+        assert_eq!(jwks["keys"][0]["crv"], "Ed25519");
+
+        // Once retired, tokens signed with the old key stop working
+        let retired = Config {
+            jwt_secret: "new-secret".to_string(),
+            jwt_kid: "2025-12".to_string(),
+            ..Config::default()
+        };
+        assert!(JwtKeys::from_config(&retired).unwrap().verify_jwt(&old_token).is_err());
+        let missing_kid = Config { jwt_signing_kid: Some("nope".to_string()), ..Config::default() };
+        assert!(JwtKeys::from_config(&missing_kid).is_err());
+    }
+
+    #[test]
+    fn test_house_rules() {
+        use super::models::HouseRules;
+        use validator::Validate;
+
+        let defaults = serde_json::to_value(HouseRules::default()).unwrap();
+        assert_eq!(defaults["check_in_from"], "15:00");
+        assert_eq!(defaults["check_out_until"], "11:00");
+
+        let mut rules: HouseRules = serde_json::from_value(serde_json::json!({
+            "check_in_from": "14:00",
+            "check_in_until": "01:00",
+            "check_out_until": "10:30",
+            "pets_allowed": true,
+            "smoking_allowed": false,
+            "events_allowed": false,
+            "children_allowed": true,
+            "quiet_hours_start": "22:00",
+            "quiet_hours_end": "07:00",
+            "additional_rules": "Please take your shoes off inside."
+        }))
+        .unwrap();
+        assert!(rules.validate().is_ok());
+
+        // Any change to the rules gives them a new version
+        let version = rules.version();
+        assert_eq!(rules.clone().version(), version);
+        rules.quiet_hours_end = None;
+        assert!(rules.validate().is_err());
+        assert_ne!(rules.version(), version);
+
+        let filters: PropertyFilters = serde_json::from_value(serde_json::json!({"pets_allowed": true})).unwrap();
+        assert_eq!(filters.pets_allowed, Some(true));
+    }
+
+    #[test]
+    fn test_listing_cursor_round_trip() {
+        let cursor = ListingCursor {
+            sort: PropertySort::PriceAsc,
+            key: CursorKey::Price(15000),
+            id: uuid::Uuid::new_v4(),
+        };
+        assert_eq!(ListingCursor::decode(&cursor.encode()), Some(cursor));
+        assert_eq!(ListingCursor::decode("not-a-cursor"), None);
+
+        let filters: PropertyFilters =
+            serde_json::from_str(r#"{"sort": "price_desc", "per_page": 5000}"#).unwrap();
+        assert_eq!(filters.sort(), PropertySort::PriceDesc);
+        assert_eq!(filters.per_page(), MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_process_photo_generates_thumbnails() {
+        use super::images::process_photo;
+        use super::models::PhotoVariant;
+
+        let mut png = Vec::new();
+        image::RgbImage::new(1000, 500)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        assert!(process_photo(png.clone(), "image/jpeg").is_err());
+        assert!(process_photo(png.clone(), "text/plain").is_err());
+
+        let processed = process_photo(png, "image/png").unwrap();
+        assert_eq!((processed.width, processed.height), (1000, 500));
+        assert_eq!(processed.thumbnails.len(), 3);
+
+        let (variant, small) = &processed.thumbnails[0];
+        assert_eq!(*variant, PhotoVariant::Small);
+        let small = image::load_from_memory(small).unwrap();
+        assert_eq!((small.width(), small.height()), (320, 160));
+
+        // Metadata such as the GPS position is not kept in the stored original
+        let jpeg = jpeg_with_gps_exif();
+        let processed = process_photo(jpeg, "image/jpeg").unwrap();
+        assert!(!contains(&processed.original, b"Exif"));
+        assert_eq!(processed.content_type, "image/jpeg");
+        assert!(image::load_from_memory(&processed.original).is_ok());
+    }
+
+    // A small JPEG carrying an EXIF block with a GPS position, as phone cameras write them
+    fn jpeg_with_gps_exif() -> Vec<u8> {
+        let mut jpeg = Vec::new();
+        image::RgbImage::new(40, 30)
+            .write_with_encoder(image::codecs::jpeg::JpegEncoder::new(&mut jpeg))
+            .unwrap();
+
+        // Big-endian TIFF: IFD0 points to a GPS IFD holding latitude "N 52° 22' 12.34\""
+        let mut tiff = b"MM\0\x2a\0\0\0\x08".to_vec();
+        tiff.extend([0, 1, 0x88, 0x25, 0, 4, 0, 0, 0, 1, 0, 0, 0, 26, 0, 0, 0, 0]);
+        tiff.extend([0, 2, 0, 1, 0, 2, 0, 0, 0, 2, b'N', 0, 0, 0]);
+        tiff.extend([0, 2, 0, 5, 0, 0, 0, 3, 0, 0, 0, 56, 0, 0, 0, 0]);
+        for (numerator, denominator) in [(52u32, 1u32), (22, 1), (1234, 100)] {
+            tiff.extend(numerator.to_be_bytes());
+            tiff.extend(denominator.to_be_bytes());
+        }
+        let mut app1 = vec![0xff, 0xe1];
+        app1.extend((2 + 6 + tiff.len() as u16).to_be_bytes());
+        app1.extend(b"Exif\0\0");
+        app1.extend(tiff);
+
+        // Right after the start-of-image marker
+        jpeg.splice(2..2, app1);
+        assert!(image::load_from_memory(&jpeg).is_ok() && contains(&jpeg, b"Exif"));
+        jpeg
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|window| window == needle)
+    }
+
+    #[test]
+    fn test_listing_checklist() {
+        use super::models::{HouseRules, ListingChecklist, ListingStatus, Property};
+
+        let mut property: Property = serde_json::from_value(serde_json::json!({
+            "id": uuid::Uuid::new_v4(),
+            "owner_id": uuid::Uuid::new_v4(),
+            "title": "Sunny loft near the canal",
+            "description": "Short",
+            "property_type": "Apartment",
+            "location": "Amsterdam, Netherlands",
+            "address": "1 Canal Street",
+            "city": "Amsterdam",
+            "country": "Netherlands",
+            "postal_code": "1000",
+            "latitude": 52.37,
+            "longitude": 4.89,
+            "price_per_night": 12000,
+            "max_guests": 2,
+            "bedrooms": 1,
+            "bathrooms": 1,
+            "amenities": ["wifi"],
+            "attributes": {},
+            "house_rules": HouseRules::default(),
+            "timezone": "Europe/Amsterdam",
+            "average_rating": null,
+            "review_count": 0,
+            "is_active": true,
+            "status": ListingStatus::Draft,
+            "rejection_reason": null,
+            "submitted_at": null,
+            "reviewed_at": null,
+            "reviewed_by": null,
+            "created_at": [2024, 1, 0, 0, 0, 0, 0, 0, 0],
+            "updated_at": [2024, 1, 0, 0, 0, 0, 0, 0, 0]
+        }))
+        .unwrap();
+
+        let checklist = ListingChecklist::for_property(&property, 3);
+        assert!(!checklist.complete);
+        let missing: Vec<&str> = checklist.items.iter().filter(|i| !i.complete).map(|i| i.key).collect();
+        assert_eq!(missing, vec!["description"]);
+
+        property.description = "A bright open-plan loft with canal views, a full kitchen and fast wifi.".to_string();
+        assert!(ListingChecklist::for_property(&property, 3).complete);
+        assert!(!ListingChecklist::for_property(&property, 2).complete);
+    }
+
+    // Counts every statement sqlx executes while the returned guard is alive
+    struct QueryCounter(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for QueryCounter {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if event.metadata().target() == "sqlx::query" {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+    }
+
+    async fn count_queries<F: std::future::Future>(future: F) -> (F::Output, usize) {
+        use tracing_subscriber::{filter::Targets, layer::SubscriberExt, Layer};
+
+        let count = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let subscriber = tracing_subscriber::registry().with(
+            QueryCounter(count.clone())
+                .with_filter(Targets::new().with_target("sqlx::query", tracing::Level::TRACE)),
+        );
+
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let output = future.await;
+        (output, count.load(std::sync::atomic::Ordering::SeqCst))
+    }
+
+    // A test listing; fields in `overrides` replace the defaults
+    fn property_request(overrides: serde_json::Value) -> super::models::CreatePropertyRequest {
+        let mut request = serde_json::json!({
+            "title": "Test listing",
+            "description": "Test listing",
+            "property_type": "Apartment",
+            "location": "Testville, Testland",
+            "address": "1 Test Street",
+            "city": "Testville",
+            "country": "Testland",
+            "postal_code": "00000",
+            "price_per_night": 1000,
+            "max_guests": 2,
+            "bedrooms": 1,
+            "bathrooms": 1,
+            "amenities": []
+        });
+        if let (Some(request), serde_json::Value::Object(overrides)) = (request.as_object_mut(), overrides) {
+            request.extend(overrides);
+        }
+        serde_json::from_value(request).unwrap()
+    }
+
+    async fn publish_property(
+        pool: &sqlx::PgPool,
+        host_id: uuid::Uuid,
+        overrides: serde_json::Value,
+    ) -> super::models::Property {
+        use super::{db, models::ListingStatus};
+
+        let property = db::create_property_db(pool, host_id, &property_request(overrides)).await.unwrap();
+        db::submit_property_for_review(pool, property.id).await.unwrap();
+        db::review_property(pool, property.id, host_id, ListingStatus::Published, None)
+            .await
+            .unwrap()
+    }
+
+    // A guest, a host and one of the host's listings, already published
+    async fn seed_published_property(
+        pool: &sqlx::PgPool,
+        overrides: serde_json::Value,
+    ) -> (super::models::User, super::models::User, super::models::Property) {
+        use super::{db, models::UserRole};
+
+        let run_id = uuid::Uuid::new_v4();
+        let guest = db::create_user(pool, &format!("guest-{run_id}@example.com"), "hash", "Guest", "User", UserRole::User)
+            .await
+            .unwrap();
+        let host = db::create_user(pool, &format!("host-{run_id}@example.com"), "hash", "Host", "User", UserRole::PropertyOwner)
+            .await
+            .unwrap();
+        let property = publish_property(pool, host.id, overrides).await;
+        (guest, host, property)
+    }
+
+    // Removes test users; their listings, bookings and sessions go with them
+    async fn delete_users(pool: &sqlx::PgPool, user_ids: &[uuid::Uuid]) {
+        sqlx::query("DELETE FROM users WHERE id = ANY($1)")
+            .bind(user_ids)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    // Connects to the test database; None when DATABASE_URL is not set
+    async fn test_pool() -> Option<sqlx::PgPool> {
+        let database_url = std::env::var("DATABASE_URL").ok()?;
+        Some(sqlx::PgPool::connect(&database_url).await.unwrap())
+    }
+
+    // The API with the app data every route needs; `extra` adds whatever else a
+    // test's routes use (mailer, blob store, OIDC client)
+    async fn test_app(
+        pool: &sqlx::PgPool,
+        config: super::config::Config,
+        extra: impl FnOnce(&mut actix_web::web::ServiceConfig),
+    ) -> impl actix_web::dev::Service<actix_http::Request, Response = actix_web::dev::ServiceResponse, Error = actix_web::Error> {
+        use actix_web::{test, web, App};
+
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(super::auth::JwtKeys::from_config(&config).unwrap()))
+                .app_data(web::Data::new(config))
+                .configure(extra)
+                .configure(super::routes::configure_routes),
+        )
+        .await
+    }
+
+    // A request carrying `token` as its bearer credential, if there is one
+    fn authed_request(
+        method: actix_web::http::Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<serde_json::Value>,
+    ) -> actix_http::Request {
+        use actix_web::{http::header, test};
+
+        let mut req = test::TestRequest::default().method(method).uri(uri);
+        if let Some(token) = token {
+            req = req.insert_header((header::AUTHORIZATION, format!("Bearer {token}")));
+        }
+        if let Some(body) = body {
+            req = req.set_json(body);
+        }
+        req.to_request()
+    }
+
+    // Needs a migrated database; skipped when DATABASE_URL is not set
+    #[actix_web::test]
+    async fn test_list_endpoints_use_constant_query_count() {
+        use super::{auth::AuthUser, db, handlers, models::*};
+        use actix_web::web;
+
+        let Ok(database_url) = std::env::var("DATABASE_URL") else {
+            return;
+        };
+        // A single connection keeps sqlx's one-off type introspection queries out of the counts
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(1)
+            .connect(&database_url)
+            .await
+            .unwrap();
+
+        let run_id = uuid::Uuid::new_v4();
+        let guest = db::create_user(&pool, &format!("guest-{run_id}@example.com"), "hash", "Guest", "User", UserRole::User)
+            .await
+            .unwrap();
+        let mut user_ids = vec![guest.id];
+
+        // Every property gets its own owner so per-row owner lookups would show up
+        for i in 0..6 {
+            let owner = db::create_user(&pool, &format!("owner-{i}-{run_id}@example.com"), "hash", "Owner", "User", UserRole::PropertyOwner)
+                .await
+                .unwrap();
+            user_ids.push(owner.id);
+
+            let property = publish_property(&pool, owner.id, serde_json::json!({ "title": format!("Query count {i}") })).await;
+
+            let today = time::OffsetDateTime::now_utc().date();
+            db::create_booking_db(&pool, property.id, guest.id, today, today.next_day().unwrap(), 1000, 1, None, None)
+                .await
+                .unwrap();
+        }
+
+        let filters = |per_page: i64| {
+            serde_json::from_value::<PropertyFilters>(serde_json::json!({ "per_page": per_page })).unwrap()
+        };
+        let guest_user = || AuthUser {
+            id: guest.id,
+            email: guest.email.clone(),
+            role: UserRole::User,
+            session_id: None,
+            email_verified: true,
+            two_factor_enabled: false,
+            api_key_id: None,
+        };
+
+        // Warm up the connection before counting
+        handlers::get_properties(web::Data::new(pool.clone()), web::Query(filters(1))).await.unwrap();
+        handlers::get_my_bookings(web::Data::new(pool.clone()), guest_user()).await.unwrap();
+
+        let (small_page, small_count) = count_queries(handlers::get_properties(
+            web::Data::new(pool.clone()),
+            web::Query(filters(1)),
+        ))
+        .await;
+        let (large_page, large_count) = count_queries(handlers::get_properties(
+            web::Data::new(pool.clone()),
+            web::Query(filters(6)),
+        ))
+        .await;
+        assert!(small_page.unwrap().status().is_success());
+        assert!(large_page.unwrap().status().is_success());
+        assert_eq!(small_count, large_count);
+        // listing page, total count, and one batched lookup each for owners and photos
+        assert_eq!(large_count, 4);
+
+        let (bookings, bookings_count) =
+            count_queries(handlers::get_my_bookings(web::Data::new(pool.clone()), guest_user())).await;
+        assert!(bookings.unwrap().status().is_success());
+        // bookings, their properties, owners plus guest in one batch, and photos
+        assert_eq!(bookings_count, 4);
+
+        // A cursor followed without `sort` keeps the order it was issued for
+        let first_page = handlers::get_properties(
+            web::Data::new(pool.clone()),
+            web::Query(serde_json::from_value(serde_json::json!({ "pagination": "cursor", "sort": "price_desc", "per_page": 1 })).unwrap()),
+        )
+        .await
+        .unwrap();
+        let body = actix_web::body::to_bytes(first_page.into_body()).await.unwrap();
+        let next_cursor = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["next_cursor"].clone();
+        let next_page = handlers::get_properties(
+            web::Data::new(pool.clone()),
+            web::Query(serde_json::from_value(serde_json::json!({ "cursor": next_cursor, "per_page": 1 })).unwrap()),
+        )
+        .await;
+        assert!(next_page.unwrap().status().is_success());
+
+        delete_users(&pool, &user_ids).await;
+    }
+
+    #[actix_web::test]
+    async fn test_reviews_stay_hidden_until_both_sides_submit() {
+        use super::{db, models::*};
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let (guest, host, property) = seed_published_property(&pool, serde_json::json!({ "title": "Blind review test" })).await;
+
+        let today = time::OffsetDateTime::now_utc().date();
+        let booking = db::create_booking_db(&pool, property.id, guest.id, today - time::Duration::days(3), today - time::Duration::days(1), 2000, 1, None, None)
+            .await
+            .unwrap();
+        db::update_booking_status(&pool, booking.id, BookingStatus::Approved).await.unwrap();
+
+        // Check-out was yesterday at 11:00 property time, so the sweep completes the stay
+        db::complete_finished_bookings(&pool).await.unwrap();
+        let booking = db::get_booking_by_id(&pool, booking.id).await.unwrap().unwrap();
+        assert_eq!(booking.status, BookingStatus::Completed);
+
+        let review = db::create_review_db(&pool, &booking, &CreateReviewRequest {
+            overall_rating: 4,
+            cleanliness_rating: 4,
+            accuracy_rating: 5,
+            location_rating: 3,
+            value_rating: 4,
+            comment: None,
+        })
+        .await
+        .unwrap();
+        assert!(review.published_at.is_none());
+        let (visible, total) = db::get_reviews_by_property(&pool, property.id, 1, 10).await.unwrap();
+        assert!(visible.is_empty());
+        assert_eq!(total, 0);
+        assert_eq!(db::get_property_by_id(&pool, property.id).await.unwrap().unwrap().review_count, 0);
+
+        let guest_review = db::create_guest_review_db(&pool, &booking, host.id, &CreateGuestReviewRequest {
+            overall_rating: 5,
+            cleanliness_rating: 5,
+            communication_rating: 4,
+            house_rules_rating: 5,
+            comment: Some("Great guest".to_string()),
+        })
+        .await
+        .unwrap();
+        assert!(guest_review.published_at.is_some());
+
+        let (visible, _) = db::get_reviews_by_property(&pool, property.id, 1, 10).await.unwrap();
+        assert_eq!(visible.len(), 1);
+        let property = db::get_property_by_id(&pool, property.id).await.unwrap().unwrap();
+        assert_eq!(property.review_count, 1);
+        assert_eq!(property.average_rating, Some(4.0));
+
+        let summaries = db::get_guest_rating_summaries(&pool, &[guest.id]).await.unwrap();
+        assert_eq!(summaries[&guest.id].review_count, 1);
+        assert_eq!(summaries[&guest.id].average_rating, Some(5.0));
+
+        assert!(db::add_host_reply(&pool, review.id, "Thanks!").await.unwrap().is_some());
+        assert!(db::add_host_reply(&pool, review.id, "Again").await.unwrap().is_none());
+
+        delete_users(&pool, &[guest.id, host.id]).await;
+    }
+
+    #[actix_web::test]
+    async fn test_saved_search_alerts_for_new_listings_and_price_drops() {
+        use super::{alerts, db, models::*};
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let city = format!("Alertville {}", uuid::Uuid::new_v4().simple());
+        let (guest, host, existing) = seed_published_property(&pool, serde_json::json!({
+            "title": "Existing flat",
+            "city": city,
+            "price_per_night": 10000
+        }))
+        .await;
+        let moved = publish_property(&pool, host.id, serde_json::json!({ "title": "Moved flat", "city": "Elsewhere" })).await;
+
+        let filters: PropertyFilters = serde_json::from_value(serde_json::json!({ "location": city })).unwrap();
+        let search = db::create_saved_search(&pool, guest.id, "Alertville", &filters, true).await.unwrap();
+        alerts::seed_saved_search(&pool, &search).await.unwrap();
+        let wishlist = db::create_wishlist(&pool, guest.id, "Favourites").await.unwrap();
+        db::add_wishlist_item(&pool, wishlist.id, existing.id).await.unwrap();
+
+        // Nothing changed since the search was saved
+        alerts::run_saved_search_alerts(&pool).await.unwrap();
+        alerts::run_wishlist_price_alerts(&pool).await.unwrap();
+        let (notifications, _, _) = db::get_notifications_by_user(&pool, guest.id, false, 1, 10).await.unwrap();
+        assert!(notifications.is_empty());
+
+        // A listing published before the last check that only now matches is not new
+        sqlx::query("UPDATE properties SET city = $2 WHERE id = $1")
+            .bind(moved.id)
+            .bind(&city)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let fresh = publish_property(&pool, host.id, serde_json::json!({
+            "title": "Fresh flat",
+            "city": city,
+            "price_per_night": 12000
+        }))
+        .await;
+        sqlx::query("UPDATE properties SET price_per_night = 8000 WHERE id = $1")
+            .bind(existing.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        alerts::run_saved_search_alerts(&pool).await.unwrap();
+        alerts::run_wishlist_price_alerts(&pool).await.unwrap();
+        let (notifications, total, unread) = db::get_notifications_by_user(&pool, guest.id, false, 1, 10).await.unwrap();
+        assert_eq!((total, unread), (3, 3));
+
+        let new_listing = notifications.iter().find(|n| n.kind == NotificationKind::NewListing).unwrap();
+        assert_eq!(new_listing.data["property_ids"], serde_json::json!([fresh.id]));
+        let (search_drops, wishlist_drops): (Vec<_>, Vec<_>) = notifications
+            .iter()
+            .filter(|n| n.kind == NotificationKind::PriceDrop)
+            .partition(|n| !n.data["saved_search_id"].is_null());
+        for price_drop in [search_drops[0], wishlist_drops[0]] {
+            assert_eq!(price_drop.data["property_id"], serde_json::json!(existing.id));
+            assert_eq!(price_drop.data["previous_price"], 10000);
+            assert_eq!(price_drop.data["price"], 8000);
+        }
+
+        // Alerts are not repeated
+        alerts::run_saved_search_alerts(&pool).await.unwrap();
+        alerts::run_wishlist_price_alerts(&pool).await.unwrap();
+        assert_eq!(db::mark_all_notifications_read(&pool, guest.id).await.unwrap(), 3);
+
+        delete_users(&pool, &[guest.id, host.id]).await;
+    }
+
+    #[actix_web::test]
+    async fn test_room_type_inventory_and_unit_bookings() {
+        use super::{auth::{AuthUser, RequireOwner}, db, handlers, models::*};
+        use actix_web::web;
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let (guest, host, property) = seed_published_property(&pool, serde_json::json!({
+            "title": "Inventory hostel",
+            "property_type": "Hostel",
+            "max_guests": 20,
+            "bedrooms": 4,
+            "bathrooms": 2
+        })).await;
+        let host_user = || RequireOwner(AuthUser {
+            id: host.id,
+            email: host.email.clone(),
+            role: UserRole::PropertyOwner,
+            session_id: None,
+            email_verified: true,
+            two_factor_enabled: false,
+            api_key_id: None,
+        });
+
+        let dorm_request = || -> CreateRoomTypeRequest {
+            serde_json::from_value(serde_json::json!({
+                "name": "Bed in 4-bed dorm",
+                "kind": "DormBed",
+                "max_guests": 1,
+                "price_per_night": 2500,
+                "total_units": 4
+            }))
+            .unwrap()
+        };
+        let day = |offset: i64| time::OffsetDateTime::now_utc().date() + time::Duration::days(offset);
+
+        // Room types would hide open bookings of the whole hostel from availability
+        let whole = db::create_booking_db(&pool, property.id, guest.id, day(20), day(22), 2000, 1, None, None)
+            .await
+            .unwrap();
+        let added = handlers::create_room_type_handler(web::Data::new(pool.clone()), host_user(), web::Path::from(property.id), web::Json(dorm_request())).await;
+        assert!(added.is_err());
+        db::update_booking_status(&pool, whole.id, BookingStatus::Cancelled).await.unwrap();
+
+        let dorm = db::create_room_type(&pool, property.id, &dorm_request(), UnitKind::DormBed).await.unwrap();
+
+        // Night 11 sells only 3 beds at a higher price
+        db::set_room_type_inventory(&pool, dorm.id, &SetInventoryRequest {
+            start_date: day(11),
+            end_date: day(11),
+            units_available: Some(3),
+            price_override: Some(4000),
+        })
+        .await
+        .unwrap();
+
+        let availability = db::get_room_type_availability(&pool, property.id, day(10), day(13)).await.unwrap();
+        assert_eq!(availability[&dorm.id].available_units, 3);
+        assert_eq!(availability[&dorm.id].price_per_unit, 2500 + 4000 + 2500);
+
+        let rules = HouseRules::default();
+        let booking = db::create_room_booking(&pool, &dorm, guest.id, day(10), day(12), 2, 2, None, Some(&rules)).await.unwrap();
+        assert_eq!(booking.units, 2);
+        assert_eq!(booking.accepted_house_rules, Some(rules));
+        assert!(booking.house_rules_accepted_at.is_some());
+        assert_eq!(booking.total_price, 2 * (2500 + 4000));
+
+        // One bed left on night 11, two on night 10
+        assert!(db::create_room_booking(&pool, &dorm, guest.id, day(11), day(12), 2, 2, None, None).await.is_err());
+        db::create_room_booking(&pool, &dorm, guest.id, day(11), day(12), 1, 1, None, None).await.unwrap();
+
+        assert!(!db::check_property_availability(&pool, property.id, day(11), day(12)).await.unwrap());
+        assert!(db::check_property_availability(&pool, property.id, day(10), day(11)).await.unwrap());
+
+        let calendar = db::get_room_type_inventory(&pool, dorm.id, day(10), day(13)).await.unwrap();
+        let booked: Vec<(i32, i32)> = calendar.iter().map(|d| (d.booked_units, d.available_units)).collect();
+        assert_eq!(booked, vec![(2, 2), (3, 0), (0, 4)]);
+
+        // Capacity can't be cut below what is already booked
+        let shrink: UpdateRoomTypeRequest = serde_json::from_value(serde_json::json!({ "total_units": 2 })).unwrap();
+        let path = || web::Path::from((property.id, dorm.id));
+        assert!(handlers::update_room_type_handler(web::Data::new(pool.clone()), host_user(), path(), web::Json(shrink)).await.is_err());
+        let close_night = |units: i32| SetInventoryRequest {
+            start_date: day(11),
+            end_date: day(11),
+            units_available: Some(units),
+            price_override: None,
+        };
+        assert!(handlers::set_room_type_inventory_handler(web::Data::new(pool.clone()), host_user(), path(), web::Json(close_night(2))).await.is_err());
+        handlers::set_room_type_inventory_handler(web::Data::new(pool.clone()), host_user(), path(), web::Json(close_night(3))).await.unwrap();
+
+        delete_users(&pool, &[guest.id, host.id]).await;
+    }
+
+    #[actix_web::test]
+    async fn test_exact_address_hidden_until_booking_approved() {
+        use super::{auth, config::Config, db, models::*};
+        use actix_web::{http::Method, http::StatusCode, test};
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let (guest, host, property) = seed_published_property(&pool, serde_json::json!({
+            "title": "Private address test",
+            "location": "Paris, France",
+            "address": "12 Rue Secrète",
+            "city": "Paris",
+            "country": "France",
+            "postal_code": "75001",
+            "latitude": 48.8566,
+            "longitude": 2.3522
+        })).await;
+
+        let config = Config { require_verified_email_for_booking: false, ..Config::default() };
+        let guest_token = auth::JwtKeys::from_config(&config).unwrap().create_jwt(guest.id, UserRole::User, None).unwrap();
+        let app = test_app(&pool, config, |_| {}).await;
+        let view = |token: Option<&str>| {
+            let req = authed_request(Method::GET, &format!("/api/v1/properties/{}", property.id), token, None);
+            test::call_and_read_body_json::<_, _, serde_json::Value>(&app, req)
+        };
+
+        let anonymous = view(None).await;
+        assert!(anonymous["address"].is_null());
+        assert_eq!(anonymous["exact_location"], false);
+        assert_eq!(anonymous["city"], "Paris");
+        assert!(anonymous["owner"].get("email").is_none());
+        let (lat, lng) = (anonymous["latitude"].as_f64().unwrap(), anonymous["longitude"].as_f64().unwrap());
+        let offset_m = ((lat - 48.8566) * 111_320.0).hypot((lng - 2.3522) * 111_320.0 * 48.8566_f64.to_radians().cos());
+        assert!((150.0..850.0).contains(&offset_m), "fuzzed by {offset_m} m");
+        // The same approximate location every time
+        assert_eq!(view(None).await["latitude"], anonymous["latitude"]);
+
+        let today = time::OffsetDateTime::now_utc().date();
+        let book = |house_rules_version: &serde_json::Value| {
+            let body = serde_json::json!({
+                "property_id": property.id,
+                "check_in_date": (today + time::Duration::days(5)).to_string(),
+                "check_out_date": (today + time::Duration::days(7)).to_string(),
+                "guest_count": 1,
+                "house_rules_version": house_rules_version,
+            });
+            test::call_service(&app, authed_request(Method::POST, "/api/v1/bookings", Some(&guest_token), Some(body)))
+        };
+
+        // Acceptance only counts for the rules the guest was shown
+        let shown_rules = anonymous["house_rules_version"].clone();
+        db::update_house_rules(&pool, property.id, &HouseRules { pets_allowed: true, ..HouseRules::default() })
+            .await
+            .unwrap();
+        assert_eq!(book(&shown_rules).await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(book(&serde_json::Value::Null).await.status(), StatusCode::BAD_REQUEST);
+        let booked = book(&view(None).await["house_rules_version"]).await;
+        assert_eq!(booked.status(), StatusCode::CREATED);
+        let booking: serde_json::Value = test::read_body_json(booked).await;
+        let booking_id: uuid::Uuid = serde_json::from_value(booking["id"].clone()).unwrap();
+        assert!(view(Some(&guest_token)).await["address"].is_null());
+
+        db::update_booking_status(&pool, booking_id, BookingStatus::Approved).await.unwrap();
+        let confirmed = view(Some(&guest_token)).await;
+        assert_eq!(confirmed["address"], "12 Rue Secrète");
+        assert_eq!(confirmed["latitude"], 48.8566);
+        assert_eq!(confirmed["exact_location"], true);
+        assert_eq!(confirmed["owner"]["email"], host.email.as_str());
+
+        // The guest's booking list carries the same details
+        let req = authed_request(Method::GET, "/api/v1/bookings", Some(&guest_token), None);
+        let bookings: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(bookings[0]["property"]["address"], "12 Rue Secrète");
+        assert_eq!(bookings[0]["property"]["owner"]["email"], host.email.as_str());
+
+        delete_users(&pool, &[guest.id, host.id]).await;
+    }
+
+    #[actix_web::test]
+    async fn test_photos_of_unpublished_listings_are_private() {
+        use super::{auth, config::Config, db, images::MAX_PHOTOS_PER_PROPERTY, models::*, storage::{BlobStore, LocalBlobStore}};
+        use actix_web::{http::header, http::Method, http::StatusCode, test, web};
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let (guest, host, published) = seed_published_property(&pool, serde_json::json!({})).await;
+        let draft = db::create_property_db(&pool, host.id, &property_request(serde_json::json!({ "title": "Draft" })))
+            .await
+            .unwrap();
+
+        let upload_dir = std::env::temp_dir().join(format!("photos-{}", host.id));
+        let store = LocalBlobStore::new(&upload_dir);
+        let mut photo_ids = Vec::new();
+        for property in [&published, &draft] {
+            let prefix = format!("properties/{}/{}", property.id, uuid::Uuid::new_v4());
+            store.put(&format!("{prefix}/original"), b"image".to_vec()).await.unwrap();
+            let photo = db::create_photo(&pool, uuid::Uuid::new_v4(), property.id, &prefix, "image/png", 1, 1, 5, None)
+                .await
+                .unwrap();
+            photo_ids.push(photo.id);
+        }
+
+        let keys = auth::JwtKeys::from_config(&Config::default()).unwrap();
+        let guest_token = keys.create_jwt(guest.id, UserRole::User, None).unwrap();
+        let host_token = keys.create_jwt(host.id, UserRole::PropertyOwner, None).unwrap();
+        let store: std::sync::Arc<dyn BlobStore> = std::sync::Arc::new(store);
+        let app = test_app(&pool, Config::default(), |cfg| {
+            cfg.app_data(web::Data::from(store));
+        })
+        .await;
+        let fetch = |photo_id: uuid::Uuid, token: Option<&str>| {
+            let uri = format!("/api/v1/photos/{photo_id}/original");
+            test::call_service(&app, authed_request(Method::GET, &uri, token, None))
+        };
+
+        let public = fetch(photo_ids[0], None).await;
+        assert_eq!(public.status(), StatusCode::OK);
+        assert!(public.headers().get(header::CACHE_CONTROL).unwrap().to_str().unwrap().starts_with("public"));
+
+        assert_eq!(fetch(photo_ids[1], None).await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(fetch(photo_ids[1], Some(&guest_token)).await.status(), StatusCode::NOT_FOUND);
+        let owner_view = fetch(photo_ids[1], Some(&host_token)).await;
+        assert_eq!(owner_view.status(), StatusCode::OK);
+        assert_eq!(owner_view.headers().get(header::CACHE_CONTROL).unwrap(), "private, no-store");
+
+        // Uploaded originals are served without their EXIF block
+        let boundary = "photo-upload-boundary";
+        let mut body = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"gps.jpg\"\r\nContent-Type: image/jpeg\r\n\r\n"
+        )
+        .into_bytes();
+        body.extend(jpeg_with_gps_exif());
+        body.extend(format!("\r\n--{boundary}--\r\n").into_bytes());
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/v1/properties/{}/photos", published.id))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {host_token}")))
+            .insert_header((header::CONTENT_TYPE, format!("multipart/form-data; boundary={boundary}")))
+            .set_payload(body);
+        let uploaded = test::call_service(&app, req.to_request()).await;
+        assert_eq!(uploaded.status(), StatusCode::CREATED);
+        let uploaded: serde_json::Value = test::read_body_json(uploaded).await;
+        let served = fetch(serde_json::from_value(uploaded["id"].clone()).unwrap(), None).await;
+        assert_eq!(served.status(), StatusCode::OK);
+        let served = test::read_body(served).await;
+        assert!(served.starts_with(&[0xff, 0xd8]));
+        assert!(!contains(&served, b"Exif"));
+
+        // A live listing can't drop below the photos it was approved with; a draft can
+        let delete = |property: &Property, photo_id: uuid::Uuid| {
+            let uri = format!("/api/v1/properties/{}/photos/{photo_id}", property.id);
+            test::call_service(&app, authed_request(Method::DELETE, &uri, Some(&host_token), None))
+        };
+        assert_eq!(delete(&published, photo_ids[0]).await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(delete(&draft, photo_ids[1]).await.status(), StatusCode::NO_CONTENT);
+
+        // The photo cap is enforced where photos are saved
+        for _ in 0..MAX_PHOTOS_PER_PROPERTY {
+            db::create_photo(&pool, uuid::Uuid::new_v4(), draft.id, "unused", "image/png", 1, 1, 5, None)
+                .await
+                .unwrap();
+        }
+        assert!(db::create_photo(&pool, uuid::Uuid::new_v4(), draft.id, "unused", "image/png", 1, 1, 5, None)
+            .await
+            .is_err());
+
+        std::fs::remove_dir_all(upload_dir).unwrap();
+        delete_users(&pool, &[guest.id, host.id]).await;
+    }
+
+    #[actix_web::test]
+    async fn test_moderation_follows_listing_status() {
+        use super::{auth::{AuthUser, RequireAdmin}, cache::SuggestionCache, db, error::AppError, handlers, models::*};
+        use actix_web::web;
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let (guest, host, _) = seed_published_property(&pool, serde_json::json!({})).await;
+        let description = "A quiet flat with a long enough description to pass the listing checklist.";
+        let draft = db::create_property_db(&pool, host.id, &property_request(serde_json::json!({ "description": description })))
+            .await
+            .unwrap();
+
+        // Decisions and submissions only apply to listings in the matching state
+        assert!(db::review_property(&pool, draft.id, guest.id, ListingStatus::Published, None).await.is_err());
+        db::submit_property_for_review(&pool, draft.id).await.unwrap();
+        assert!(db::submit_property_for_review(&pool, draft.id).await.is_err());
+        db::review_property(&pool, draft.id, guest.id, ListingStatus::Rejected, Some("Blurry photos")).await.unwrap();
+        assert!(db::review_property(&pool, draft.id, guest.id, ListingStatus::Published, None).await.is_err());
+        db::submit_property_for_review(&pool, draft.id).await.unwrap();
+
+        // Approval checks the listing again, so one without photos stays in the queue
+        let admin = || RequireAdmin(AuthUser {
+            id: guest.id,
+            email: guest.email.clone(),
+            role: UserRole::Admin,
+            session_id: None,
+            email_verified: true,
+            two_factor_enabled: true,
+            api_key_id: None,
+        });
+        let cache = web::Data::new(SuggestionCache::new(std::time::Duration::from_secs(60), 10));
+        let approved = handlers::approve_property(web::Data::new(pool.clone()), cache, admin(), web::Path::from(draft.id)).await;
+        assert!(matches!(approved, Err(AppError::Validation(_))));
+        let property = db::get_property_by_id(&pool, draft.id).await.unwrap().unwrap();
+        assert_eq!(property.status, ListingStatus::PendingReview);
+
+        delete_users(&pool, &[guest.id, host.id]).await;
+    }
+
+    #[actix_web::test]
+    async fn test_protected_routes_check_token_and_role() {
+        use super::{auth, config::Config, models::*};
+        use actix_web::{http::Method, http::StatusCode, test};
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let (guest, host, _) = seed_published_property(&pool, serde_json::json!({})).await;
+        let keys = auth::JwtKeys::from_config(&Config::default()).unwrap();
+        let guest_token = keys.create_jwt(guest.id, UserRole::User, None).unwrap();
+        let host_token = keys.create_jwt(host.id, UserRole::PropertyOwner, None).unwrap();
+
+        let app = test_app(&pool, Config::default(), |_| {}).await;
+        let status = |uri: &str, token: Option<&str>| test::call_service(&app, authed_request(Method::GET, uri, token, None));
+
+        assert_eq!(status("/api/v1/bookings", None).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(status("/api/v1/bookings", Some("not-a-token")).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(status("/api/v1/bookings", Some(&guest_token)).await.status(), StatusCode::OK);
+        assert_eq!(status("/api/v1/properties/my", Some(&guest_token)).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(status("/api/v1/properties/my", Some(&host_token)).await.status(), StatusCode::OK);
+        assert_eq!(status("/api/v1/admin/moderation/queue", Some(&host_token)).await.status(), StatusCode::FORBIDDEN);
+
+        delete_users(&pool, &[guest.id, host.id]).await;
+    }
+
+    #[actix_web::test]
+    async fn test_wishlists_and_share_links() {
+        use super::{auth, config::Config, db, models::*};
+        use actix_web::{http::Method, http::StatusCode, test};
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let (guest, host, booked) = seed_published_property(&pool, serde_json::json!({ "title": "Booked flat" })).await;
+        let free = publish_property(&pool, host.id, serde_json::json!({ "title": "Free flat" })).await;
+        let day = |offset: i64| time::OffsetDateTime::now_utc().date() + time::Duration::days(offset);
+        let booking = db::create_booking_db(&pool, booked.id, host.id, day(10), day(12), 2000, 1, None, None)
+            .await
+            .unwrap();
+        db::update_booking_status(&pool, booking.id, BookingStatus::Approved).await.unwrap();
+
+        let keys = auth::JwtKeys::from_config(&Config::default()).unwrap();
+        let guest_token = keys.create_jwt(guest.id, UserRole::User, None).unwrap();
+        let host_token = keys.create_jwt(host.id, UserRole::PropertyOwner, None).unwrap();
+        let app = test_app(&pool, Config::default(), |_| {}).await;
+        let call = |method: Method, uri: &str, token: Option<&str>, body: Option<serde_json::Value>| {
+            test::call_service(&app, authed_request(method, uri, token, body))
+        };
+
+        let created = call(Method::POST, "/api/v1/wishlists", Some(&guest_token), Some(serde_json::json!({ "name": "Summer" }))).await;
+        assert_eq!(created.status(), StatusCode::CREATED);
+        let wishlist: serde_json::Value = test::read_body_json(created).await;
+        let uri = format!("/api/v1/wishlists/{}", wishlist["id"].as_str().unwrap());
+        for property in [&booked, &free] {
+            let body = serde_json::json!({ "property_id": property.id });
+            let added = call(Method::POST, &format!("{uri}/properties"), Some(&guest_token), Some(body)).await;
+            assert_eq!(added.status(), StatusCode::OK);
+        }
+
+        // Other users can neither see nor change the list
+        assert_eq!(call(Method::GET, &uri, Some(&host_token), None).await.status(), StatusCode::FORBIDDEN);
+        let body = serde_json::json!({ "property_id": free.id });
+        let added = call(Method::POST, &format!("{uri}/properties"), Some(&host_token), Some(body)).await;
+        assert_eq!(added.status(), StatusCode::FORBIDDEN);
+        assert_eq!(call(Method::POST, &format!("{uri}/share"), Some(&host_token), None).await.status(), StatusCode::FORBIDDEN);
+
+        // Availability is reported for the requested dates only
+        let dates = format!("check_in_date={}&check_out_date={}", day(11), day(13));
+        let listed: serde_json::Value = test::read_body_json(call(Method::GET, &uri, Some(&guest_token), None).await).await;
+        assert!(listed["properties"].as_array().unwrap().iter().all(|property| property["available"].is_null()));
+        let dated: serde_json::Value =
+            test::read_body_json(call(Method::GET, &format!("{uri}?{dates}"), Some(&guest_token), None).await).await;
+        let available = |list: &serde_json::Value, property: &Property| {
+            list["properties"].as_array().unwrap().iter().find(|p| p["id"] == property.id.to_string()).unwrap()["available"].clone()
+        };
+        assert_eq!(available(&dated, &booked), false);
+        assert_eq!(available(&dated, &free), true);
+
+        // Responses never carry the owner's user id
+        let renamed = call(Method::PUT, &uri, Some(&guest_token), Some(serde_json::json!({ "name": "Autumn" }))).await;
+        let renamed: serde_json::Value = test::read_body_json(renamed).await;
+        assert_eq!(renamed["name"], "Autumn");
+        assert!(renamed.get("user_id").is_none());
+
+        // The share link gives anyone a read-only view until it is revoked
+        let shared: serde_json::Value =
+            test::read_body_json(call(Method::POST, &format!("{uri}/share"), Some(&guest_token), None).await).await;
+        assert!(shared.get("user_id").is_none());
+        let shared_uri = format!("/api/v1/shared/wishlists/{}", shared["share_token"].as_str().unwrap());
+        let public: serde_json::Value =
+            test::read_body_json(call(Method::GET, &format!("{shared_uri}?{dates}"), None, None).await).await;
+        assert_eq!(public["name"], "Autumn");
+        assert_eq!(available(&public, &booked), false);
+        let unshared: serde_json::Value =
+            test::read_body_json(call(Method::DELETE, &format!("{uri}/share"), Some(&guest_token), None).await).await;
+        assert!(unshared["share_token"].is_null());
+        assert!(unshared.get("user_id").is_none());
+        assert_eq!(call(Method::GET, &shared_uri, None, None).await.status(), StatusCode::NOT_FOUND);
+
+        let item_uri = format!("{uri}/properties/{}", free.id);
+        assert_eq!(call(Method::DELETE, &item_uri, Some(&host_token), None).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(call(Method::DELETE, &item_uri, Some(&guest_token), None).await.status(), StatusCode::NO_CONTENT);
+        assert_eq!(call(Method::DELETE, &item_uri, Some(&guest_token), None).await.status(), StatusCode::NOT_FOUND);
+        let listed: serde_json::Value = test::read_body_json(call(Method::GET, &uri, Some(&guest_token), None).await).await;
+        assert_eq!(listed["properties"].as_array().unwrap().len(), 1);
+
+        delete_users(&pool, &[guest.id, host.id]).await;
+    }
+
+    #[actix_web::test]
+    async fn test_refresh_token_rotation_and_reuse_detection() {
+        use super::{auth, config::Config, db, models::*};
+        use actix_web::{http::Method, http::StatusCode, test};
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let email = format!("refresh-{}@example.com", uuid::Uuid::new_v4());
+        let password_hash = auth::hash_password("password123").await.unwrap();
+        let user = db::create_user(&pool, &email, &password_hash, "Refresh", "User", UserRole::User)
+            .await
+            .unwrap();
+
+        let app = test_app(&pool, Config::default(), |_| {}).await;
+        let post = |uri: &str, body: serde_json::Value| test::call_service(&app, authed_request(Method::POST, uri, None, Some(body)));
+        let bookings_status = |token: &str| test::call_service(&app, authed_request(Method::GET, "/api/v1/bookings", Some(token), None));
+        let tokens = |body: serde_json::Value| {
+            (body["token"].as_str().unwrap().to_string(), body["refresh_token"].as_str().unwrap().to_string())
+        };
+
+        let login = || post("/api/v1/auth/login", serde_json::json!({ "email": email, "password": "password123" }));
+        let (_, first_refresh) = tokens(test::read_body_json(login().await).await);
+
+        let response = post("/api/v1/auth/refresh", serde_json::json!({ "refresh_token": first_refresh })).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let (access, second_refresh) = tokens(test::read_body_json(response).await);
+        assert_ne!(first_refresh, second_refresh);
+        assert_eq!(bookings_status(&access).await.status(), StatusCode::OK);
+
+        // Replaying the first token ends the login, including the latest tokens
+        let replay = post("/api/v1/auth/refresh", serde_json::json!({ "refresh_token": first_refresh })).await;
+        assert_eq!(replay.status(), StatusCode::UNAUTHORIZED);
+        let latest = post("/api/v1/auth/refresh", serde_json::json!({ "refresh_token": second_refresh })).await;
+        assert_eq!(latest.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(bookings_status(&access).await.status(), StatusCode::UNAUTHORIZED);
+
+        // Logout ends one login, logout-all every other one
+        let (phone, phone_refresh) = tokens(test::read_body_json(login().await).await);
+        let (laptop, laptop_refresh) = tokens(test::read_body_json(login().await).await);
+        let (tablet, _) = tokens(test::read_body_json(login().await).await);
+        let logout = post("/api/v1/auth/logout", serde_json::json!({ "refresh_token": phone_refresh })).await;
+        assert_eq!(logout.status(), StatusCode::NO_CONTENT);
+        assert_eq!(bookings_status(&phone).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(bookings_status(&laptop).await.status(), StatusCode::OK);
+
+        let req = authed_request(Method::POST, "/api/v1/auth/logout-all", Some(&tablet), None);
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["revoked_sessions"], 2);
+        assert_eq!(bookings_status(&laptop).await.status(), StatusCode::UNAUTHORIZED);
+        let refresh = post("/api/v1/auth/refresh", serde_json::json!({ "refresh_token": laptop_refresh })).await;
+        assert_eq!(refresh.status(), StatusCode::UNAUTHORIZED);
+
+        delete_users(&pool, &[user.id]).await;
+    }
+
+    #[actix_web::test]
+    async fn test_sessions_list_and_revoke() {
+        use super::{auth, config::Config, db, models::*};
+        use actix_web::{http::header, http::Method, http::StatusCode, test};
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let run_id = uuid::Uuid::new_v4();
+        let password_hash = auth::hash_password("password123").await.unwrap();
+        let user = db::create_user(&pool, &format!("sessions-{run_id}@example.com"), &password_hash, "Session", "User", UserRole::User)
+            .await
+            .unwrap();
+        let admin = db::create_user(&pool, &format!("admin-{run_id}@example.com"), &password_hash, "Admin", "User", UserRole::Admin)
+            .await
+            .unwrap();
+        let admin_totp = enable_test_two_factor(&pool, &admin).await;
+
+        let app = test_app(&pool, Config::default(), |_| {}).await;
+        let login = |email: String, user_agent: &'static str| {
+            let req = test::TestRequest::post()
+                .uri("/api/v1/auth/login")
+                .insert_header((header::USER_AGENT, user_agent))
+                .peer_addr("203.0.113.7:50000".parse().unwrap())
+                .set_json(serde_json::json!({ "email": email, "password": "password123" }));
+            test::call_and_read_body_json::<_, _, serde_json::Value>(&app, req.to_request())
+        };
+        let authed = |method: Method, uri: &str, token: &serde_json::Value| authed_request(method, uri, token.as_str(), None);
+
+        let phone = login(user.email.clone(), "PhoneApp/1.0").await;
+        let laptop = login(user.email.clone(), "Mozilla/5.0 (X11; Linux x86_64)").await;
+
+        let sessions: serde_json::Value =
+            test::call_and_read_body_json(&app, authed(Method::GET, "/api/v1/me/sessions", &laptop["token"])).await;
+        let sessions = sessions.as_array().unwrap();
+        assert_eq!(sessions.len(), 2);
+        let current: Vec<_> = sessions.iter().filter(|session| session["current"] == true).collect();
+        assert_eq!(current.len(), 1);
+        assert_eq!(current[0]["user_agent"], "Mozilla/5.0 (X11; Linux x86_64)");
+        assert_eq!(current[0]["ip_address"], "203.0.113.7");
+        let phone_session = sessions.iter().find(|session| session["current"] == false).unwrap();
+        assert_eq!(phone_session["user_agent"], "PhoneApp/1.0");
+
+        // Revoking the phone's session signs the phone out, not the laptop
+        let uri = format!("/api/v1/me/sessions/{}", phone_session["id"].as_str().unwrap());
+        let response = test::call_service(&app, authed(Method::DELETE, &uri, &laptop["token"])).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = test::call_service(&app, authed(Method::DELETE, &uri, &laptop["token"])).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = test::call_service(&app, authed(Method::GET, "/api/v1/bookings", &phone["token"])).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = test::call_service(&app, authed(Method::GET, "/api/v1/bookings", &laptop["token"])).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Only admins can revoke someone else's sessions
+        let uri = format!("/api/v1/admin/users/{}/sessions", user.id);
+        let response = test::call_service(&app, authed(Method::DELETE, &uri, &laptop["token"])).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let challenge = login(admin.email.clone(), "AdminConsole").await;
+        let verify = serde_json::json!({
+            "challenge_token": challenge["challenge_token"],
+            "code": admin_totp.generate_current().unwrap(),
+        });
+        let req = authed_request(Method::POST, "/api/v1/auth/2fa/verify", None, Some(verify));
+        let admin_login: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let body: serde_json::Value =
+            test::call_and_read_body_json(&app, authed(Method::DELETE, &uri, &admin_login["token"])).await;
+        assert_eq!(body["revoked_sessions"], 1);
+        let response = test::call_service(&app, authed(Method::GET, "/api/v1/bookings", &laptop["token"])).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        delete_users(&pool, &[user.id, admin.id]).await;
+    }
+
+    // Hands emails to the test instead of sending them
+    struct ChannelMailer(tokio::sync::mpsc::UnboundedSender<super::email::OutgoingEmail>);
+
+    #[async_trait::async_trait]
+    impl super::email::Mailer for ChannelMailer {
+        async fn send(&self, email: &super::email::OutgoingEmail) -> Result<(), super::error::AppError> {
+            self.0.send(email.clone()).unwrap();
+            Ok(())
+        }
+    }
+
+    // Turns on two-factor for a test user, returning their authenticator
+    async fn enable_test_two_factor(pool: &sqlx::PgPool, user: &super::models::User) -> totp_rs::TOTP {
+        let secret = super::auth::generate_totp_secret().unwrap();
+        super::db::set_totp_secret(pool, user.id, &secret).await.unwrap();
+        super::db::enable_two_factor(pool, user.id, &[]).await.unwrap();
+        super::auth::totp(&secret, "Airbnb", &user.email).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_password_reset() {
+        use super::{auth, config::Config, db, email::EmailOutbox, models::*};
+        use actix_web::{http::Method, http::StatusCode, test, web};
+        use std::{sync::Arc, time::Duration};
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let run = uuid::Uuid::new_v4();
+        let email = format!("reset-{}@example.com", run);
+        let password_hash = auth::hash_password("old-password").await.unwrap();
+        let user = db::create_user(&pool, &email, &password_hash, "Reset", "User", UserRole::User)
+            .await
+            .unwrap();
+        let other = db::create_user(&pool, &format!("reset-other-{}@example.com", run), &password_hash, "Other", "User", UserRole::User)
+            .await
+            .unwrap();
+
+        let (sent, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+        let config = Config { password_reset_ip_daily_limit: 2, ..Config::default() };
+        let app = test_app(&pool, config, |cfg| {
+            cfg.app_data(web::Data::new(EmailOutbox::start(Arc::new(ChannelMailer(sent)))));
+        })
+        .await;
+        let post = |uri: &str, body: serde_json::Value| test::call_service(&app, authed_request(Method::POST, uri, None, Some(body)));
+        let login = |password: &'static str| post("/api/v1/auth/login", serde_json::json!({ "email": email, "password": password }));
+
+        let session: serde_json::Value = test::read_body_json(login("old-password").await).await;
+
+        // Unknown addresses get the same answer and no email
+        let nobody = format!("reset-nobody-{}@example.com", run);
+        let unknown = post("/api/v1/auth/forgot-password", serde_json::json!({ "email": nobody })).await;
+        assert_eq!(unknown.status(), StatusCode::ACCEPTED);
+        let unknown_body: serde_json::Value = test::read_body_json(unknown).await;
+        let known = post("/api/v1/auth/forgot-password", serde_json::json!({ "email": email })).await;
+        assert_eq!(known.status(), StatusCode::ACCEPTED);
+        assert_eq!(test::read_body_json::<serde_json::Value, _>(known).await, unknown_body);
+
+        // Asking again too soon, or from an address that used up its day, gets
+        // the same answer too, but no email
+        let again = post("/api/v1/auth/forgot-password", serde_json::json!({ "email": email.to_uppercase() })).await;
+        assert_eq!(again.status(), StatusCode::ACCEPTED);
+        assert_eq!(test::read_body_json::<serde_json::Value, _>(again).await, unknown_body);
+        let ip = format!("10.{}.{}.9", run.as_bytes()[0], run.as_bytes()[1]);
+        let forgot_from_ip = |email: String| {
+            let req = test::TestRequest::post()
+                .uri("/api/v1/auth/forgot-password")
+                .peer_addr(format!("{ip}:50000").parse().unwrap())
+                .set_json(serde_json::json!({ "email": email }));
+            test::call_service(&app, req.to_request())
+        };
+        for n in 0..2 {
+            let response = forgot_from_ip(format!("reset-spray-{}-{}@example.com", n, run)).await;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+        }
+        let limited = forgot_from_ip(other.email.clone()).await;
+        assert_eq!(test::read_body_json::<serde_json::Value, _>(limited).await, unknown_body);
+        for (user_id, expected) in [(user.id, 1), (other.id, 0)] {
+            let tokens: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM password_reset_tokens WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(tokens, expected);
+        }
+
+        let reset_email = tokio::time::timeout(Duration::from_secs(5), inbox.recv()).await.unwrap().unwrap();
+        assert_eq!(reset_email.to, email);
+        let token = reset_email.body.split("token=").nth(1).unwrap().split_whitespace().next().unwrap().to_string();
+        // Only the hash is stored
+        let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM password_reset_tokens WHERE token_hash = $1")
+            .bind(&token)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, 0);
+
+        let reset = serde_json::json!({ "token": token, "new_password": "new-password" });
+        assert_eq!(post("/api/v1/auth/reset-password", reset.clone()).await.status(), StatusCode::NO_CONTENT);
+        assert_eq!(post("/api/v1/auth/reset-password", reset).await.status(), StatusCode::BAD_REQUEST);
+        let changed_email = tokio::time::timeout(Duration::from_secs(5), inbox.recv()).await.unwrap().unwrap();
+        assert_eq!(changed_email.subject, "Your password was changed");
+
+        // Existing sessions are signed out, and only the new password works
+        let req = authed_request(Method::GET, "/api/v1/bookings", session["token"].as_str(), None);
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+        let refresh = post("/api/v1/auth/refresh", serde_json::json!({ "refresh_token": session["refresh_token"] })).await;
+        assert_eq!(refresh.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(login("old-password").await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(login("new-password").await.status(), StatusCode::OK);
+
+        delete_users(&pool, &[user.id, other.id]).await;
+        sqlx::query("DELETE FROM password_reset_throttles WHERE key LIKE $1 OR key = $2")
+            .bind(format!("%{}%", run))
+            .bind(&ip)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM login_throttles WHERE key LIKE $1").bind(format!("%{}%", run)).execute(&pool).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_email_verification() {
+        use super::{config::Config, email::EmailOutbox};
+        use actix_web::{http::Method, http::StatusCode, test, web};
+        use std::{sync::Arc, time::Duration};
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let (sent, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+        let app = test_app(&pool, Config::default(), |cfg| {
+            cfg.app_data(web::Data::new(EmailOutbox::start(Arc::new(ChannelMailer(sent)))));
+        })
+        .await;
+        let post = |uri: &str, token: Option<&str>, body: serde_json::Value| {
+            test::call_service(&app, authed_request(Method::POST, uri, token, Some(body)))
+        };
+
+        let email = format!("verify-{}@example.com", uuid::Uuid::new_v4());
+        let credentials = serde_json::json!({ "email": email, "password": "password123" });
+        let mut registration = credentials.clone();
+        registration["first_name"] = "Verify".into();
+        registration["last_name"] = "User".into();
+        let registered = post("/api/v1/auth/register", None, registration).await;
+        assert_eq!(registered.status(), StatusCode::CREATED);
+        let user: serde_json::Value = test::read_body_json(registered).await;
+        assert_eq!(user["email_verified"], false);
+
+        let verification = tokio::time::timeout(Duration::from_secs(5), inbox.recv()).await.unwrap().unwrap();
+        assert_eq!(verification.to, email);
+        let token = verification.body.split("token=").nth(1).unwrap().split_whitespace().next().unwrap().to_string();
+
+        // Unverified guests cannot book, and resending is throttled
+        let session: serde_json::Value = test::read_body_json(post("/api/v1/auth/login", None, credentials.clone()).await).await;
+        let access_token = session["token"].as_str().unwrap();
+        let booking = serde_json::json!({
+            "property_id": uuid::Uuid::new_v4(),
+            "check_in_date": "2030-01-01",
+            "check_out_date": "2030-01-03",
+            "guest_count": 1,
+        });
+        assert_eq!(post("/api/v1/bookings", Some(access_token), booking.clone()).await.status(), StatusCode::FORBIDDEN);
+        let resend = post("/api/v1/auth/resend-verification", Some(access_token), serde_json::json!({})).await;
+        assert_eq!(resend.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let verified = post("/api/v1/auth/verify-email", None, serde_json::json!({ "token": token })).await;
+        assert_eq!(verified.status(), StatusCode::OK);
+        assert_eq!(test::read_body_json::<serde_json::Value, _>(verified).await["email_verified"], true);
+        let reused = post("/api/v1/auth/verify-email", None, serde_json::json!({ "token": token })).await;
+        assert_eq!(reused.status(), StatusCode::BAD_REQUEST);
+
+        // The same access token now gets past the check (and on to the missing property)
+        assert_eq!(post("/api/v1/bookings", Some(access_token), booking).await.status(), StatusCode::NOT_FOUND);
+        let resend = post("/api/v1/auth/resend-verification", Some(access_token), serde_json::json!({})).await;
+        assert_eq!(resend.status(), StatusCode::BAD_REQUEST);
+
+        sqlx::query("DELETE FROM users WHERE email = $1").bind(&email).execute(&pool).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_login_lockout() {
+        use super::{auth, config::Config, db, models::*};
+        use actix_web::{http::Method, http::StatusCode, test};
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let run = uuid::Uuid::new_v4();
+        let password_hash = auth::hash_password("password123").await.unwrap();
+        let user = db::create_user(&pool, &format!("lockout-{}@example.com", run), &password_hash, "Lock", "Out", UserRole::User)
+            .await
+            .unwrap();
+        let admin = db::create_user(&pool, &format!("lockout-admin-{}@example.com", run), &password_hash, "Lock", "Admin", UserRole::Admin)
+            .await
+            .unwrap();
+        let admin_totp = enable_test_two_factor(&pool, &admin).await;
+
+        // Each run gets its own addresses, so leftover counts do not interfere
+        let ip = |n: u8| format!("10.{}.{}.{}", run.as_bytes()[0], run.as_bytes()[1], n);
+        let config = Config {
+            login_max_failures: 2,
+            login_ip_max_failures: 3,
+            trusted_proxies: vec![ip(7).parse().unwrap()],
+            ..Config::default()
+        };
+        let app = test_app(&pool, config, |_| {}).await;
+        let login_via = |email: &str, password: &str, peer: String, forwarded_for: Option<String>| {
+            let mut req = test::TestRequest::post()
+                .uri("/api/v1/auth/login")
+                .peer_addr(format!("{peer}:50000").parse().unwrap())
+                .set_json(serde_json::json!({ "email": email, "password": password }));
+            if let Some(forwarded_for) = forwarded_for {
+                req = req.insert_header(("X-Forwarded-For", forwarded_for));
+            }
+            test::call_service(&app, req.to_request())
+        };
+        let login = |email: &str, password: &str, from: String| login_via(email, password, from, None);
+
+        // The account locks after its allowed failures, even for the right password
+        assert_eq!(login(&user.email, "wrong-password", ip(1)).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(login(&user.email, "wrong-password", ip(2)).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(login(&user.email, "password123", ip(3)).await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Unknown emails behave the same way
+        let unknown = format!("lockout-nobody-{}@example.com", run);
+        assert_eq!(login(&unknown, "password123", ip(1)).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(login(&unknown, "password123", ip(2)).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(login(&unknown, "password123", ip(3)).await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // One address guessing across many accounts is locked too
+        for n in 0..3 {
+            let email = format!("lockout-spray-{}-{}@example.com", n, run);
+            assert_eq!(login(&email, "password123", ip(4)).await.status(), StatusCode::UNAUTHORIZED);
+        }
+        assert_eq!(login(&admin.email, "password123", ip(4)).await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Forwarded addresses only count when they come through a trusted proxy
+        let spoofed = login_via(&admin.email, "password123", ip(4), Some(ip(5))).await;
+        assert_eq!(spoofed.status(), StatusCode::TOO_MANY_REQUESTS);
+        let proxied = login_via(&admin.email, "password123", ip(7), Some(format!("{}, {}", ip(5), ip(4)))).await;
+        assert_eq!(proxied.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let proxied = login_via(&admin.email, "password123", ip(7), Some(ip(5))).await;
+        let challenge: serde_json::Value = test::read_body_json(proxied).await;
+        let verify = serde_json::json!({
+            "challenge_token": challenge["challenge_token"],
+            "code": admin_totp.generate_current().unwrap(),
+        });
+        let req = authed_request(Method::POST, "/api/v1/auth/2fa/verify", None, Some(verify));
+        let session: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let uri = format!("/api/v1/admin/users/{}/login-lock", user.id);
+        let unlocked: serde_json::Value =
+            test::call_and_read_body_json(&app, authed_request(Method::DELETE, &uri, session["token"].as_str(), None)).await;
+        assert_eq!(unlocked["unlocked"], true);
+        assert_eq!(login(&user.email, "password123", ip(3)).await.status(), StatusCode::OK);
+
+        delete_users(&pool, &[user.id, admin.id]).await;
+        sqlx::query("DELETE FROM login_throttles WHERE key LIKE $1 OR key = ANY($2)")
+            .bind(format!("%{}%", run))
+            .bind((1..=7).map(ip).collect::<Vec<_>>())
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_two_factor_login() {
+        use super::{auth, config::Config, db, models::*};
+        use actix_web::{http::Method, http::StatusCode, test};
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let run = uuid::Uuid::new_v4();
+        let password_hash = auth::hash_password("password123").await.unwrap();
+        let owner = db::create_user(&pool, &format!("2fa-owner-{}@example.com", run), &password_hash, "Two", "Factor", UserRole::PropertyOwner)
+            .await
+            .unwrap();
+        let admin = db::create_user(&pool, &format!("2fa-admin-{}@example.com", run), &password_hash, "Two", "Admin", UserRole::Admin)
+            .await
+            .unwrap();
+
+        let app = test_app(&pool, Config::default(), |_| {}).await;
+        let post = |uri: &str, token: Option<&serde_json::Value>, body: serde_json::Value| {
+            test::call_service(&app, authed_request(Method::POST, uri, token.and_then(|token| token.as_str()), Some(body)))
+        };
+        let login = |email: &str| post("/api/v1/auth/login", None, serde_json::json!({ "email": email, "password": "password123" }));
+
+        // Enrolment only takes effect once a code is confirmed
+        let session: serde_json::Value = test::read_body_json(login(&owner.email).await).await;
+        let token = &session["token"];
+        let setup: serde_json::Value = test::read_body_json(post("/api/v1/auth/2fa/setup", Some(token), serde_json::json!({})).await).await;
+        let secret = setup["secret"].as_str().unwrap();
+        assert!(setup["otpauth_url"].as_str().unwrap().starts_with("otpauth://totp/Airbnb:"));
+        let authenticator = auth::totp(secret, "Airbnb", &owner.email).unwrap();
+        let code = authenticator.generate_current().unwrap();
+        let wrong_code = if code == "123456" { "654321" } else { "123456" };
+        let response = post("/api/v1/auth/2fa/confirm", Some(token), serde_json::json!({ "code": wrong_code })).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let confirmed = post("/api/v1/auth/2fa/confirm", Some(token), serde_json::json!({ "code": code })).await;
+        assert_eq!(confirmed.status(), StatusCode::OK);
+        let confirmed: serde_json::Value = test::read_body_json(confirmed).await;
+        let recovery_codes = confirmed["recovery_codes"].as_array().unwrap();
+        assert_eq!(recovery_codes.len(), 10);
+
+        // Login now stops at a challenge; a code that was already used is refused
+        let challenge: serde_json::Value = test::read_body_json(login(&owner.email).await).await;
+        assert_eq!(challenge["two_factor_required"], true);
+        assert!(challenge.get("token").is_none());
+        let verify = |code: &serde_json::Value| {
+            post("/api/v1/auth/2fa/verify", None, serde_json::json!({ "challenge_token": challenge["challenge_token"], "code": code }))
+        };
+        assert_eq!(verify(&code.clone().into()).await.status(), StatusCode::UNAUTHORIZED);
+        let recovered = verify(&recovery_codes[0]).await;
+        assert_eq!(recovered.status(), StatusCode::OK);
+        let recovered: serde_json::Value = test::read_body_json(recovered).await;
+        assert_eq!(recovered["user"]["two_factor_enabled"], true);
+        // Challenges and recovery codes are single-use
+        assert_eq!(verify(&recovery_codes[1]).await.status(), StatusCode::UNAUTHORIZED);
+        let challenge: serde_json::Value = test::read_body_json(login(&owner.email).await).await;
+        let response = post(
+            "/api/v1/auth/2fa/verify",
+            None,
+            serde_json::json!({ "challenge_token": challenge["challenge_token"], "code": recovery_codes[0] }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Without it admins can only enrol: not use admin or owner endpoints,
+        // nor turn two-factor off
+        let admin_session: serde_json::Value = test::read_body_json(login(&admin.email).await).await;
+        for uri in ["/api/v1/admin/moderation/queue", "/api/v1/properties/my"] {
+            let req = authed_request(Method::GET, uri, admin_session["token"].as_str(), None);
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        }
+        let response = post("/api/v1/auth/2fa/disable", Some(&admin_session["token"]), serde_json::json!({ "code": "123456" })).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = post("/api/v1/auth/2fa/setup", Some(&admin_session["token"]), serde_json::json!({})).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        delete_users(&pool, &[owner.id, admin.id]).await;
+        sqlx::query("DELETE FROM login_throttles WHERE key LIKE $1").bind(format!("%{}%", run)).execute(&pool).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_oidc_sign_in() {
+        use super::{auth, config::{Config, OidcProviderConfig}, db, models::*, oidc::{pkce_challenge, OidcClient}};
+        use actix_web::{http::Method, http::StatusCode, test, web, App, HttpResponse, HttpServer};
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+        use ring::{rand::SystemRandom, signature::{Ed25519KeyPair, KeyPair}};
+        use std::{collections::HashMap, sync::Mutex};
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        // A local provider that signs in whoever the test asks for, once the
+        // code and PKCE verifier check out
+        struct MockIssuer {
+            issuer: String,
+            code_challenge: String,
+            claims: serde_json::Value,
+            pkcs8: Vec<u8>,
+        }
+
+        async fn discovery(mock: web::Data<Mutex<MockIssuer>>) -> HttpResponse {
+            let issuer = mock.lock().unwrap().issuer.clone();
+            HttpResponse::Ok().json(serde_json::json!({
+                "issuer": issuer,
+                "authorization_endpoint": format!("{issuer}/authorize"),
+                "token_endpoint": format!("{issuer}/token"),
+                "jwks_uri": format!("{issuer}/jwks"),
+            }))
+        }
+
+        async fn jwks(mock: web::Data<Mutex<MockIssuer>>) -> HttpResponse {
+            let key_pair = Ed25519KeyPair::from_pkcs8(&mock.lock().unwrap().pkcs8).unwrap();
+            HttpResponse::Ok().json(serde_json::json!({ "keys": [{
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "kid": "mock-key",
+                "alg": "EdDSA",
+                "use": "sig",
+                "x": URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref()),
+            }] }))
+        }
+
+        async fn token(mock: web::Data<Mutex<MockIssuer>>, form: web::Form<HashMap<String, String>>) -> HttpResponse {
+            let mock = mock.lock().unwrap();
+            let verifier = form.get("code_verifier").map(String::as_str).unwrap_or_default();
+            if form.get("code").map(String::as_str) != Some("mock-code") || pkce_challenge(verifier) != mock.code_challenge {
+                return HttpResponse::BadRequest().json(serde_json::json!({ "error": "invalid_grant" }));
+            }
+            let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::EdDSA);
+            header.kid = Some("mock-key".to_string());
+            let id_token = jsonwebtoken::encode(&header, &mock.claims, &jsonwebtoken::EncodingKey::from_ed_der(&mock.pkcs8)).unwrap();
+            HttpResponse::Ok().json(serde_json::json!({ "access_token": "mock", "token_type": "Bearer", "id_token": id_token }))
+        }
+
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap().as_ref().to_vec();
+        let mock = web::Data::new(Mutex::new(MockIssuer {
+            issuer: String::new(),
+            code_challenge: String::new(),
+            claims: serde_json::Value::Null,
+            pkcs8,
+        }));
+        let server_mock = mock.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(server_mock.clone())
+                .route("/.well-known/openid-configuration", web::get().to(discovery))
+                .route("/jwks", web::get().to(jwks))
+                .route("/token", web::post().to(token))
+        })
+        .workers(1)
+        .disable_signals()
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let issuer = format!("http://{}", server.addrs()[0]);
+        mock.lock().unwrap().issuer = issuer.clone();
+        actix_web::rt::spawn(server.run());
+
+        let config = Config {
+            oidc_providers: vec![OidcProviderConfig {
+                name: "mock".to_string(),
+                issuer: issuer.clone(),
+                client_id: "test-client".to_string(),
+                client_secret: Some("test-secret".to_string()),
+                scopes: "openid email profile".to_string(),
+                redirect_url: None,
+            }],
+            ..Config::default()
+        };
+        let oidc = OidcClient::from_config(&config).unwrap();
+        let app = test_app(&pool, config, |cfg| {
+            cfg.app_data(web::Data::new(oidc));
+        })
+        .await;
+        let callback = |state: String| {
+            let body = serde_json::json!({ "code": "mock-code", "state": state });
+            test::call_service(&app, authed_request(Method::POST, "/api/v1/auth/oidc/mock/callback", None, Some(body)))
+        };
+        // Starts a sign-in, has the provider vouch for `claims` and finishes it
+        let sign_in = |claims: serde_json::Value| {
+            let (app, mock, issuer) = (&app, &mock, &issuer);
+            async move {
+                let req = authed_request(Method::GET, "/api/v1/auth/oidc/mock/authorize", None, None);
+                let started: serde_json::Value = test::call_and_read_body_json(app, req).await;
+                let url = reqwest::Url::parse(started["authorization_url"].as_str().unwrap()).unwrap();
+                assert!(url.as_str().starts_with(&format!("{issuer}/authorize?")));
+                let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+                assert_eq!(params["client_id"], "test-client");
+                assert_eq!(params["redirect_uri"], "http://localhost:3000/auth/callback/mock");
+                assert_eq!(params["code_challenge_method"], "S256");
+
+                let now = time::OffsetDateTime::now_utc().unix_timestamp();
+                let mut id_claims = serde_json::json!({
+                    "iss": issuer,
+                    "aud": "test-client",
+                    "iat": now,
+                    "exp": now + 300,
+                    "nonce": params["nonce"],
+                });
+                for (claim, value) in claims.as_object().unwrap() {
+                    id_claims[claim] = value.clone();
+                }
+                {
+                    let mut mock = mock.lock().unwrap();
+                    mock.code_challenge = params["code_challenge"].clone();
+                    mock.claims = id_claims;
+                }
+
+                let state = params["state"].clone();
+                let response = callback(state.clone()).await;
+                let status = response.status();
+                let body: serde_json::Value = serde_json::from_slice(&test::read_body(response).await).unwrap_or_default();
+                (status, body, state)
+            }
+        };
+
+        let run = uuid::Uuid::new_v4();
+        let existing_email = format!("oidc-existing-{}@example.com", run);
+        let new_email = format!("oidc-new-{}@example.com", run);
+        let password_hash = auth::hash_password("password123").await.unwrap();
+        let existing = db::create_user(&pool, &existing_email, &password_hash, "Existing", "User", UserRole::User)
+            .await
+            .unwrap();
+
+        // A verified email links the identity to the existing account
+        let subject = format!("subject-{}", run);
+        let (status, body, used_state) = sign_in(serde_json::json!({ "sub": subject, "email": existing_email, "email_verified": true })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["user"]["id"], existing.id.to_string());
+        assert_eq!(body["user"]["email_verified"], true);
+        assert!(body["token"].is_string() && body["refresh_token"].is_string());
+
+        // Later sign-ins find the account by subject, whatever the email says
+        let (status, body, _) = sign_in(serde_json::json!({ "sub": subject, "email": "someone-else@example.com" })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["user"]["id"], existing.id.to_string());
+
+        // A new identity with an unknown email gets a new account
+        let (status, body, _) = sign_in(serde_json::json!({
+            "sub": format!("new-{}", run),
+            "email": new_email,
+            "email_verified": "true",
+            "given_name": "Nova",
+            "family_name": "Guest",
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["user"]["email"], new_email);
+        assert_eq!(body["user"]["role"], "User");
+        let created = db::get_user_by_email(&pool, &new_email).await.unwrap().unwrap();
+        assert_eq!((created.first_name.as_str(), created.last_name.as_str()), ("Nova", "Guest"));
+
+        // Unverified emails never take over accounts, tokens for another client
+        // are refused, and each state works once
+        let (status, _, _) = sign_in(serde_json::json!({ "sub": format!("other-{}", run), "email": existing_email, "email_verified": false })).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _, _) = sign_in(serde_json::json!({ "sub": subject, "aud": "another-client" })).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(callback(used_state).await.status(), StatusCode::BAD_REQUEST);
+        let req = authed_request(Method::GET, "/api/v1/auth/oidc/unknown/authorize", None, None);
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+        // Someone who registered the address without verifying it loses the
+        // account to its verified owner, along with their password, second
+        // factor and sessions
+        let squatted_email = format!("oidc-squatted-{}@example.com", run);
+        let squatted = db::create_user(&pool, &squatted_email, &password_hash, "Squatter", "User", UserRole::User)
+            .await
+            .unwrap();
+        let login = |password: &'static str| {
+            let body = serde_json::json!({ "email": squatted_email, "password": password });
+            test::call_service(&app, authed_request(Method::POST, "/api/v1/auth/login", None, Some(body)))
+        };
+        let squatter_session: serde_json::Value = test::read_body_json(login("password123").await).await;
+        enable_test_two_factor(&pool, &squatted).await;
+
+        let (status, body, _) = sign_in(serde_json::json!({ "sub": format!("owner-{}", run), "email": squatted_email, "email_verified": true })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["user"]["id"], squatted.id.to_string());
+        assert!(body["token"].is_string());
+        let req = authed_request(Method::GET, "/api/v1/bookings", squatter_session["token"].as_str(), None);
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(login("password123").await.status(), StatusCode::UNAUTHORIZED);
+        assert!(db::get_user_by_id(&pool, squatted.id).await.unwrap().unwrap().totp_enabled_at.is_none());
+
+        delete_users(&pool, &[existing.id, created.id, squatted.id]).await;
+        sqlx::query("DELETE FROM login_throttles WHERE key LIKE $1").bind(format!("%{}%", run)).execute(&pool).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_api_keys() {
+        use super::{auth, config::Config, db, models::*};
+        use actix_web::{http::Method, http::StatusCode, test};
+
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+
+        let password_hash = auth::hash_password("password123").await.unwrap();
+        let owner = db::create_user(&pool, &format!("api-keys-{}@example.com", uuid::Uuid::new_v4()), &password_hash, "Key", "Owner", UserRole::PropertyOwner)
+            .await
+            .unwrap();
+
+        let app = test_app(&pool, Config::default(), |_| {}).await;
+        let call = |method: Method, uri: &str, credential: &str, body: Option<serde_json::Value>| {
+            test::call_service(&app, authed_request(method, uri, Some(credential), body))
+        };
+
+        let credentials = serde_json::json!({ "email": owner.email, "password": "password123" });
+        let req = authed_request(Method::POST, "/api/v1/auth/login", None, Some(credentials));
+        let session: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let token = session["token"].as_str().unwrap();
+
+        let invalid = serde_json::json!({ "name": "Channel manager", "scopes": ["bookings:delete"] });
+        let response = call(Method::POST, "/api/v1/me/api-keys", token, Some(invalid)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let request = serde_json::json!({ "name": "Channel manager", "scopes": ["properties:read", "calendar:read"] });
+        let response = call(Method::POST, "/api/v1/me/api-keys", token, Some(request)).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created: serde_json::Value = test::read_body_json(response).await;
+        let key = created["key"].as_str().unwrap().to_string();
+        assert!(key.starts_with(&format!("abk_{}_", created["prefix"].as_str().unwrap())));
+
+        // Keys reach the routes their scopes cover, and nothing else
+        assert_eq!(call(Method::GET, "/api/v1/properties/my", &key, None).await.status(), StatusCode::OK);
+        let bookings = format!("/api/v1/properties/{}/bookings", uuid::Uuid::new_v4());
+        assert_eq!(call(Method::GET, &bookings, &key, None).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(call(Method::GET, "/api/v1/me/sessions", &key, None).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(call(Method::GET, "/api/v1/me/api-keys", &key, None).await.status(), StatusCode::FORBIDDEN);
+        let guessed = format!("{}x", key);
+        assert_eq!(call(Method::GET, "/api/v1/properties/my", &guessed, None).await.status(), StatusCode::UNAUTHORIZED);
+
+        // Listings never include the secret, but show when the key was used
+        let response = call(Method::GET, "/api/v1/me/api-keys", token, None).await;
+        let keys: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(keys.as_array().unwrap().len(), 1);
+        assert!(keys[0].get("key").is_none());
+        assert!(!keys[0]["last_used_at"].is_null());
+
+        // Expired and revoked keys stop working
+        let request = serde_json::json!({ "name": "Short-lived", "scopes": ["properties:read"], "expires_in_days": 1 });
+        let expiring: serde_json::Value =
+            test::read_body_json(call(Method::POST, "/api/v1/me/api-keys", token, Some(request)).await).await;
+        sqlx::query("UPDATE api_keys SET expires_at = NOW() - INTERVAL '1 minute' WHERE id = $1")
+            .bind(uuid::Uuid::parse_str(expiring["id"].as_str().unwrap()).unwrap())
+            .execute(&pool)
+            .await
+            .unwrap();
+        let expired_key = expiring["key"].as_str().unwrap();
+        assert_eq!(call(Method::GET, "/api/v1/properties/my", expired_key, None).await.status(), StatusCode::UNAUTHORIZED);
+
+        let uri = format!("/api/v1/me/api-keys/{}", created["id"].as_str().unwrap());
+        assert_eq!(call(Method::DELETE, &uri, token, None).await.status(), StatusCode::NO_CONTENT);
+        assert_eq!(call(Method::DELETE, &uri, token, None).await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(call(Method::GET, "/api/v1/properties/my", &key, None).await.status(), StatusCode::UNAUTHORIZED);
+
+        // Admins can neither create keys nor use one they already hold
+        let admin = db::create_user(&pool, &format!("api-keys-admin-{}@example.com", uuid::Uuid::new_v4()), &password_hash, "Key", "Admin", UserRole::Admin)
+            .await
+            .unwrap();
+        enable_test_two_factor(&pool, &admin).await;
+        let admin_token = auth::JwtKeys::from_config(&Config::default()).unwrap().create_jwt(admin.id, UserRole::Admin, None).unwrap();
+        let request = serde_json::json!({ "name": "Admin tool", "scopes": ["properties:read"] });
+        let response = call(Method::POST, "/api/v1/me/api-keys", &admin_token, Some(request)).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let (prefix, admin_key) = auth::generate_api_key().unwrap();
+        db::create_api_key(&pool, admin.id, "Admin tool", &prefix, &auth::hash_token(&admin_key), &["properties:read".to_string()], None)
+            .await
+            .unwrap();
+        assert_eq!(call(Method::GET, "/api/v1/properties/my", &admin_key, None).await.status(), StatusCode::FORBIDDEN);
+
+        delete_users(&pool, &[owner.id, admin.id]).await;
+    }
+}
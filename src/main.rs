@@ -2,8 +2,20 @@ use actix_web::{App, HttpServer, web, middleware::Logger};
 use actix_cors::Cors;
 use dotenvy::dotenv;
 use std::env;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use airbnb_backend::{db, routes};
+use airbnb_backend::{
+    alerts,
+    auth::JwtKeys,
+    cache::SuggestionCache,
+    config::Config,
+    db,
+    email::{self, EmailOutbox},
+    oidc::OidcClient,
+    routes,
+    storage::{BlobStore, LocalBlobStore},
+};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -28,6 +40,13 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("Failed to create database pool");
 
+    // Listings created before timezones were stored get one from their coordinates
+    match db::backfill_property_timezones(&pool).await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("Derived timezones for {} properties", count),
+        Err(e) => tracing::warn!("Failed to derive property timezones: {}", e),
+    }
+
     // Get server configuration
     let host = env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
     let port = env::var("PORT")
@@ -35,6 +54,68 @@ async fn main() -> std::io::Result<()> {
         .parse::<u16>()
         .expect("PORT must be a valid number");
 
+    let config = Config::from_env().expect("Invalid configuration");
+
+    // Access token signing keys
+    let jwt_keys = web::Data::new(JwtKeys::from_config(&config).expect("Invalid JWT keys"));
+
+    // External sign-in providers
+    let oidc_client = web::Data::new(OidcClient::from_config(&config).expect("Invalid OpenID Connect configuration"));
+
+    // Outbound email is delivered by a background worker
+    let mailer = email::mailer_from_config(&config).expect("Invalid email configuration");
+    let email_outbox = web::Data::new(EmailOutbox::start(mailer));
+
+    // Uploaded photos are stored on the local filesystem
+    let blob_store: Arc<dyn BlobStore> = Arc::new(LocalBlobStore::new(&config.upload_dir));
+    let blob_store = web::Data::from(blob_store);
+    let app_config = web::Data::new(config);
+
+    // In-process cache for location autocomplete
+    let suggestion_cache = web::Data::new(SuggestionCache::new(Duration::from_secs(300), 10_000));
+
+    // Periodically move finished stays to completed so guests can review them,
+    // and publish blind reviews whose review window has closed
+    let sweep_pool = pool.clone();
+    let review_window_days = app_config.review_window_days;
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match db::complete_finished_bookings(&sweep_pool).await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!("Marked {} bookings as completed", count),
+                Err(e) => tracing::warn!("Failed to complete finished bookings: {}", e),
+            }
+            match db::publish_expired_reviews(&sweep_pool, review_window_days).await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!("Published {} reviews after their window closed", count),
+                Err(e) => tracing::warn!("Failed to publish expired reviews: {}", e),
+            }
+        }
+    });
+
+    // Match saved searches against new listings and price changes, and
+    // wishlisted properties against price drops
+    let alert_pool = pool.clone();
+    let alert_interval = Duration::from_secs(app_config.saved_search_alert_interval_secs.max(1));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(alert_interval);
+        loop {
+            interval.tick().await;
+            match alerts::run_saved_search_alerts(&alert_pool).await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!("Sent {} saved search alerts", count),
+                Err(e) => tracing::warn!("Saved search alerts failed: {}", e),
+            }
+            match alerts::run_wishlist_price_alerts(&alert_pool).await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!("Sent {} wishlist price alerts", count),
+                Err(e) => tracing::warn!("Wishlist price alerts failed: {}", e),
+            }
+        }
+    });
+
     tracing::info!("Starting server at {}:{}", host, port);
 
     // Start HTTP server
@@ -50,6 +131,12 @@ async fn main() -> std::io::Result<()> {
             .wrap(cors)
             .wrap(Logger::default())
             .app_data(web::Data::new(pool.clone()))
+            .app_data(suggestion_cache.clone())
+            .app_data(blob_store.clone())
+            .app_data(app_config.clone())
+            .app_data(jwt_keys.clone())
+            .app_data(email_outbox.clone())
+            .app_data(oidc_client.clone())
             .configure(routes::configure_routes)
     })
     .bind((host, port))?
@@ -1,8 +1,9 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use time::{Date, OffsetDateTime};
+use time::{macros::time, Date, OffsetDateTime, Time};
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct User {
@@ -14,6 +15,9 @@ pub struct User {
     #[sqlx(rename = "role")]
     pub role: UserRole,
     pub is_active: bool,
+    pub email_verified_at: Option<OffsetDateTime>,
+    // Set once two-factor authentication has been confirmed
+    pub totp_enabled_at: Option<OffsetDateTime>,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
 }
@@ -41,23 +45,374 @@ pub struct Property {
     pub postal_code: String,
     pub latitude: Option<f64>,
     pub longitude: Option<f64>,
+    // Fuzzed coordinates shown until a guest's booking is approved
+    pub approximate_latitude: Option<f64>,
+    pub approximate_longitude: Option<f64>,
     pub price_per_night: i32, // in cents
     pub max_guests: i32,
     pub bedrooms: i32,
     pub bathrooms: i32,
     pub amenities: Vec<String>,
-    pub images: Vec<String>,
+    pub attributes: PropertyAttributes,
+    pub house_rules: HouseRules,
+    // IANA timezone; check-in/check-out times and deadlines are in this local time
+    pub timezone: String,
+    pub average_rating: Option<f64>,
+    pub review_count: i32,
     pub is_active: bool,
+    pub status: ListingStatus,
+    pub rejection_reason: Option<String>,
+    pub submitted_at: Option<OffsetDateTime>,
+    pub reviewed_at: Option<OffsetDateTime>,
+    pub reviewed_by: Option<Uuid>,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
 }
 
+impl Property {
+    // Current date and time at the property
+    pub fn local_now(&self) -> OffsetDateTime {
+        crate::timezone::now_in(&self.timezone)
+    }
+
+    pub fn local_today(&self) -> Date {
+        self.local_now().date()
+    }
+
+    // When a stay starting on `check_in_date` begins, i.e. the earliest check-in time
+    pub fn stay_starts_at(&self, check_in_date: Date) -> OffsetDateTime {
+        crate::timezone::local_datetime(&self.timezone, check_in_date, self.house_rules.check_in_from)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
-#[sqlx(type_name = "property_type", rename_all = "lowercase")]
+#[sqlx(type_name = "property_type", rename_all = "snake_case")]
 pub enum PropertyType {
     Hotel,
     Hostel,
     Apartment,
+    House,
+    Villa,
+    Cabin,
+    Guesthouse,
+    RoomInHome,
+}
+
+impl PropertyType {
+    // Label of the Postgres `property_type` enum value
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PropertyType::Hotel => "hotel",
+            PropertyType::Hostel => "hostel",
+            PropertyType::Apartment => "apartment",
+            PropertyType::House => "house",
+            PropertyType::Villa => "villa",
+            PropertyType::Cabin => "cabin",
+            PropertyType::Guesthouse => "guesthouse",
+            PropertyType::RoomInHome => "room_in_home",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "hotel" => Some(PropertyType::Hotel),
+            "hostel" => Some(PropertyType::Hostel),
+            "apartment" => Some(PropertyType::Apartment),
+            "house" => Some(PropertyType::House),
+            "villa" => Some(PropertyType::Villa),
+            "cabin" => Some(PropertyType::Cabin),
+            "guesthouse" => Some(PropertyType::Guesthouse),
+            "room_in_home" => Some(PropertyType::RoomInHome),
+            _ => None,
+        }
+    }
+
+    // Keys of PropertyAttributes that apply to this type
+    pub fn attribute_keys(&self) -> &'static [&'static str] {
+        match self {
+            PropertyType::Hotel => &["star_rating", "front_desk_24h"],
+            PropertyType::Hostel => &["dorm_size", "female_only_dorm", "front_desk_24h"],
+            PropertyType::Apartment => &["floor", "has_elevator"],
+            PropertyType::House => &["floors", "has_garden", "has_fireplace"],
+            PropertyType::Villa => &["floors", "has_garden", "has_private_pool"],
+            PropertyType::Cabin => &["has_fireplace", "off_grid"],
+            PropertyType::Guesthouse => &["host_on_site"],
+            PropertyType::RoomInHome => &["host_on_site", "shared_bathroom"],
+        }
+    }
+}
+
+// Type-specific listing details stored as JSONB. Only the keys that apply to the
+// property's type may be set, see PropertyType::attribute_keys.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct PropertyAttributes {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1, max = 5))]
+    pub star_rating: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub front_desk_24h: Option<bool>,
+    // Beds in the largest dorm
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 2, max = 100))]
+    pub dorm_size: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub female_only_dorm: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = -5, max = 200))]
+    pub floor: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_elevator: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1, max = 20))]
+    pub floors: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_garden: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_fireplace: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_private_pool: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub off_grid: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_on_site: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared_bathroom: Option<bool>,
+}
+
+impl PropertyAttributes {
+    // Keys that are set
+    pub fn keys(&self) -> Vec<String> {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(map)) => map.keys().cloned().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Set keys that do not apply to the given property type
+    pub fn keys_not_allowed_for(&self, property_type: &PropertyType) -> Vec<String> {
+        let allowed = property_type.attribute_keys();
+        self.keys()
+            .into_iter()
+            .filter(|key| !allowed.contains(&key.as_str()))
+            .collect()
+    }
+
+    // Parse a search filter like `has_elevator:true,floor:3`
+    pub fn parse_filter(raw: &str) -> Result<Self, String> {
+        let mut map = serde_json::Map::new();
+        for pair in raw.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair
+                .split_once(':')
+                .ok_or_else(|| format!("Expected key:value, got \"{}\"", pair))?;
+            let value = match value.trim() {
+                "true" => serde_json::Value::Bool(true),
+                "false" => serde_json::Value::Bool(false),
+                number => serde_json::Value::from(
+                    number
+                        .parse::<i64>()
+                        .map_err(|_| format!("Invalid value for {}", key.trim()))?,
+                ),
+            };
+            map.insert(key.trim().to_string(), value);
+        }
+
+        serde_json::from_value(serde_json::Value::Object(map)).map_err(|e| e.to_string())
+    }
+}
+
+fn validate_attribute_filter(raw: &str) -> Result<(), ValidationError> {
+    PropertyAttributes::parse_filter(raw)
+        .map(|_| ())
+        .map_err(|_| ValidationError::new("invalid_attribute_filter"))
+}
+
+// Times of day are plain `HH:MM` in property local time
+time::serde::format_description!(hour_minute, Time, "[hour]:[minute]");
+
+// Check-in/check-out times and house rules guests accept when booking
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "validate_house_rules"))]
+pub struct HouseRules {
+    #[serde(with = "hour_minute")]
+    pub check_in_from: Time,
+    // Latest check-in time; may be after midnight
+    #[serde(default, with = "hour_minute::option")]
+    pub check_in_until: Option<Time>,
+    #[serde(with = "hour_minute")]
+    pub check_out_until: Time,
+    pub pets_allowed: bool,
+    pub smoking_allowed: bool,
+    pub events_allowed: bool,
+    pub children_allowed: bool,
+    #[serde(default, with = "hour_minute::option")]
+    pub quiet_hours_start: Option<Time>,
+    #[serde(default, with = "hour_minute::option")]
+    pub quiet_hours_end: Option<Time>,
+    #[validate(length(max = 5000))]
+    pub additional_rules: Option<String>,
+}
+
+impl Default for HouseRules {
+    fn default() -> Self {
+        Self {
+            check_in_from: time!(15:00),
+            check_in_until: None,
+            check_out_until: time!(11:00),
+            pets_allowed: false,
+            smoking_allowed: false,
+            events_allowed: false,
+            children_allowed: true,
+            quiet_hours_start: None,
+            quiet_hours_end: None,
+            additional_rules: None,
+        }
+    }
+}
+
+impl HouseRules {
+    // Short fingerprint of these exact rules; guests send it back when booking
+    // so acceptance is tied to the rules they were shown
+    pub fn version(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        crate::auth::hash_token(&json)[..16].to_string()
+    }
+}
+
+fn validate_house_rules(rules: &HouseRules) -> Result<(), ValidationError> {
+    if rules.check_in_until == Some(rules.check_in_from) {
+        return Err(ValidationError::new("check_in_until must differ from check_in_from"));
+    }
+    match (rules.quiet_hours_start, rules.quiet_hours_end) {
+        (None, None) => Ok(()),
+        (Some(start), Some(end)) if start != end => Ok(()),
+        (Some(_), Some(_)) => Err(ValidationError::new("quiet hours must not start and end at the same time")),
+        _ => Err(ValidationError::new("quiet_hours_start and quiet_hours_end must be given together")),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "listing_status", rename_all = "snake_case")]
+pub enum ListingStatus {
+    Draft,
+    PendingReview,
+    Published,
+    Rejected,
+}
+
+// Minimum listing quality required before a property can be submitted for review
+pub const MIN_TITLE_LENGTH: usize = 10;
+pub const MIN_DESCRIPTION_LENGTH: usize = 50;
+pub const MIN_PHOTOS: i64 = 3;
+
+#[derive(Debug, Serialize)]
+pub struct ChecklistItem {
+    pub key: &'static str,
+    pub description: String,
+    pub complete: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListingChecklist {
+    pub complete: bool,
+    pub items: Vec<ChecklistItem>,
+}
+
+impl ListingChecklist {
+    pub fn for_property(property: &Property, photo_count: i64) -> Self {
+        let items = vec![
+            ChecklistItem {
+                key: "title",
+                description: format!("Title has at least {} characters", MIN_TITLE_LENGTH),
+                complete: property.title.trim().chars().count() >= MIN_TITLE_LENGTH,
+            },
+            ChecklistItem {
+                key: "description",
+                description: format!("Description has at least {} characters", MIN_DESCRIPTION_LENGTH),
+                complete: property.description.trim().chars().count() >= MIN_DESCRIPTION_LENGTH,
+            },
+            ChecklistItem {
+                key: "photos",
+                description: format!("At least {} photos", MIN_PHOTOS),
+                complete: photo_count >= MIN_PHOTOS,
+            },
+            ChecklistItem {
+                key: "amenities",
+                description: "At least one amenity".to_string(),
+                complete: !property.amenities.is_empty(),
+            },
+            ChecklistItem {
+                key: "coordinates",
+                description: "Map location (latitude and longitude) is set".to_string(),
+                complete: property.latitude.is_some() && property.longitude.is_some(),
+            },
+        ];
+
+        ListingChecklist {
+            complete: items.iter().all(|item| item.complete),
+            items,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Amenity {
+    pub key: String,
+    pub label: String,
+    pub category: String,
+    pub icon: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PropertyPhoto {
+    pub id: Uuid,
+    pub property_id: Uuid,
+    // Key prefix in the blob store; None for photos imported as external URLs
+    pub storage_key: Option<String>,
+    pub external_url: Option<String>,
+    pub content_type: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub size_bytes: i64,
+    pub caption: Option<String>,
+    pub position: i32,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PhotoVariant {
+    Original,
+    Small,
+    Medium,
+    Large,
+}
+
+impl PhotoVariant {
+    pub const THUMBNAILS: [PhotoVariant; 3] =
+        [PhotoVariant::Small, PhotoVariant::Medium, PhotoVariant::Large];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PhotoVariant::Original => "original",
+            PhotoVariant::Small => "small",
+            PhotoVariant::Medium => "medium",
+            PhotoVariant::Large => "large",
+        }
+    }
+
+    // Longest edge in pixels; the original is stored untouched
+    pub fn max_dimension(self) -> Option<u32> {
+        match self {
+            PhotoVariant::Original => None,
+            PhotoVariant::Small => Some(320),
+            PhotoVariant::Medium => Some(800),
+            PhotoVariant::Large => Some(1600),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -77,10 +432,15 @@ pub struct Booking {
     pub check_in_date: Date,
     pub check_out_date: Date,
     pub total_price: i32, // in cents
-    #[sqlx(rename = "status")]
     pub status: BookingStatus,
     pub guest_count: i32,
     pub special_requests: Option<String>,
+    // Set when the booking reserves units of a room type instead of the whole property
+    pub room_type_id: Option<Uuid>,
+    pub units: i32,
+    // Snapshot of the house rules the guest accepted; absent on older bookings
+    pub accepted_house_rules: Option<HouseRules>,
+    pub house_rules_accepted_at: Option<OffsetDateTime>,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
 }
@@ -95,6 +455,239 @@ pub enum BookingStatus {
     Completed,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "unit_kind", rename_all = "snake_case")]
+pub enum UnitKind {
+    Room,
+    DormBed,
+}
+
+impl UnitKind {
+    // Label of the Postgres `unit_kind` enum value
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UnitKind::Room => "room",
+            UnitKind::DormBed => "dorm_bed",
+        }
+    }
+}
+
+// A bookable unit type of a hotel or hostel, e.g. "Double room" or "Bed in 8-bed dorm"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomType {
+    pub id: Uuid,
+    pub property_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub kind: UnitKind,
+    pub max_guests: i32, // per unit
+    pub price_per_night: i32, // per unit, in cents
+    pub total_units: i32,
+    pub is_active: bool,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+// Maximum nights covered by one inventory update or calendar request
+pub const MAX_INVENTORY_RANGE_DAYS: i64 = 366;
+
+impl Booking {
+    // Last day on which the stay can still be reviewed
+    pub fn review_deadline(&self, window_days: i64) -> Date {
+        self.check_out_date + time::Duration::days(window_days)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Review {
+    pub id: Uuid,
+    pub booking_id: Uuid,
+    pub property_id: Uuid,
+    pub guest_id: Uuid,
+    pub overall_rating: i16,
+    pub cleanliness_rating: i16,
+    pub accuracy_rating: i16,
+    pub location_rating: i16,
+    pub value_rating: i16,
+    pub comment: Option<String>,
+    // Hidden until the host has reviewed the guest or the review window ends
+    pub published_at: Option<OffsetDateTime>,
+    pub host_reply: Option<String>,
+    pub host_replied_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Wishlist {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    // Present while the wishlist is shared through a read-only link
+    pub share_token: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WishlistItem {
+    pub wishlist_id: Uuid,
+    pub property_id: Uuid,
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub filters: PropertyFilters,
+    pub alerts_enabled: bool,
+    pub last_checked_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    NewListing,
+    PriceDrop,
+}
+
+impl NotificationKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NotificationKind::NewListing => "new_listing",
+            NotificationKind::PriceDrop => "price_drop",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "new_listing" => Some(NotificationKind::NewListing),
+            "price_drop" => Some(NotificationKind::PriceDrop),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: NotificationKind,
+    pub title: String,
+    pub body: String,
+    // Kind-specific payload, e.g. the property and saved search an alert refers to
+    pub data: serde_json::Value,
+    pub read_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+// A notification to store together with the change that triggered it
+#[derive(Debug, Clone)]
+pub struct NewNotification {
+    pub user_id: Uuid,
+    pub kind: NotificationKind,
+    pub title: String,
+    pub body: String,
+    pub data: serde_json::Value,
+}
+
+// A published property on one of the user's wishlists, with its effective
+// price now and the one the user was last alerted about
+#[derive(Debug, Clone)]
+pub struct WatchedPrice {
+    pub user_id: Uuid,
+    pub property_id: Uuid,
+    pub title: String,
+    pub price: i32,
+    pub last_seen_price: Option<i32>,
+}
+
+// One login on one device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub expires_at: OffsetDateTime,
+    pub last_seen_at: OffsetDateTime,
+    pub revoked_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+// A refresh token, stored by hash. Each refresh replaces the session's token.
+#[derive(Debug, Clone)]
+pub struct RefreshToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub session_id: Uuid,
+    pub expires_at: OffsetDateTime,
+    // Set once the token has been exchanged for the next one
+    pub used_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+// Second login step for accounts with two-factor authentication
+#[derive(Debug, Clone)]
+pub struct LoginChallenge {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub failed_attempts: i32,
+    pub expires_at: OffsetDateTime,
+}
+
+// A sign-in started with an external provider
+#[derive(Debug, Clone)]
+pub struct OidcLoginState {
+    pub provider: String,
+    pub code_verifier: String,
+    pub nonce: String,
+}
+
+// Scopes an API key can be given; see `auth::API_KEY_ROUTES` for what each allows
+pub const API_KEY_SCOPES: &[&str] = &[
+    "properties:read",
+    "bookings:read",
+    "bookings:write",
+    "calendar:read",
+    "calendar:write",
+];
+
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    // Public part of the key, shown in listings
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<OffsetDateTime>,
+    pub last_used_at: Option<OffsetDateTime>,
+    pub revoked_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+// A host's review of a guest, published under the same rules as guest reviews
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuestReview {
+    pub id: Uuid,
+    pub booking_id: Uuid,
+    pub guest_id: Uuid,
+    pub host_id: Uuid,
+    pub overall_rating: i16,
+    pub cleanliness_rating: i16,
+    pub communication_rating: i16,
+    pub house_rules_rating: i16,
+    pub comment: Option<String>,
+    pub published_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
 // DTOs for API requests/responses
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateUserRequest {
@@ -119,9 +712,145 @@ pub struct LoginRequest {
 #[derive(Debug, Serialize)]
 pub struct LoginResponse {
     pub token: String,
+    pub refresh_token: String,
+    // Access token lifetime in seconds
+    pub expires_in: i64,
     pub user: UserResponse,
 }
 
+// Returned by login instead of tokens when a two-factor code is still needed
+#[derive(Debug, Serialize)]
+pub struct TwoFactorChallengeResponse {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+    // Challenge lifetime in seconds
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct VerifyTwoFactorRequest {
+    #[validate(length(min = 1))]
+    pub challenge_token: String,
+    // Authenticator code or an unused recovery code
+    #[validate(length(min = 6, max = 32))]
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct TwoFactorCodeRequest {
+    #[validate(length(min = 6, max = 32))]
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorSetupResponse {
+    // Base32 secret for manual entry; `otpauth_url` is the same secret as a QR code payload
+    pub secret: String,
+    pub otpauth_url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OidcAuthorizationResponse {
+    pub authorization_url: String,
+}
+
+// Query parameters the provider redirected back with
+#[derive(Debug, Deserialize, Validate)]
+pub struct OidcCallbackRequest {
+    #[validate(length(min = 1))]
+    pub code: String,
+    #[validate(length(min = 1))]
+    pub state: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RefreshTokenRequest {
+    #[validate(length(min = 1))]
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ForgotPasswordRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct VerifyEmailRequest {
+    #[validate(length(min = 1))]
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordRequest {
+    #[validate(length(min = 1))]
+    pub token: String,
+    #[validate(length(min = 8))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateApiKeyRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(min = 1), custom = "validate_api_key_scopes")]
+    pub scopes: Vec<String>,
+    // Keys without an expiry last until revoked
+    #[validate(range(min = 1, max = 365))]
+    pub expires_in_days: Option<i64>,
+}
+
+fn validate_api_key_scopes(scopes: &[String]) -> Result<(), ValidationError> {
+    if scopes.iter().all(|scope| API_KEY_SCOPES.contains(&scope.as_str())) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("api_key_scope"))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeyResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<OffsetDateTime>,
+    pub last_used_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+// Returned once, when the key is created
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKeyResponse {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKeyResponse,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub last_seen_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    // The session making this request
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    pub token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,
+}
+
 #[derive(Debug, Serialize)]
 pub struct UserResponse {
     pub id: Uuid,
@@ -129,6 +858,8 @@ pub struct UserResponse {
     pub first_name: String,
     pub last_name: String,
     pub role: UserRole,
+    pub email_verified: bool,
+    pub two_factor_enabled: bool,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -159,30 +890,434 @@ pub struct CreatePropertyRequest {
     #[validate(range(min = 1))]
     pub bathrooms: i32,
     pub amenities: Vec<String>,
-    pub images: Vec<String>,
+    #[serde(default)]
+    #[validate]
+    pub attributes: PropertyAttributes,
+    #[serde(default)]
+    #[validate]
+    pub house_rules: HouseRules,
+    // IANA name such as `Europe/Paris`; derived from the coordinates when omitted
+    #[validate(custom = "validate_timezone")]
+    pub timezone: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
+pub struct UpdateTimezoneRequest {
+    // None derives the timezone from the property's coordinates again
+    #[validate(custom = "validate_timezone")]
+    pub timezone: Option<String>,
+}
+
+fn validate_timezone(timezone: &str) -> Result<(), ValidationError> {
+    match crate::timezone::canonical_name(timezone) {
+        Some(_) => Ok(()),
+        None => Err(ValidationError::new("unknown_timezone")),
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateAmenityRequest {
+    #[validate(length(min = 1, max = 50), custom = "validate_amenity_key")]
+    pub key: String,
+    #[validate(length(min = 1, max = 100))]
+    pub label: String,
+    #[validate(length(min = 1, max = 50))]
+    pub category: String,
+    #[validate(length(min = 1, max = 100))]
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateAmenityRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub label: Option<String>,
+    #[validate(length(min = 1, max = 50))]
+    pub category: Option<String>,
+    #[validate(length(min = 1, max = 100))]
+    pub icon: Option<String>,
+}
+
+// Amenity keys are lowercase snake_case identifiers, e.g. "air_conditioning"
+pub fn validate_amenity_key(key: &str) -> Result<(), ValidationError> {
+    let valid = key
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("amenity_key"))
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdatePhotoRequest {
+    #[validate(length(max = 500))]
+    pub caption: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReorderPhotosRequest {
+    // Every photo of the property, in the desired order
+    #[validate(length(min = 1))]
+    pub photo_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RejectListingRequest {
+    #[validate(length(min = 1, max = 2000))]
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateReviewRequest {
+    #[validate(range(min = 1, max = 5))]
+    pub overall_rating: i16,
+    #[validate(range(min = 1, max = 5))]
+    pub cleanliness_rating: i16,
+    #[validate(range(min = 1, max = 5))]
+    pub accuracy_rating: i16,
+    #[validate(range(min = 1, max = 5))]
+    pub location_rating: i16,
+    #[validate(range(min = 1, max = 5))]
+    pub value_rating: i16,
+    #[validate(length(max = 5000))]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateGuestReviewRequest {
+    #[validate(range(min = 1, max = 5))]
+    pub overall_rating: i16,
+    #[validate(range(min = 1, max = 5))]
+    pub cleanliness_rating: i16,
+    #[validate(range(min = 1, max = 5))]
+    pub communication_rating: i16,
+    #[validate(range(min = 1, max = 5))]
+    pub house_rules_rating: i16,
+    #[validate(length(max = 5000))]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReviewReplyRequest {
+    #[validate(length(min = 1, max = 2000))]
+    pub reply: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateWishlistRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateWishlistRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddWishlistItemRequest {
+    pub property_id: Uuid,
+}
+
+// Dates in query strings and request bodies are plain `YYYY-MM-DD`
+time::serde::format_description!(iso_date, Date, "[year]-[month]-[day]");
+
+// Optional stay dates used to report live availability
+#[derive(Debug, Deserialize, Validate)]
+#[validate(schema(function = "validate_stay_dates"))]
+pub struct StayDatesQuery {
+    #[serde(default, with = "iso_date::option")]
+    pub check_in_date: Option<Date>,
+    #[serde(default, with = "iso_date::option")]
+    pub check_out_date: Option<Date>,
+}
+
+impl StayDatesQuery {
+    pub fn dates(&self) -> Option<(Date, Date)> {
+        self.check_in_date.zip(self.check_out_date)
+    }
+}
+
+fn validate_stay_dates(query: &StayDatesQuery) -> Result<(), ValidationError> {
+    validate_date_pair(query.check_in_date, query.check_out_date)
+}
+
+// Stay dates are optional, but must come as a pair with check-out after check-in
+fn validate_date_pair(check_in_date: Option<Date>, check_out_date: Option<Date>) -> Result<(), ValidationError> {
+    match (check_in_date, check_out_date) {
+        (None, None) => Ok(()),
+        (Some(check_in), Some(check_out)) if check_out > check_in => Ok(()),
+        (Some(_), Some(_)) => Err(ValidationError::new("check_out_date must be after check_in_date")),
+        _ => Err(ValidationError::new("check_in_date and check_out_date must be given together")),
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateSavedSearchRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate]
+    pub filters: PropertyFilters,
+    pub alerts_enabled: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateSavedSearchRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: Option<String>,
+    pub alerts_enabled: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct NotificationQuery {
+    pub unread_only: Option<bool>,
+    #[validate(range(min = 1))]
+    pub page: Option<i64>,
+    #[validate(range(min = 1))]
+    pub per_page: Option<i64>,
+}
+
+impl NotificationQuery {
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn per_page(&self) -> i64 {
+        self.per_page.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NotificationListResponse {
+    pub data: Vec<Notification>,
+    pub unread_count: i64,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+    pub total_pages: i64,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PageQuery {
+    #[validate(range(min = 1))]
+    pub page: Option<i64>,
+    #[validate(range(min = 1))]
+    pub per_page: Option<i64>,
+}
+
+impl PageQuery {
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn per_page(&self) -> i64 {
+        self.per_page.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[validate(schema(function = "validate_booking_dates"))]
 pub struct CreateBookingRequest {
     pub property_id: Uuid,
+    #[serde(with = "iso_date")]
+    pub check_in_date: Date,
+    #[serde(with = "iso_date")]
+    pub check_out_date: Date,
     #[validate(range(min = 1))]
     pub guest_count: i32,
     pub special_requests: Option<String>,
+    // Required for properties with room types
+    pub room_type_id: Option<Uuid>,
+    #[validate(range(min = 1, max = 100))]
+    pub units: Option<i32>,
+    // `house_rules_version` of the listing the guest accepted; must match the current rules
+    pub house_rules_version: Option<String>,
+}
+
+fn validate_booking_dates(booking: &CreateBookingRequest) -> Result<(), ValidationError> {
+    validate_date_pair(Some(booking.check_in_date), Some(booking.check_out_date))
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateRoomTypeRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(max = 2000))]
+    pub description: Option<String>,
+    pub kind: Option<UnitKind>,
+    #[validate(range(min = 1, max = 50))]
+    pub max_guests: i32,
+    #[validate(range(min = 1))]
+    pub price_per_night: i32,
+    #[validate(range(min = 1, max = 1000))]
+    pub total_units: i32,
 }
 
 #[derive(Debug, Deserialize, Validate)]
+pub struct UpdateRoomTypeRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: Option<String>,
+    #[validate(length(max = 2000))]
+    pub description: Option<String>,
+    #[validate(range(min = 1, max = 50))]
+    pub max_guests: Option<i32>,
+    #[validate(range(min = 1))]
+    pub price_per_night: Option<i32>,
+    #[validate(range(min = 1, max = 1000))]
+    pub total_units: Option<i32>,
+    pub is_active: Option<bool>,
+}
+
+// Overrides for every night from start_date to end_date inclusive.
+// Leaving both values empty removes the overrides for those nights.
+#[derive(Debug, Deserialize, Validate)]
+#[validate(schema(function = "validate_inventory_range"))]
+pub struct SetInventoryRequest {
+    #[serde(with = "iso_date")]
+    pub start_date: Date,
+    #[serde(with = "iso_date")]
+    pub end_date: Date,
+    #[validate(range(min = 0))]
+    pub units_available: Option<i32>,
+    #[validate(range(min = 1))]
+    pub price_override: Option<i32>,
+}
+
+fn validate_inventory_range(request: &SetInventoryRequest) -> Result<(), ValidationError> {
+    let days = (request.end_date - request.start_date).whole_days();
+    if days < 0 {
+        return Err(ValidationError::new("end_date must not be before start_date"));
+    }
+    if days >= MAX_INVENTORY_RANGE_DAYS {
+        return Err(ValidationError::new("inventory range is too long"));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "validate_filter_dates"))]
 pub struct PropertyFilters {
     pub location: Option<String>,
     pub property_type: Option<PropertyType>,
     pub min_price: Option<i32>,
     pub max_price: Option<i32>,
+    // Number of guests the property must accommodate
     pub max_guests: Option<i32>,
+    // Only properties free for the whole stay
+    #[serde(default, with = "iso_date::option")]
     pub check_in_date: Option<Date>,
+    #[serde(default, with = "iso_date::option")]
     pub check_out_date: Option<Date>,
+    // Comma separated amenity keys; a property must have all of them
+    pub amenities: Option<String>,
+    // Comma separated `key:value` type attributes, e.g. `has_elevator:true,floor:3`
+    #[validate(custom = "validate_attribute_filter")]
+    pub attributes: Option<String>,
+    // House rule filters, e.g. `pets_allowed=true`
+    pub pets_allowed: Option<bool>,
+    pub smoking_allowed: Option<bool>,
+    pub events_allowed: Option<bool>,
+    pub children_allowed: Option<bool>,
+    pub sort: Option<PropertySort>,
+    // Reference point for `sort=distance`
+    #[validate(range(min = -90.0, max = 90.0))]
+    pub lat: Option<f64>,
+    #[validate(range(min = -180.0, max = 180.0))]
+    pub lng: Option<f64>,
+    pub pagination: Option<PaginationMode>,
+    // Opaque cursor from a previous `next_cursor`; implies cursor pagination
+    pub cursor: Option<String>,
+    #[validate(range(min = 1))]
     pub page: Option<i64>,
+    #[validate(range(min = 1))]
     pub per_page: Option<i64>,
 }
 
+pub const DEFAULT_PAGE_SIZE: i64 = 10;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PropertySort {
+    PriceAsc,
+    PriceDesc,
+    #[default]
+    Newest,
+    Rating,
+    Distance,
+}
+
+impl PropertySort {
+    pub fn is_ascending(self) -> bool {
+        matches!(self, PropertySort::PriceAsc | PropertySort::Distance)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PaginationMode {
+    Offset,
+    Cursor,
+}
+
+impl PropertyFilters {
+    pub fn sort(&self) -> PropertySort {
+        self.sort.unwrap_or_default()
+    }
+
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    // Page size is capped server-side regardless of what the client asks for
+    pub fn per_page(&self) -> i64 {
+        self.per_page.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+
+    pub fn uses_cursor(&self) -> bool {
+        self.cursor.is_some() || self.pagination == Some(PaginationMode::Cursor)
+    }
+
+    pub fn amenity_keys(&self) -> Vec<String> {
+        self.amenities
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|key| key.trim().to_lowercase())
+            .filter(|key| !key.is_empty())
+            .collect()
+    }
+
+    // Parsed attribute filter; None when absent or empty
+    pub fn attribute_filter(&self) -> Option<PropertyAttributes> {
+        self.attributes
+            .as_deref()
+            .and_then(|raw| PropertyAttributes::parse_filter(raw).ok())
+            .filter(|attributes| *attributes != PropertyAttributes::default())
+    }
+
+    pub fn stay_dates(&self) -> Option<(Date, Date)> {
+        self.check_in_date.zip(self.check_out_date)
+    }
+
+    // The filtering part alone, as stored on a saved search
+    pub fn without_paging(&self) -> PropertyFilters {
+        PropertyFilters {
+            pagination: None,
+            cursor: None,
+            page: None,
+            per_page: None,
+            ..self.clone()
+        }
+    }
+}
+
+fn validate_filter_dates(filters: &PropertyFilters) -> Result<(), ValidationError> {
+    validate_date_pair(filters.check_in_date, filters.check_out_date)
+}
+
 #[derive(Debug, Serialize)]
 pub struct PaginatedResponse<T> {
     pub data: Vec<T>,
@@ -192,6 +1327,42 @@ pub struct PaginatedResponse<T> {
     pub total_pages: i64,
 }
 
+#[derive(Debug, Serialize)]
+pub struct CursorPaginatedResponse<T> {
+    pub data: Vec<T>,
+    pub per_page: i64,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+// Position of the last row of a cursor page: its sort key plus id as a tie-breaker
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ListingCursor {
+    pub sort: PropertySort,
+    pub key: CursorKey,
+    pub id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum CursorKey {
+    CreatedAt(#[serde(with = "time::serde::rfc3339")] OffsetDateTime),
+    Price(i32),
+    Rating(f64),
+    Distance(f64),
+}
+
+impl ListingCursor {
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let json = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct PropertyResponse {
     pub id: Uuid,
@@ -199,16 +1370,191 @@ pub struct PropertyResponse {
     pub description: String,
     pub property_type: PropertyType,
     pub location: String,
-    pub address: String,
+    // Only shown to the owner, admins and guests with an approved booking
+    pub address: Option<String>,
     pub city: String,
     pub country: String,
+    // Approximate unless `exact_location` is set
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub exact_location: bool,
     pub price_per_night: i32,
     pub max_guests: i32,
     pub bedrooms: i32,
     pub bathrooms: i32,
     pub amenities: Vec<String>,
-    pub images: Vec<String>,
-    pub owner: UserResponse,
+    pub attributes: PropertyAttributes,
+    pub house_rules: HouseRules,
+    pub house_rules_version: String,
+    pub timezone: String,
+    pub photos: Vec<PhotoResponse>,
+    pub average_rating: Option<f64>,
+    pub review_count: i32,
+    pub owner: HostResponse,
+}
+
+// Public view of a host; contact details only alongside the exact location
+#[derive(Debug, Serialize)]
+pub struct HostResponse {
+    pub id: Uuid,
+    pub first_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoomTypeResponse {
+    #[serde(flatten)]
+    pub room_type: RoomType,
+    // Only reported when stay dates were requested
+    pub availability: Option<RoomTypeAvailability>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoomTypeAvailability {
+    pub available_units: i32,
+    pub nights: i64,
+    // Price of one unit for the whole stay, in cents
+    pub price_per_unit: i64,
+}
+
+// One night of a room type's inventory calendar
+#[derive(Debug, Serialize)]
+pub struct InventoryDay {
+    pub date: Date,
+    pub total_units: i32,
+    pub booked_units: i32,
+    pub available_units: i32,
+    pub price_per_night: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WishlistSummaryResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub item_count: i64,
+    pub share_token: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WishlistResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub share_token: Option<String>,
+    pub properties: Vec<WishlistPropertyResponse>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WishlistPropertyResponse {
+    #[serde(flatten)]
+    pub property: PropertyResponse,
+    pub added_at: OffsetDateTime,
+    // Only reported when stay dates were requested
+    pub available: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReviewResponse {
+    pub id: Uuid,
+    pub booking_id: Uuid,
+    pub overall_rating: i16,
+    pub cleanliness_rating: i16,
+    pub accuracy_rating: i16,
+    pub location_rating: i16,
+    pub value_rating: i16,
+    pub comment: Option<String>,
+    pub reviewer: ReviewerResponse,
+    pub host_reply: Option<HostReplyResponse>,
+    pub published_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HostReplyResponse {
+    pub reply: String,
+    pub replied_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GuestReviewResponse {
+    pub id: Uuid,
+    pub booking_id: Uuid,
+    pub overall_rating: i16,
+    pub cleanliness_rating: i16,
+    pub communication_rating: i16,
+    pub house_rules_rating: i16,
+    pub comment: Option<String>,
+    pub host: ReviewerResponse,
+    pub published_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+// Published host reviews of a guest, summarized for hosts weighing a request
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GuestRatingSummary {
+    pub average_rating: Option<f64>,
+    pub review_count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PropertyBookingResponse {
+    #[serde(flatten)]
+    pub booking: Booking,
+    pub guest_rating: GuestRatingSummary,
+}
+
+// Public view of a reviewer: first name only
+#[derive(Debug, Serialize)]
+pub struct ReviewerResponse {
+    pub id: Uuid,
+    pub first_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PhotoResponse {
+    pub id: Uuid,
+    pub caption: Option<String>,
+    pub position: i32,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub urls: PhotoUrls,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PhotoUrls {
+    pub original: String,
+    pub small: String,
+    pub medium: String,
+    pub large: String,
+}
+
+impl From<PropertyPhoto> for PhotoResponse {
+    fn from(photo: PropertyPhoto) -> Self {
+        let url = |variant: PhotoVariant| match &photo.external_url {
+            Some(external_url) if photo.storage_key.is_none() => external_url.clone(),
+            _ => format!("/api/v1/photos/{}/{}", photo.id, variant.as_str()),
+        };
+
+        let urls = PhotoUrls {
+            original: url(PhotoVariant::Original),
+            small: url(PhotoVariant::Small),
+            medium: url(PhotoVariant::Medium),
+            large: url(PhotoVariant::Large),
+        };
+
+        PhotoResponse {
+            id: photo.id,
+            caption: photo.caption,
+            position: photo.position,
+            width: photo.width,
+            height: photo.height,
+            urls,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -222,5 +1568,34 @@ pub struct BookingResponse {
     pub status: BookingStatus,
     pub guest_count: i32,
     pub special_requests: Option<String>,
+    pub room_type_id: Option<Uuid>,
+    pub units: i32,
+    pub accepted_house_rules: Option<HouseRules>,
+    pub house_rules_accepted_at: Option<OffsetDateTime>,
     pub created_at: OffsetDateTime,
 }
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SearchSuggestQuery {
+    #[validate(length(min = 1, max = 100))]
+    pub q: String,
+    #[validate(range(min = 1, max = 20))]
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SuggestionKind {
+    City,
+    Country,
+    Location,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchSuggestion {
+    pub kind: SuggestionKind,
+    pub value: String,
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub listing_count: i64,
+    pub score: f32,
+}
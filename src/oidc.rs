@@ -0,0 +1,247 @@
+use crate::{
+    config::{Config, OidcProviderConfig},
+    error::AppError,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use reqwest::Url;
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+// Endpoints from the provider's discovery document
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+// The ID token claims used to find or create the account
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default, deserialize_with = "bool_or_string")]
+    pub email_verified: bool,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+    pub name: Option<String>,
+    nonce: Option<String>,
+}
+
+// Some providers send `"email_verified": "true"`
+fn bool_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BoolOrString {
+        Bool(bool),
+        String(String),
+    }
+
+    Ok(match BoolOrString::deserialize(deserializer)? {
+        BoolOrString::Bool(value) => value,
+        BoolOrString::String(value) => value == "true",
+    })
+}
+
+struct OidcProvider {
+    config: OidcProviderConfig,
+    redirect_url: String,
+    // Fetched on first use
+    metadata: OnceCell<ProviderMetadata>,
+}
+
+// Authorization code flow with PKCE against the configured OpenID Connect providers
+pub struct OidcClient {
+    http: reqwest::Client,
+    providers: HashMap<String, OidcProvider>,
+}
+
+impl OidcClient {
+    pub fn from_config(config: &Config) -> Result<Self, AppError> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))?;
+
+        let providers = config
+            .oidc_providers
+            .iter()
+            .map(|provider| {
+                let redirect_url = provider.redirect_url.clone().unwrap_or_else(|| {
+                    format!("{}/auth/callback/{}", config.app_base_url.trim_end_matches('/'), provider.name)
+                });
+                let provider = OidcProvider { config: provider.clone(), redirect_url, metadata: OnceCell::new() };
+                (provider.config.name.clone(), provider)
+            })
+            .collect();
+
+        Ok(OidcClient { http, providers })
+    }
+
+    fn provider(&self, name: &str) -> Result<&OidcProvider, AppError> {
+        self.providers
+            .get(name)
+            .ok_or_else(|| AppError::NotFound(format!("Unknown sign-in provider: {}", name)))
+    }
+
+    async fn metadata<'a>(&self, provider: &'a OidcProvider) -> Result<&'a ProviderMetadata, AppError> {
+        provider
+            .metadata
+            .get_or_try_init(|| async {
+                let issuer = provider.config.issuer.trim_end_matches('/');
+                let metadata: ProviderMetadata = self
+                    .get_json(&format!("{}/.well-known/openid-configuration", issuer))
+                    .await?;
+                if metadata.issuer.trim_end_matches('/') != issuer {
+                    return Err(AppError::Internal(format!(
+                        "Provider {} reports issuer {}",
+                        provider.config.name, metadata.issuer
+                    )));
+                }
+                Ok(metadata)
+            })
+            .await
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, AppError> {
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AppError::Internal(format!("Failed to fetch {}: {}", url, e)))?
+            .json()
+            .await
+            .map_err(|e| AppError::Internal(format!("Invalid response from {}: {}", url, e)))
+    }
+
+    // Where to send the user to sign in. `state`, `nonce` and `code_verifier`
+    // must be kept to finish the sign-in.
+    pub async fn authorization_url(
+        &self,
+        provider_name: &str,
+        state: &str,
+        nonce: &str,
+        code_verifier: &str,
+    ) -> Result<String, AppError> {
+        let provider = self.provider(provider_name)?;
+        let metadata = self.metadata(provider).await?;
+
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", provider.config.client_id.as_str()),
+                ("redirect_uri", provider.redirect_url.as_str()),
+                ("scope", provider.config.scopes.as_str()),
+                ("state", state),
+                ("nonce", nonce),
+                ("code_challenge", pkce_challenge(code_verifier).as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| AppError::Internal(format!("Invalid authorization endpoint: {}", e)))?;
+
+        Ok(url.into())
+    }
+
+    // Exchanges the code from the provider's redirect and returns the verified
+    // ID token claims
+    pub async fn exchange_code(
+        &self,
+        provider_name: &str,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, AppError> {
+        let provider = self.provider(provider_name)?;
+        let metadata = self.metadata(provider).await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", provider.redirect_url.as_str()),
+            ("client_id", provider.config.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(secret) = &provider.config.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to reach {}: {}", metadata.token_endpoint, e)))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            tracing::warn!("Code exchange with {} failed ({}): {}", provider_name, status, body);
+            return Err(AppError::Authentication(format!("Sign-in with {} failed", provider_name)));
+        }
+        let tokens: TokenResponse = response
+            .json()
+            .await
+            .map_err(|e| AppError::Internal(format!("Invalid token response from {}: {}", provider_name, e)))?;
+
+        let claims = self.verify_id_token(provider, metadata, &tokens.id_token).await?;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(AppError::Authentication("ID token nonce does not match".to_string()));
+        }
+
+        Ok(claims)
+    }
+
+    async fn verify_id_token(
+        &self,
+        provider: &OidcProvider,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+    ) -> Result<IdTokenClaims, AppError> {
+        let invalid = || AppError::Authentication("Invalid ID token".to_string());
+
+        let header = decode_header(id_token).map_err(|_| invalid())?;
+        // Only the provider's published keys are trusted, never a shared secret
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            return Err(invalid());
+        }
+
+        // Fetched every time, so keys the provider rotates in are picked up
+        let jwks: JwkSet = self.get_json(&metadata.jwks_uri).await?;
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or_else(invalid)?;
+        let key = DecodingKey::from_jwk(jwk).map_err(|_| invalid())?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&provider.config.client_id]);
+
+        decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| {
+                tracing::warn!("Rejected ID token from {}: {}", provider.config.name, e);
+                invalid()
+            })
+    }
+}
+
+// S256 code challenge for a PKCE verifier
+pub fn pkce_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(digest(&SHA256, code_verifier.as_bytes()))
+}
@@ -2,30 +2,105 @@ use crate::handlers::*;
 use actix_web::web;
 
 pub fn configure_routes(cfg: &mut web::ServiceConfig) {
-    // Public routes (no authentication required)
+    cfg.route("/.well-known/jwks.json", web::get().to(get_jwks));
+
+    // A single scope: actix matches the first scope with a matching prefix, so a
+    // second "/api/v1" scope would never be reached. Handlers authenticate through
+    // their AuthUser / RequireOwner / RequireAdmin arguments; API keys only reach
+    // the routes listed in `auth::API_KEY_ROUTES`.
     cfg.service(
         web::scope("/api/v1")
+            // Public routes (no authentication required)
             .route("/auth/register", web::post().to(register))
             .route("/auth/login", web::post().to(login))
+            .route("/auth/refresh", web::post().to(refresh))
+            .route("/auth/logout", web::post().to(logout))
+            .route("/auth/verify-email", web::post().to(verify_email))
+            .route("/auth/2fa/verify", web::post().to(verify_two_factor_login))
+            .route("/auth/oidc/{provider}/authorize", web::get().to(oidc_authorize))
+            .route("/auth/oidc/{provider}/callback", web::post().to(oidc_callback))
+            .route("/auth/forgot-password", web::post().to(forgot_password))
+            .route("/auth/reset-password", web::post().to(reset_password))
             .route("/properties", web::get().to(get_properties))
+            // Registered before /properties/{id} so "my" is not taken for an id
+            .route("/properties/my", web::get().to(get_my_properties))
             .route("/properties/{id}", web::get().to(get_property))
-    )
-    
-    // Protected routes (authentication required)
-    .service(
-        web::scope("/api/v1")
+            .route("/search/suggest", web::get().to(search_suggestions))
+            .route("/amenities", web::get().to(get_amenity_catalog))
+            .route("/properties/{id}/photos", web::get().to(get_property_photos))
+            .route("/properties/{id}/reviews", web::get().to(get_property_reviews))
+            .route("/properties/{id}/room-types", web::get().to(get_property_room_types))
+            .route("/photos/{id}/{variant}", web::get().to(serve_photo))
+            .route("/shared/wishlists/{token}", web::get().to(get_shared_wishlist))
+
+            // Protected routes (authentication required)
+            .route("/auth/logout-all", web::post().to(logout_all))
+            .route("/auth/resend-verification", web::post().to(resend_verification_email))
+            .route("/auth/2fa/setup", web::post().to(setup_two_factor))
+            .route("/auth/2fa/confirm", web::post().to(confirm_two_factor))
+            .route("/auth/2fa/disable", web::post().to(disable_two_factor_handler))
+            .route("/auth/2fa/recovery-codes", web::post().to(regenerate_recovery_codes))
+            .route("/me/sessions", web::get().to(get_my_sessions))
+            .route("/me/sessions/{id}", web::delete().to(revoke_my_session))
             .route("/bookings", web::post().to(create_booking))
             .route("/bookings", web::get().to(get_my_bookings))
             .route("/bookings/{id}/cancel", web::post().to(cancel_booking))
+            .route("/bookings/{id}/review", web::post().to(create_review))
+
+            // Wishlists
+            .route("/wishlists", web::post().to(create_wishlist_handler))
+            .route("/wishlists", web::get().to(get_my_wishlists))
+            .route("/wishlists/{id}", web::get().to(get_wishlist))
+            .route("/wishlists/{id}", web::put().to(update_wishlist))
+            .route("/wishlists/{id}", web::delete().to(delete_wishlist_handler))
+            .route("/wishlists/{id}/properties", web::post().to(add_wishlist_property))
+            .route("/wishlists/{id}/properties/{property_id}", web::delete().to(remove_wishlist_property))
+            .route("/wishlists/{id}/share", web::post().to(share_wishlist))
+            .route("/wishlists/{id}/share", web::delete().to(unshare_wishlist))
+
+            // Saved searches and notifications
+            .route("/saved-searches", web::post().to(create_saved_search_handler))
+            .route("/saved-searches", web::get().to(get_my_saved_searches))
+            .route("/saved-searches/{id}", web::put().to(update_saved_search_handler))
+            .route("/saved-searches/{id}", web::delete().to(delete_saved_search_handler))
+            .route("/notifications", web::get().to(get_my_notifications))
+            .route("/notifications/read-all", web::post().to(read_all_notifications))
+            .route("/notifications/{id}/read", web::post().to(read_notification))
             
             // Property owner routes
             .route("/properties", web::post().to(create_property))
-            .route("/properties/my", web::get().to(get_my_properties))
+            .route("/me/api-keys", web::post().to(create_api_key_handler))
+            .route("/me/api-keys", web::get().to(get_my_api_keys))
+            .route("/me/api-keys/{id}", web::delete().to(revoke_api_key_handler))
             .route("/properties/{id}/bookings", web::get().to(get_property_bookings))
+            .route("/properties/{id}/house-rules", web::put().to(update_house_rules_handler))
+            .route("/properties/{id}/timezone", web::put().to(update_property_timezone_handler))
+            .route("/properties/{id}/checklist", web::get().to(get_listing_checklist))
+            .route("/properties/{id}/submit", web::post().to(submit_property))
+            .route("/properties/{id}/photos", web::post().to(upload_property_photo))
+            .route("/properties/{id}/photos/reorder", web::put().to(reorder_property_photos))
+            .route("/properties/{id}/photos/{photo_id}", web::put().to(update_property_photo))
+            .route("/properties/{id}/photos/{photo_id}", web::delete().to(delete_property_photo))
+            .route("/properties/{id}/room-types", web::post().to(create_room_type_handler))
+            .route("/properties/{id}/room-types/{room_type_id}", web::put().to(update_room_type_handler))
+            .route("/properties/{id}/room-types/{room_type_id}", web::delete().to(deactivate_room_type))
+            .route("/properties/{id}/room-types/{room_type_id}/inventory", web::get().to(get_room_type_calendar))
+            .route("/properties/{id}/room-types/{room_type_id}/inventory", web::put().to(set_room_type_inventory_handler))
             .route("/bookings/{id}/approve", web::post().to(approve_booking))
             .route("/bookings/{id}/deny", web::post().to(deny_booking))
+            .route("/bookings/{id}/guest-review", web::post().to(create_guest_review))
+            .route("/bookings/{id}/guest-reviews", web::get().to(get_booking_guest_reviews))
+            .route("/reviews/{id}/reply", web::post().to(reply_to_review))
             
             // Admin routes
             .route("/admin/property-owners", web::post().to(create_property_owner))
+            .route("/admin/users/{id}/sessions", web::delete().to(revoke_user_sessions_handler))
+            .route("/admin/users/{id}/login-lock", web::delete().to(unlock_user_login))
+            .route("/admin/moderation/queue", web::get().to(get_moderation_queue))
+            .route("/admin/properties/{id}/approve", web::post().to(approve_property))
+            .route("/admin/properties/{id}/reject", web::post().to(reject_property))
+            .route("/admin/amenities", web::post().to(create_amenity_handler))
+            .route("/admin/amenities/{key}", web::put().to(update_amenity_handler))
+            .route("/admin/amenities/{key}", web::delete().to(delete_amenity_handler))
     );
 }
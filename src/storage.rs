@@ -0,0 +1,80 @@
+use crate::error::AppError;
+use async_trait::async_trait;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+// Storage backend for uploaded files, addressed by slash-separated keys
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), AppError>;
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AppError>;
+
+    // Deleting a missing key is not an error
+    async fn delete(&self, key: &str) -> Result<(), AppError>;
+}
+
+// Stores blobs as files below a root directory
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalBlobStore { root: root.into() }
+    }
+
+    fn path_for(&self, key: &str) -> Result<PathBuf, AppError> {
+        if !is_valid_key(key) {
+            return Err(AppError::Internal(format!("Invalid storage key: {}", key)));
+        }
+        Ok(self.root.join(Path::new(key)))
+    }
+}
+
+// Keys are generated server-side, but never let one escape the storage root
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.split('/').all(|segment| {
+            !segment.is_empty()
+                && segment != "."
+                && segment != ".."
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        })
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), AppError> {
+        let path = self.path_for(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| AppError::Internal(format!("Failed to create storage directory: {}", e)))?;
+        }
+
+        tokio::fs::write(&path, bytes)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to write blob: {}", e)))
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AppError> {
+        let path = self.path_for(key)?;
+        match tokio::fs::read(&path).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(AppError::Internal(format!("Failed to read blob: {}", e))),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), AppError> {
+        let path = self.path_for(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(AppError::Internal(format!("Failed to delete blob: {}", e))),
+        }
+    }
+}
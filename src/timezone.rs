@@ -0,0 +1,122 @@
+use std::sync::OnceLock;
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time};
+use time_tz::{timezones, OffsetDateTimeExt, OffsetResult, PrimitiveDateTimeExt, TimeZone, Tz};
+
+// Used for properties whose timezone is not known
+pub const DEFAULT_TIMEZONE: &str = "UTC";
+
+// Principal locations of tzdb timezones (public domain, from the tz database)
+const ZONE_TAB: &str = include_str!("../data/zone.tab");
+
+// Beyond this distance from any principal location (open sea, remote islands)
+// the zone is guessed from the longitude instead
+const MAX_NEAREST_ZONE_KM: f64 = 1500.0;
+
+struct ZoneLocation {
+    latitude: f64,
+    longitude: f64,
+    name: &'static str,
+}
+
+fn zone_locations() -> &'static [ZoneLocation] {
+    static LOCATIONS: OnceLock<Vec<ZoneLocation>> = OnceLock::new();
+    LOCATIONS.get_or_init(|| {
+        ZONE_TAB
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut columns = line.split('\t');
+                let coordinates = columns.nth(1)?;
+                let name = columns.next()?;
+                let (latitude, longitude) = parse_iso6709(coordinates)?;
+                Some(ZoneLocation { latitude, longitude, name })
+            })
+            .collect()
+    })
+}
+
+// `±DDMM±DDDMM` or `±DDMMSS±DDDMMSS`
+fn parse_iso6709(value: &str) -> Option<(f64, f64)> {
+    let split = value[1..].find(['+', '-'])? + 1;
+    let (latitude, longitude) = value.split_at(split);
+    Some((parse_degrees(latitude, 2)?, parse_degrees(longitude, 3)?))
+}
+
+fn parse_degrees(value: &str, degree_digits: usize) -> Option<f64> {
+    let sign = if value.starts_with('-') { -1.0 } else { 1.0 };
+    let digits = &value[1..];
+    let part = |range: std::ops::Range<usize>| digits.get(range).map_or(Ok(0.0), str::parse::<f64>);
+
+    let degrees = part(0..degree_digits).ok()?;
+    let minutes = part(degree_digits..degree_digits + 2).ok()?;
+    let seconds = part(degree_digits + 2..degree_digits + 4).ok()?;
+    Some(sign * (degrees + minutes / 60.0 + seconds / 3600.0))
+}
+
+// Great-circle distance in kilometres
+fn distance_km(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lng = (lng2 - lng1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+    6371.0 * 2.0 * a.sqrt().min(1.0).asin()
+}
+
+// Best offline guess of the IANA timezone at the given coordinates: the zone of
+// the nearest principal location. Near borders this can pick a neighbouring zone,
+// which is why owners can set the timezone explicitly.
+pub fn lookup(latitude: f64, longitude: f64) -> String {
+    let nearest = zone_locations()
+        .iter()
+        .map(|zone| (distance_km(latitude, longitude, zone.latitude, zone.longitude), zone.name))
+        .min_by(|a, b| a.0.total_cmp(&b.0));
+
+    match nearest {
+        Some((distance, name)) if distance <= MAX_NEAREST_ZONE_KM => name.to_string(),
+        _ => {
+            // Etc/GMT zones have inverted signs: Etc/GMT-3 is UTC+3
+            let offset = (longitude / 15.0).round() as i32;
+            match offset {
+                0 => "Etc/GMT".to_string(),
+                offset if offset > 0 => format!("Etc/GMT-{}", offset.min(14)),
+                offset => format!("Etc/GMT+{}", (-offset).min(12)),
+            }
+        }
+    }
+}
+
+// Timezone to store for a property: the owner's choice, otherwise derived from
+// the coordinates. None when neither is known.
+pub fn resolve(explicit: Option<&str>, latitude: Option<f64>, longitude: Option<f64>) -> Option<String> {
+    match (explicit.and_then(canonical_name), latitude, longitude) {
+        (Some(name), _, _) => Some(name.to_string()),
+        (None, Some(latitude), Some(longitude)) => Some(lookup(latitude, longitude)),
+        _ => None,
+    }
+}
+
+// Canonical name of a known IANA timezone
+pub fn canonical_name(name: &str) -> Option<&'static str> {
+    timezones::get_by_name(name).map(|tz| tz.name())
+}
+
+fn find(name: &str) -> &'static Tz {
+    timezones::get_by_name(name).unwrap_or(timezones::db::UTC)
+}
+
+// Current date and time in the given timezone
+pub fn now_in(name: &str) -> OffsetDateTime {
+    OffsetDateTime::now_utc().to_timezone(find(name))
+}
+
+// A local wall-clock time in the given timezone. Times repeated by a DST change
+// resolve to the earlier instant; skipped times use the offset in effect at the
+// same wall-clock time in UTC.
+pub fn local_datetime(name: &str, date: Date, time: Time) -> OffsetDateTime {
+    let tz = find(name);
+    let local = PrimitiveDateTime::new(date, time);
+    match local.assume_timezone(tz) {
+        OffsetResult::Some(datetime) | OffsetResult::Ambiguous(datetime, _) => datetime,
+        OffsetResult::None => local.assume_timezone_utc(tz),
+    }
+}